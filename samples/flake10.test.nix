@@ -0,0 +1,23 @@
+{
+  description = "Inputs declared as attribute sets";
+
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+  inputs.foo = {
+    inputs.nixpkgs.follows = "nixpkgs";
+    url = "github:example/foo";
+  };
+  inputs = {
+    bar = {
+      url = "github:example/bar";
+      flake = false;
+    };
+    baz = {
+      inputs = {
+        nixpkgs.follows = "nixpkgs";
+      };
+      url = "github:example/baz";
+    };
+  };
+
+  outputs = { self, nixpkgs, foo, bar, baz }: { };
+}
@@ -0,0 +1,19 @@
+{
+  description = "A flake that uses flake-utils";
+
+  inputs = {
+    nixpkgs.url = "github:NixOS/nixpkgs/nixos-23.05";
+    systems.url = "github:nix-systems/default";
+    flake-utils = {
+      url = "github:numtide/flake-utils";
+      inputs.systems.follows = "systems";
+    };
+    flake-utils-pinned.url = "github:numtide/flake-utils/v1.0.0";
+    flake-utils-pinned.inputs.systems.follows = "systems";
+  };
+
+  outputs = { self, nixpkgs, flake-utils, ... }:
+    flake-utils.lib.eachDefaultSystem (system: {
+      packages.default = nixpkgs.legacyPackages.${system}.hello;
+    });
+}
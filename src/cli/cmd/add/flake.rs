@@ -157,6 +157,33 @@ pub(crate) fn find_first_attrset_by_path(
         .next())
 }
 
+// An `inherit` can't be looked into, so it's only a problem when it brings in the attribute that's
+// being searched for, like `inherit inputs;` when looking for `inputs.nixpkgs.url`. Any other
+// `inherit` is skipped.
+pub(crate) fn check_inherit(
+    inherit: &nixel::BindingInherit,
+    searched_attr: Option<&str>,
+) -> color_eyre::Result<()> {
+    let Some(searched_attr) = searched_attr else {
+        return Ok(());
+    };
+
+    let shadows_searched_attr = inherit
+        .attributes
+        .iter()
+        .any(|attr| matches!(attr, nixel::Part::Raw(raw) if raw.content.trim() == searched_attr));
+    if shadows_searched_attr {
+        let start = &inherit.span.start;
+        return Err(color_eyre::eyre::eyre!(
+            "`{searched_attr}` comes from an `inherit`, which is not supported (at {}:{})",
+            start.line,
+            start.column
+        ));
+    }
+
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) fn find_all_attrsets_by_path(
     expr: &nixel::Expression,
@@ -170,12 +197,16 @@ pub(crate) fn find_all_attrsets_by_path(
                 match binding {
                     nixel::Binding::KeyValue(kv) => {
                         if let Some(ref attr_path) = attr_path {
-                            // Transform `inputs.nixpkgs.url` into `["inputs", "nixpkgs", "url"]`
+                            // Transform `inputs.nixpkgs.url` into `["inputs", "nixpkgs", "url"]`. The
+                            // last part can carry the whitespace before `=`, as in `inputs.foo = {`,
+                            // which would otherwise keep the `url` inside from ever being found.
                             let mut this_attr_path: VecDeque<(String, &nixel::PartRaw)> = kv
                                 .from
                                 .iter()
                                 .filter_map(|attr| match attr {
-                                    nixel::Part::Raw(raw) => Some((raw.content.to_string(), raw)),
+                                    nixel::Part::Raw(raw) => {
+                                        Some((raw.content.trim().to_string(), raw))
+                                    }
                                     _ => None,
                                 })
                                 .collect();
@@ -234,12 +265,13 @@ pub(crate) fn find_all_attrsets_by_path(
                         }
                     }
                     nixel::Binding::Inherit(inherit) => {
-                        let start = &inherit.span.start;
-                        return Err(color_eyre::eyre::eyre!(
-                            "`inherit` not supported (at {}:{})",
-                            start.line,
-                            start.column
-                        ));
+                        check_inherit(
+                            inherit,
+                            attr_path
+                                .as_ref()
+                                .and_then(|attr_path| attr_path.front())
+                                .map(String::as_str),
+                        )?;
                     }
                 }
             }
@@ -690,6 +722,593 @@ pub(crate) fn replace_input_value_uri(
     Ok(new_flake_contents)
 }
 
+// Nix keywords, which can't be used as bare attribute names or function arguments
+const NIX_KEYWORDS: &[&str] = &[
+    "assert", "else", "if", "in", "inherit", "let", "or", "rec", "then", "with",
+];
+
+/// Whether `name` can be used as an input name without quoting, and so also as an argument of
+/// the `outputs` function.
+pub(crate) fn is_valid_input_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || ['_', '\'', '-'].contains(&c))
+        && !NIX_KEYWORDS.contains(&name)
+}
+
+/// An input to rename, written as `old=new`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct InputRename {
+    pub(crate) from: String,
+    pub(crate) to: String,
+}
+
+impl std::str::FromStr for InputRename {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((from, to)) = s.split_once('=') else {
+            return Err(color_eyre::eyre::eyre!(
+                "`{s}` is not a rename; use the form `old=new`"
+            ));
+        };
+
+        if from.is_empty() {
+            return Err(color_eyre::eyre::eyre!(
+                "`{s}` is missing the input to rename"
+            ));
+        }
+        if !is_valid_input_name(to) {
+            return Err(color_eyre::eyre::eyre!(
+                "`{to}` is not a valid input name; it must start with a letter or `_`, contain only \
+                letters, digits, `_`, `'` and `-`, and not be a Nix keyword"
+            ));
+        }
+
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+}
+
+/// An input of the added flake to make follow one of ours, written as `parent=input`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct InputFollows {
+    /// The input to follow, like `nixpkgs` or `flake-utils/systems`.
+    pub(crate) parent: String,
+    /// The added flake's own input that should follow `parent`.
+    pub(crate) input: String,
+}
+
+impl std::str::FromStr for InputFollows {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((parent, input)) = s.split_once('=') else {
+            return Err(color_eyre::eyre::eyre!(
+                "`{s}` is not a follows; use the form `parent=input`"
+            ));
+        };
+
+        if parent.is_empty() || parent.split('/').any(|part| !is_valid_input_name(part)) {
+            return Err(color_eyre::eyre::eyre!(
+                "`{parent}` is not an input to follow, like `nixpkgs` or `flake-utils/systems`"
+            ));
+        }
+        if !is_valid_input_name(input) {
+            return Err(color_eyre::eyre::eyre!(
+                "`{input}` is not a valid input name"
+            ));
+        }
+
+        Ok(Self {
+            parent: parent.to_string(),
+            input: input.to_string(),
+        })
+    }
+}
+
+/// Writes `inputs.<name>.inputs.<input>.follows = "<parent>";` for each of `follows`, right below
+/// the `url` of the input, in the same style and indentation.
+#[tracing::instrument(skip_all, fields(flake_input_name = %flake_input_name))]
+pub(crate) fn insert_input_follows(
+    expr: &nixel::Expression,
+    flake_contents: &str,
+    flake_input_name: &str,
+    follows: &[InputFollows],
+) -> color_eyre::Result<String> {
+    let input_url_attr_path: VecDeque<String> = [
+        String::from("inputs"),
+        flake_input_name.to_string(),
+        String::from("url"),
+    ]
+    .into();
+    let Some(url_attr) = find_first_attrset_by_path(expr, Some(input_url_attr_path))? else {
+        return Err(color_eyre::eyre::eyre!(
+            "there is no `inputs.{flake_input_name}.url` to add follows to"
+        ));
+    };
+
+    // Whatever comes before `url`: `inputs.nixpkgs`, `nixpkgs` (in `inputs = { ... }`), or
+    // nothing (in `inputs.nixpkgs = { ... }`)
+    let prefix = url_attr.from[..url_attr.from.len() - 1]
+        .iter()
+        .map(|part| match part {
+            nixel::Part::Raw(raw) => Ok(raw.content.trim().to_string()),
+            part => {
+                let start = part.start();
+                Err(color_eyre::eyre::eyre!(
+                    "unexpected expression or interpolation (at {}:{})",
+                    start.line,
+                    start.column
+                ))
+            }
+        })
+        .collect::<color_eyre::Result<Vec<_>>>()?;
+
+    let mut follows_lines = String::new();
+    for InputFollows { parent, input } in follows {
+        let follows_attr_path: VecDeque<String> = [
+            String::from("inputs"),
+            flake_input_name.to_string(),
+            String::from("inputs"),
+            input.clone(),
+            String::from("follows"),
+        ]
+        .into();
+        if find_first_attrset_by_path(expr, Some(follows_attr_path))?.is_some() {
+            tracing::warn!(
+                "`inputs.{flake_input_name}.inputs.{input}.follows` is already set; leaving it as is"
+            );
+            continue;
+        }
+
+        let attr_path = prefix
+            .iter()
+            .map(String::as_str)
+            .chain(["inputs", input, "follows"])
+            .collect::<Vec<_>>()
+            .join(".");
+        follows_lines.push_str(&format!(r#"{attr_path} = "{parent}";{NEWLINE}"#));
+    }
+
+    let (from_span, to_span) = kv_to_span(&url_attr);
+
+    // The `;` that ends the `url` binding
+    let value_end = position_to_offset(flake_contents, &to_span.end)?;
+    let binding_end = flake_contents[value_end..]
+        .find(';')
+        .map(|idx| value_end + idx + 1)
+        .ok_or_else(|| {
+            color_eyre::eyre::eyre!("could not find the end of `inputs.{flake_input_name}.url`")
+        })?;
+    let rest_of_line = flake_contents[binding_end..]
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim();
+
+    let mut new_flake_contents = flake_contents.to_string();
+    if rest_of_line.is_empty() || rest_of_line.starts_with('#') {
+        let indentation = indentation_from_from_span(flake_contents, &from_span)?;
+        let insertion_pos = nixel::Position {
+            line: to_span.end.line + 1,
+            column: 1,
+        };
+        let offset = position_to_offset(flake_contents, &insertion_pos)?;
+
+        let follows_lines = follows_lines
+            .lines()
+            .map(|line| format!("{indentation}{line}{NEWLINE}"))
+            .collect::<String>();
+        new_flake_contents.insert_str(offset, &follows_lines);
+    } else {
+        // Something else follows `url` on its line, like the `}` of `inputs.foo = { url = ...; };`,
+        // so the follows go on the same line, still inside the same attribute set
+        let follows_inline = follows_lines
+            .lines()
+            .map(|line| format!(" {line}"))
+            .collect::<String>();
+        new_flake_contents.insert_str(binding_end, &follows_inline);
+    }
+
+    Ok(new_flake_contents)
+}
+
+/// Renames an input everywhere the flake names it: its attribute under `inputs`, any `follows`
+/// that point at it, and its argument in the `outputs` function.
+#[tracing::instrument(skip_all, fields(from = %rename.from, to = %rename.to))]
+pub(crate) fn rename_flake_input(
+    expr: &nixel::Expression,
+    flake_contents: &str,
+    rename: &InputRename,
+) -> color_eyre::Result<String> {
+    let mut edits = Vec::new();
+
+    let inputs_attr_path: VecDeque<String> = [String::from("inputs")].into();
+    for kv in find_all_attrsets_by_path(expr, Some(inputs_attr_path))? {
+        let is_inputs_attrset =
+            matches!(&kv.from[..], [nixel::Part::Raw(raw)] if &*raw.content == "inputs");
+
+        if is_inputs_attrset {
+            // inputs = { nixpkgs.url = ""; };
+            for input in find_all_attrsets_by_path(&kv.to, None)? {
+                collect_rename_edits(flake_contents, &input, 0, rename, &mut edits)?;
+            }
+        } else {
+            // inputs.nixpkgs.url = "";
+            collect_rename_edits(flake_contents, &kv, 1, rename, &mut edits)?;
+        }
+    }
+
+    if !edits.iter().any(|edit: &TextEdit| edit.renames_input) {
+        return Err(color_eyre::eyre::eyre!(
+            "there is no input named `{}` to rename",
+            rename.from
+        ));
+    }
+
+    let outputs_attr_path: VecDeque<String> = [String::from("outputs")].into();
+    if let Some(outputs_attr) = find_first_attrset_by_path(expr, Some(outputs_attr_path))? {
+        if let nixel::Expression::Function(f) = &*outputs_attr.to {
+            if let nixel::FunctionHead::Destructured(head) = &f.head {
+                if head
+                    .arguments
+                    .iter()
+                    .any(|arg| *arg.identifier == rename.from)
+                {
+                    let (start, end) = span_to_start_end_offsets(flake_contents, &f.span)?;
+                    let function_text = &flake_contents[start..end];
+                    let head_len = destructured_head_len(function_text).ok_or_else(|| {
+                        color_eyre::eyre::eyre!(
+                            "could not find the arguments of the `outputs` function"
+                        )
+                    })?;
+                    let offset = find_identifier(&function_text[..head_len], &rename.from)
+                        .ok_or_else(|| {
+                            color_eyre::eyre::eyre!(
+                                "could not find `{}` in the outputs function, but it existed when parsing it",
+                                rename.from
+                            )
+                        })?;
+
+                    edits.push(TextEdit {
+                        start: start + offset,
+                        end: start + offset + rename.from.len(),
+                        replacement: rename.to.clone(),
+                        renames_input: false,
+                    });
+                }
+            }
+
+            let (start, end) = span_to_start_end_offsets(flake_contents, &f.body.span())?;
+            if find_identifier(&flake_contents[start..end], &rename.from).is_some() {
+                tracing::warn!(
+                    "The body of `outputs` may still refer to `{}`; update it to use `{}`",
+                    rename.from,
+                    rename.to
+                );
+            }
+        }
+    }
+
+    // Apply the edits back to front, so that the offsets of the ones still to come stay valid
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.start));
+    let mut new_flake_contents = flake_contents.to_string();
+    for edit in edits {
+        new_flake_contents.replace_range(edit.start..edit.end, &edit.replacement);
+    }
+
+    Ok(new_flake_contents)
+}
+
+struct TextEdit {
+    start: usize,
+    end: usize,
+    replacement: String,
+    // Whether this renames the input itself, rather than something that refers to it
+    renames_input: bool,
+}
+
+// Collects the edits that rename the input named by the `name_index`th part of `kv`'s attr path,
+// and that update any `follows` inside `kv` that point at the renamed input.
+fn collect_rename_edits(
+    flake_contents: &str,
+    kv: &nixel::BindingKeyValue,
+    name_index: usize,
+    rename: &InputRename,
+    edits: &mut Vec<TextEdit>,
+) -> color_eyre::Result<()> {
+    if let Some(nixel::Part::Raw(name)) = kv.from.get(name_index) {
+        if *name.content == rename.to {
+            return Err(color_eyre::eyre::eyre!(
+                "there is already an input named `{}`",
+                rename.to
+            ));
+        }
+
+        if *name.content == rename.from {
+            let (start, end) = span_to_start_end_offsets(flake_contents, &name.span)?;
+            edits.push(TextEdit {
+                start,
+                end,
+                replacement: rename.to.clone(),
+                renames_input: true,
+            });
+        }
+    }
+
+    collect_follows_edits(flake_contents, kv, rename, edits)
+}
+
+// inputs.foo.inputs.nixpkgs.follows = "nixpkgs";
+fn collect_follows_edits(
+    flake_contents: &str,
+    kv: &nixel::BindingKeyValue,
+    rename: &InputRename,
+    edits: &mut Vec<TextEdit>,
+) -> color_eyre::Result<()> {
+    let is_follows =
+        matches!(kv.from.last(), Some(nixel::Part::Raw(raw)) if &*raw.content == "follows");
+
+    match &*kv.to {
+        nixel::Expression::String(value) if is_follows => {
+            if let [nixel::Part::Raw(raw)] = &value.parts[..] {
+                // A follows is a path through inputs, like `nixpkgs` or `foo/nixpkgs`
+                let follows_renamed_input = raw
+                    .content
+                    .split('/')
+                    .next()
+                    .is_some_and(|first| first == rename.from);
+
+                if follows_renamed_input {
+                    let (start, end) = span_to_start_end_offsets(flake_contents, &raw.span)?;
+                    edits.push(TextEdit {
+                        start,
+                        end,
+                        replacement: raw.content.replacen(&rename.from, &rename.to, 1),
+                        renames_input: false,
+                    });
+                }
+            }
+        }
+        nixel::Expression::Map(_) => {
+            for child in find_all_attrsets_by_path(&kv.to, None)? {
+                collect_follows_edits(flake_contents, &child, rename, edits)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// The length of the `{ ... } @ inputs:` (or `inputs @ { ... }:`) head at the start of `function_text`.
+fn destructured_head_len(function_text: &str) -> Option<usize> {
+    let open = function_text.find('{')?;
+    let mut depth = 0;
+
+    for (idx, ch) in function_text[open..].char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let close = open + idx;
+                    return function_text[close..].find(':').map(|colon| close + colon);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// The offset of the first occurrence of `identifier` in `text` that isn't part of a longer
+// identifier.
+fn find_identifier(text: &str, identifier: &str) -> Option<usize> {
+    let is_identifier_char = |c: char| c.is_ascii_alphanumeric() || ['_', '\'', '-'].contains(&c);
+
+    text.match_indices(identifier)
+        .map(|(idx, _)| idx)
+        .find(|&idx| {
+            let before = text[..idx].chars().next_back();
+            let after = text[idx + identifier.len()..].chars().next();
+
+            !before.is_some_and(is_identifier_char) && !after.is_some_and(is_identifier_char)
+        })
+}
+
+/// Removes an input from the flake: every binding under `inputs` that belongs to it (including
+/// its own `follows`), and its argument in the `outputs` function.
+#[tracing::instrument(skip_all, fields(input_name = %input_name))]
+pub(crate) fn remove_flake_input(
+    expr: &nixel::Expression,
+    flake_contents: &str,
+    input_name: &str,
+) -> color_eyre::Result<String> {
+    let mut edits = Vec::new();
+    // Bindings of other inputs, to check whether any of them follow the removed one
+    let mut remaining_inputs = Vec::new();
+
+    let inputs_attr_path: VecDeque<String> = [String::from("inputs")].into();
+    for kv in find_all_attrsets_by_path(expr, Some(inputs_attr_path))? {
+        let is_inputs_attrset =
+            matches!(&kv.from[..], [nixel::Part::Raw(raw)] if &*raw.content == "inputs");
+
+        if is_inputs_attrset {
+            // inputs = { nixpkgs.url = ""; };
+            for input in find_all_attrsets_by_path(&kv.to, None)? {
+                if matches!(input.from.first(), Some(nixel::Part::Raw(raw)) if &*raw.content == input_name)
+                {
+                    edits.push(binding_removal_edit(flake_contents, &input)?);
+                } else {
+                    remaining_inputs.push(input);
+                }
+            }
+        } else if matches!(kv.from.get(1), Some(nixel::Part::Raw(raw)) if &*raw.content == input_name)
+        {
+            // inputs.nixpkgs.url = "";
+            edits.push(binding_removal_edit(flake_contents, &kv)?);
+        } else {
+            remaining_inputs.push(kv);
+        }
+    }
+
+    if edits.is_empty() {
+        return Err(color_eyre::eyre::eyre!(
+            "there is no input named `{input_name}` to remove"
+        ));
+    }
+
+    let follows_removed_input = InputRename {
+        from: input_name.to_string(),
+        to: input_name.to_string(),
+    };
+    let mut follows_edits = Vec::new();
+    for kv in &remaining_inputs {
+        collect_follows_edits(
+            flake_contents,
+            kv,
+            &follows_removed_input,
+            &mut follows_edits,
+        )?;
+    }
+    if !follows_edits.is_empty() {
+        tracing::warn!(
+            "Other inputs still follow `{input_name}`; remove or update those `follows` too"
+        );
+    }
+
+    let outputs_attr_path: VecDeque<String> = [String::from("outputs")].into();
+    if let Some(outputs_attr) = find_first_attrset_by_path(expr, Some(outputs_attr_path))? {
+        if let nixel::Expression::Function(f) = &*outputs_attr.to {
+            if let nixel::FunctionHead::Destructured(head) = &f.head {
+                if head
+                    .arguments
+                    .iter()
+                    .any(|arg| &*arg.identifier == input_name)
+                {
+                    let (start, end) = span_to_start_end_offsets(flake_contents, &f.span)?;
+                    edits.push(outputs_argument_removal_edit(
+                        &flake_contents[start..end],
+                        start,
+                        input_name,
+                    )?);
+                }
+            }
+
+            let (start, end) = span_to_start_end_offsets(flake_contents, &f.body.span())?;
+            if find_identifier(&flake_contents[start..end], input_name).is_some() {
+                tracing::warn!("The body of `outputs` may still refer to `{input_name}`");
+            }
+        }
+    }
+
+    // Apply the edits back to front, so that the offsets of the ones still to come stay valid
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.start));
+    let mut new_flake_contents = flake_contents.to_string();
+    for edit in edits {
+        new_flake_contents.replace_range(edit.start..edit.end, &edit.replacement);
+    }
+
+    Ok(new_flake_contents)
+}
+
+// The edit that deletes `kv` up to and including its `;`. When the binding is alone on its lines,
+// the lines go too.
+fn binding_removal_edit(
+    flake_contents: &str,
+    kv: &nixel::BindingKeyValue,
+) -> color_eyre::Result<TextEdit> {
+    let (from_span, to_span) = kv_to_span(kv);
+    let start = position_to_offset(flake_contents, &from_span.start)?;
+    let value_end = position_to_offset(flake_contents, &to_span.end)?;
+    let end = flake_contents[value_end..]
+        .find(';')
+        .map(|semicolon| value_end + semicolon + 1)
+        .ok_or_else(|| {
+            color_eyre::eyre::eyre!(
+                "could not find the end of the binding at {}:{}",
+                from_span.start.line,
+                from_span.start.column
+            )
+        })?;
+
+    let line_start = flake_contents[..start].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = flake_contents[end..]
+        .find('\n')
+        .map_or(flake_contents.len(), |idx| end + idx + 1);
+
+    let (start, end) = if flake_contents[line_start..start].trim().is_empty()
+        && flake_contents[end..line_end].trim().is_empty()
+    {
+        (line_start, line_end)
+    } else {
+        let trailing_whitespace = flake_contents[end..line_end]
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(0);
+        (start, end + trailing_whitespace)
+    };
+
+    Ok(TextEdit {
+        start,
+        end,
+        replacement: String::new(),
+        renames_input: false,
+    })
+}
+
+// The edit that deletes `argument`, and a comma next to it, from the head of the `outputs`
+// function `function_text`, which starts at `function_offset` in the flake.
+fn outputs_argument_removal_edit(
+    function_text: &str,
+    function_offset: usize,
+    argument: &str,
+) -> color_eyre::Result<TextEdit> {
+    let head_len = destructured_head_len(function_text).ok_or_else(|| {
+        color_eyre::eyre::eyre!("could not find the arguments of the `outputs` function")
+    })?;
+    let head = &function_text[..head_len];
+    let start = find_identifier(head, argument).ok_or_else(|| {
+        color_eyre::eyre::eyre!(
+            "could not find `{argument}` in the outputs function, but it existed when parsing it"
+        )
+    })?;
+    let end = start + argument.len();
+
+    let after = &head[end..];
+    let (start, end) = if after.trim_start().starts_with(',') {
+        // { self, foo, nixpkgs }: takes `foo, `
+        let comma = end + after.find(',').expect("there is a comma");
+        let next_argument = head[comma + 1..]
+            .find(|c: char| !c.is_whitespace())
+            .map_or(head_len, |idx| comma + 1 + idx);
+        (start, next_argument)
+    } else {
+        // { self, foo }: takes `, foo`
+        let before = head[..start].trim_end();
+        let start = if before.ends_with(',') {
+            before.len() - 1
+        } else {
+            start
+        };
+        (start, end)
+    };
+
+    Ok(TextEdit {
+        start: function_offset + start,
+        end: function_offset + end,
+        replacement: String::new(),
+        renames_input: false,
+    })
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) fn span_to_start_end_offsets(
     flake_contents: &str,
@@ -736,6 +1355,223 @@ pub(crate) fn position_to_offset(
 mod test {
     use super::InputsInsertionLocation;
 
+    #[test]
+    fn input_renames() {
+        let rename: super::InputRename = "nixpkgs=nixpkgs-stable".parse().unwrap();
+        assert_eq!(rename.from, "nixpkgs");
+        assert_eq!(rename.to, "nixpkgs-stable");
+
+        for invalid in [
+            "nixpkgs",
+            "=nixpkgs",
+            "nixpkgs=",
+            "nixpkgs=1nixpkgs",
+            "a=in",
+            "a=b.c",
+        ] {
+            assert!(
+                invalid.parse::<super::InputRename>().is_err(),
+                "{invalid} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn rename_input_in_inputs_attrset() {
+        let flake_contents = r#"{
+  inputs = {
+    nixpkgs.url = "github:NixOS/nixpkgs/nixos-23.05";
+    home-manager = {
+      url = "github:nix-community/home-manager";
+      inputs.nixpkgs.follows = "nixpkgs";
+    };
+  };
+
+  outputs = inputs @ { self, nixpkgs, ... }: { };
+}
+"#;
+        let parsed = nixel::parse(flake_contents.to_string());
+        let rename = "nixpkgs=pkgs".parse().unwrap();
+
+        let renamed =
+            super::rename_flake_input(&parsed.expression, flake_contents, &rename).unwrap();
+
+        assert!(renamed.contains(r#"    pkgs.url = "github:NixOS/nixpkgs/nixos-23.05";"#));
+        assert!(renamed.contains(r#"inputs.nixpkgs.follows = "pkgs";"#));
+        assert!(renamed.contains("outputs = inputs @ { self, pkgs, ... }: { };"));
+
+        let missing = "nope=pkgs".parse().unwrap();
+        assert!(super::rename_flake_input(&parsed.expression, flake_contents, &missing).is_err());
+        let taken = "nixpkgs=home-manager".parse().unwrap();
+        assert!(super::rename_flake_input(&parsed.expression, flake_contents, &taken).is_err());
+    }
+
+    #[test]
+    fn add_input_with_follows() {
+        let flake_contents = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/samples/flake5.test.nix"
+        ));
+        let flake_contents = flake_contents.to_string();
+        let input_name = String::from("home-manager");
+        let input_value =
+            url::Url::parse("https://flakehub.com/f/nix-community/home-manager/*.tar.gz").unwrap();
+        let parsed = nixel::parse(flake_contents.clone());
+
+        let res = super::upsert_flake_input(
+            &parsed.expression,
+            input_name.clone(),
+            input_value,
+            flake_contents,
+            ["inputs", &input_name, "url"]
+                .map(ToString::to_string)
+                .into(),
+            InputsInsertionLocation::Top,
+        )
+        .unwrap();
+
+        let follows: Vec<super::InputFollows> = ["nixpkgs=nixpkgs", "nixpkgs=nixpkgs-darwin"]
+            .iter()
+            .map(|f| f.parse().unwrap())
+            .collect();
+        let parsed = nixel::parse(res.clone());
+        let res =
+            super::insert_input_follows(&parsed.expression, &res, &input_name, &follows).unwrap();
+
+        assert!(res.contains(
+            r#"    home-manager.url = "https://flakehub.com/f/nix-community/home-manager/*.tar.gz";
+    home-manager.inputs.nixpkgs.follows = "nixpkgs";
+    home-manager.inputs.nixpkgs-darwin.follows = "nixpkgs";
+    nixpkgs.url = "github:nixos/nixpkgs/nixos-unstable";
+"#
+        ));
+        assert!(res.contains("outputs = { self, home-manager, ... } @ tes: { };"));
+
+        // Adding them again doesn't duplicate them
+        let parsed = nixel::parse(res.clone());
+        let again =
+            super::insert_input_follows(&parsed.expression, &res, &input_name, &follows).unwrap();
+        assert_eq!(again, res);
+
+        assert!("nixpkgs".parse::<super::InputFollows>().is_err());
+        assert!("nixpkgs=a.b".parse::<super::InputFollows>().is_err());
+    }
+
+    #[test]
+    fn insert_input_follows_single_line() {
+        let flake_contents = r#"{
+  inputs.nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.1.*";
+  inputs.home-manager = { url = "github:nix-community/home-manager"; };
+
+  outputs = { self, nixpkgs, home-manager }: { };
+}
+"#;
+        let follows: Vec<super::InputFollows> = vec!["nixpkgs=nixpkgs".parse().unwrap()];
+
+        let parsed = nixel::parse(flake_contents.to_string());
+        let res = super::insert_input_follows(
+            &parsed.expression,
+            flake_contents,
+            "home-manager",
+            &follows,
+        )
+        .unwrap();
+
+        assert!(res.contains(
+            r#"  inputs.home-manager = { url = "github:nix-community/home-manager"; inputs.nixpkgs.follows = "nixpkgs"; };
+"#
+        ));
+        assert!(!res.contains("\n  inputs.nixpkgs.follows"));
+    }
+
+    #[test]
+    fn rename_input_with_follows_chains() {
+        let flake_contents = r#"{
+  inputs.nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.1.*";
+  inputs.home-manager.url = "github:nix-community/home-manager";
+  inputs.home-manager.inputs.nixpkgs.follows = "nixpkgs";
+  inputs.nix-darwin = {
+    url = "github:LnL7/nix-darwin";
+    inputs.nixpkgs.follows = "nixpkgs";
+  };
+
+  outputs = { self, nixpkgs, home-manager, nix-darwin }: { };
+}
+"#;
+        let parsed = nixel::parse(flake_contents.to_string());
+        let rename = "nixpkgs=nixpkgs-unstable".parse().unwrap();
+
+        let renamed =
+            super::rename_flake_input(&parsed.expression, flake_contents, &rename).unwrap();
+
+        assert_eq!(
+            renamed,
+            r#"{
+  inputs.nixpkgs-unstable.url = "https://flakehub.com/f/NixOS/nixpkgs/0.1.*";
+  inputs.home-manager.url = "github:nix-community/home-manager";
+  inputs.home-manager.inputs.nixpkgs.follows = "nixpkgs-unstable";
+  inputs.nix-darwin = {
+    url = "github:LnL7/nix-darwin";
+    inputs.nixpkgs.follows = "nixpkgs-unstable";
+  };
+
+  outputs = { self, nixpkgs-unstable, home-manager, nix-darwin }: { };
+}
+"#
+        );
+    }
+
+    #[test]
+    fn remove_input() {
+        let flake_contents = r#"{
+  inputs.nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.1.*";
+  inputs.flake-utils.url = "github:numtide/flake-utils";
+  inputs.flake-utils.inputs.nixpkgs.follows = "nixpkgs";
+  inputs.home-manager = {
+    url = "github:nix-community/home-manager";
+    inputs.nixpkgs.follows = "nixpkgs";
+  };
+  inputs = {
+    flake-utils.flake = true;
+    crane.url = "github:ipetkov/crane";
+  };
+
+  outputs = { self, nixpkgs, flake-utils, home-manager, crane }: { };
+}
+"#;
+        let parsed = nixel::parse(flake_contents.to_string());
+
+        let removed =
+            super::remove_flake_input(&parsed.expression, flake_contents, "flake-utils").unwrap();
+        assert_eq!(
+            removed,
+            r#"{
+  inputs.nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.1.*";
+  inputs.home-manager = {
+    url = "github:nix-community/home-manager";
+    inputs.nixpkgs.follows = "nixpkgs";
+  };
+  inputs = {
+    crane.url = "github:ipetkov/crane";
+  };
+
+  outputs = { self, nixpkgs, home-manager, crane }: { };
+}
+"#
+        );
+
+        let removed =
+            super::remove_flake_input(&parsed.expression, flake_contents, "home-manager").unwrap();
+        assert!(!removed.contains("home-manager"));
+        assert!(removed.contains("inputs.flake-utils.inputs.nixpkgs.follows"));
+
+        let removed =
+            super::remove_flake_input(&parsed.expression, flake_contents, "crane").unwrap();
+        assert!(removed.contains("outputs = { self, nixpkgs, flake-utils, home-manager }: { };"));
+
+        assert!(super::remove_flake_input(&parsed.expression, flake_contents, "nope").is_err());
+    }
+
     #[test]
     fn test_flake_1_rewrite_less_simple_flake_input() {
         let flake_contents = include_str!(concat!(
@@ -839,6 +1675,82 @@ mod test {
         }
     }
 
+    #[test]
+    fn unrelated_inherits_are_skipped() {
+        let flake_contents = String::from(
+            r#"{
+  inherit (import ./config.nix) nixConfig;
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+  outputs = { nixpkgs, ... }: { };
+}"#,
+        );
+        let parsed = nixel::parse(flake_contents);
+        let found = super::find_first_attrset_by_path(
+            &parsed.expression,
+            Some(["inputs", "nixpkgs", "url"].map(ToString::to_string).into()),
+        )
+        .unwrap();
+        assert!(found.is_some());
+
+        // An `inherit` that provides the inputs themselves can't be edited through
+        let flake_contents = String::from(
+            r#"{
+  inherit (import ./inputs.nix) inputs;
+  outputs = { nixpkgs, ... }: { };
+}"#,
+        );
+        let parsed = nixel::parse(flake_contents);
+        let err = super::find_first_attrset_by_path(
+            &parsed.expression,
+            Some(["inputs", "nixpkgs", "url"].map(ToString::to_string).into()),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("`inputs` comes from an `inherit`"));
+    }
+
+    #[test]
+    fn test_flake_10_update_inputs_declared_as_attrsets() {
+        let flake_contents = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/samples/flake10.test.nix"
+        ));
+        let flake_contents = flake_contents.to_string();
+        let parsed = nixel::parse(flake_contents.clone());
+
+        for input in ["foo", "bar", "baz"] {
+            let input_name = input.to_string();
+            let input_value =
+                url::Url::parse(&format!("https://flakehub.com/f/example/{input}/*")).unwrap();
+
+            let res = super::upsert_flake_input(
+                &parsed.expression,
+                input_name.clone(),
+                input_value.clone(),
+                flake_contents.clone(),
+                ["inputs", &input_name, "url"]
+                    .map(ToString::to_string)
+                    .into(),
+                InputsInsertionLocation::Top,
+            )
+            .unwrap();
+
+            // The existing `url` is replaced rather than a second one being added
+            assert!(
+                !res.contains(&format!("github:example/{input}")),
+                "{input} wasn't updated:\n{res}"
+            );
+            assert_eq!(
+                res.matches(input_value.as_str()).count(),
+                1,
+                "{input} was added more than once:\n{res}"
+            );
+            assert!(
+                !res.contains(&format!("{input}.url")),
+                "{input} was added again:\n{res}"
+            );
+        }
+    }
+
     #[test]
     fn test_flake_4_add_new_input_before_existing_outputs() {
         let flake_contents = include_str!(concat!(
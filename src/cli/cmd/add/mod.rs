@@ -1,16 +1,19 @@
 // FIXME: extract to somewhere else so it's more convenient
 pub(crate) mod flake;
 
-use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::sync::{Mutex, OnceLock};
 
 use clap::Parser;
 use color_eyre::eyre::WrapErr;
+use tokio::io::{AsyncRead, AsyncReadExt as _};
 
-use self::flake::InputsInsertionLocation;
+use self::flake::{InputFollows, InputRename, InputsInsertionLocation};
 
-use super::{CommandExecute, FlakeHubClient};
+use super::{CommandExecute, FlakeHubClient, ProjectCanonicalNames};
+use crate::cli::error::FhError;
 
 const FALLBACK_FLAKE_CONTENTS: &str = r#"{
   description = "My new flake.";
@@ -22,7 +25,7 @@ const FALLBACK_FLAKE_CONTENTS: &str = r#"{
 /// Adds a flake input to your flake.nix.
 #[derive(Parser, Debug)]
 pub(crate) struct AddSubcommand {
-    /// The flake.nix to modify.
+    /// The flake.nix to modify, or `-` to read it from stdin (implies `--dry-run`).
     #[clap(long, default_value = "./flake.nix")]
     pub(crate) flake_path: PathBuf,
     /// The name of the flake input.
@@ -34,13 +37,29 @@ pub(crate) struct AddSubcommand {
     ///
     /// A reference in the form of `NixOS/nixpkgs` or `NixOS/nixpkgs/0.2411.*` (without a URL
     /// scheme) will be inferred as a FlakeHub input.
-    pub(crate) input_ref: String,
+    #[clap(required_unless_present = "renames")]
+    pub(crate) input_ref: Option<String>,
+    /// Rename an existing input, as `old=new`. Its `follows` and its argument in the `outputs`
+    /// function are renamed too. May be given more than once, with or without an input to add.
+    #[clap(long = "rename", value_name = "OLD=NEW")]
+    pub(crate) renames: Vec<InputRename>,
+    /// Make an input of the added flake follow one of yours, as `parent=input`. For example,
+    /// `--follows nixpkgs=nixpkgs` writes `inputs.<name>.inputs.nixpkgs.follows = "nixpkgs"`. May
+    /// be given more than once.
+    #[clap(long = "follows", value_name = "PARENT=INPUT", requires = "input_ref")]
+    pub(crate) follows: Vec<InputFollows>,
     /// Whether to insert a new input at the top of or the bottom of an existing `inputs` attrset.
     #[clap(long, default_value_t = InputsInsertionLocation::Top)]
     pub(crate) insertion_location: InputsInsertionLocation,
     /// Print to stdout the new flake.nix contents instead of writing it to disk.
     #[clap(long)]
     pub(crate) dry_run: bool,
+    /// Replace any invalid UTF-8 in the flake.nix instead of refusing to edit it.
+    #[clap(long)]
+    pub(crate) lossy: bool,
+    /// Write the new flake.nix even if it no longer parses.
+    #[clap(long)]
+    pub(crate) force: bool,
 
     #[clap(from_global)]
     api_addr: url::Url,
@@ -48,53 +67,148 @@ pub(crate) struct AddSubcommand {
 
 #[async_trait::async_trait]
 impl CommandExecute for AddSubcommand {
-    async fn execute(self) -> color_eyre::Result<ExitCode> {
-        let (flake_contents, parsed) = load_flake(&self.flake_path).await?;
-
-        let (flake_input_name, flake_input_url) =
-            infer_flake_input_name_url(self.api_addr, self.input_ref, self.input_name).await?;
-        let input_url_attr_path: VecDeque<String> = [
-            String::from("inputs"),
-            flake_input_name.clone(),
-            String::from("url"),
-        ]
-        .into();
-
-        let new_flake_contents = flake::upsert_flake_input(
-            &parsed.expression,
-            flake_input_name,
-            flake_input_url,
-            flake_contents,
-            input_url_attr_path,
-            self.insertion_location,
-        )?;
+    async fn execute(mut self) -> color_eyre::Result<ExitCode> {
+        // There's no file to write the result back to when the flake came from stdin
+        if is_stdin(&self.flake_path) {
+            self.dry_run = true;
+        }
+
+        let (flake_contents, parsed) = load_flake(&self.flake_path, self.lossy).await?;
+
+        let mut new_flake_contents = match self.input_ref {
+            Some(input_ref) => {
+                let (flake_input_name, flake_input_url) =
+                    infer_flake_input_name_url(self.api_addr, input_ref, self.input_name).await?;
+                let input_url_attr_path: VecDeque<String> = [
+                    String::from("inputs"),
+                    flake_input_name.clone(),
+                    String::from("url"),
+                ]
+                .into();
+
+                let new_flake_contents = flake::upsert_flake_input(
+                    &parsed.expression,
+                    flake_input_name.clone(),
+                    flake_input_url,
+                    flake_contents.clone(),
+                    input_url_attr_path,
+                    self.insertion_location,
+                )?;
+
+                if self.follows.is_empty() {
+                    new_flake_contents
+                } else {
+                    let parsed = nixel::parse(new_flake_contents.clone());
+                    flake::insert_input_follows(
+                        &parsed.expression,
+                        &new_flake_contents,
+                        &flake_input_name,
+                        &self.follows,
+                    )?
+                }
+            }
+            None => flake_contents.clone(),
+        };
+
+        for rename in &self.renames {
+            let parsed = nixel::parse(new_flake_contents.clone());
+            new_flake_contents =
+                flake::rename_flake_input(&parsed.expression, &new_flake_contents, rename)?;
+        }
 
         if self.dry_run {
             println!("{new_flake_contents}");
         } else {
-            tokio::fs::write(self.flake_path, new_flake_contents).await?;
+            if !self.force {
+                verify_edited_flake(&parsed.expression, &new_flake_contents)?;
+            }
+
+            write_edited_flake(&self.flake_path, &flake_contents, &new_flake_contents).await?;
         }
 
         Ok(ExitCode::SUCCESS)
     }
 }
 
+/// Write an edited flake.nix, ending it with the same trailing whitespace as the original so that
+/// edits don't add or remove a final newline.
+pub(crate) async fn write_edited_flake(
+    flake_path: &Path,
+    original_contents: &str,
+    new_flake_contents: &str,
+) -> color_eyre::Result<()> {
+    tokio::fs::write(
+        flake_path,
+        with_trailing_whitespace_of(original_contents, new_flake_contents),
+    )
+    .await
+    .wrap_err_with(|| format!("Failed to write {}", flake_path.display()))
+}
+
+fn with_trailing_whitespace_of(original_contents: &str, new_flake_contents: &str) -> String {
+    let trailing_whitespace = &original_contents[original_contents.trim_end().len()..];
+
+    format!("{}{trailing_whitespace}", new_flake_contents.trim_end())
+}
+
+/// Whether the given flake path means "read the flake from stdin".
+pub(crate) fn is_stdin(flake_path: &Path) -> bool {
+    flake_path == Path::new("-")
+}
+
 #[tracing::instrument(skip_all)]
 // FIXME: make a nix or nix_util module or something
 pub(crate) async fn load_flake(
     flake_path: &PathBuf,
+    lossy: bool,
 ) -> color_eyre::Result<(String, nixel::Parsed)> {
-    let mut contents = tokio::fs::read_to_string(&flake_path)
+    if is_stdin(flake_path) {
+        return load_flake_from_reader(tokio::io::stdin(), lossy).await;
+    }
+
+    let contents = match tokio::fs::read(&flake_path).await {
+        Ok(bytes) => decode_flake_contents(bytes, &flake_path.display().to_string(), lossy)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => FALLBACK_FLAKE_CONTENTS.to_string(),
+        Err(e) => {
+            return Err(e).wrap_err_with(|| format!("Failed to open {}", flake_path.display()))
+        }
+    };
+
+    Ok(parse_flake_contents(contents))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn load_flake_from_reader<R: AsyncRead + Unpin>(
+    mut reader: R,
+    lossy: bool,
+) -> color_eyre::Result<(String, nixel::Parsed)> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
         .await
-        .or_else(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                Ok(FALLBACK_FLAKE_CONTENTS.to_string())
-            } else {
-                Err(e)
-            }
-        })
-        .wrap_err_with(|| format!("Failed to open {}", flake_path.display()))?;
+        .wrap_err("Failed to read flake from stdin")?;
+    let contents = decode_flake_contents(bytes, "stdin", lossy)?;
 
+    Ok(parse_flake_contents(contents))
+}
+
+fn decode_flake_contents(bytes: Vec<u8>, source: &str, lossy: bool) -> Result<String, FhError> {
+    match String::from_utf8(bytes) {
+        Ok(contents) => Ok(contents),
+        Err(e) if lossy => {
+            tracing::warn!(
+                "{source} is not valid UTF-8; invalid bytes will be replaced with U+FFFD"
+            );
+            Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+        }
+        Err(e) => Err(FhError::InvalidUtf8(
+            source.to_string(),
+            e.utf8_error().valid_up_to(),
+        )),
+    }
+}
+
+fn parse_flake_contents(mut contents: String) -> (String, nixel::Parsed) {
     if contents.trim().is_empty() {
         contents = FALLBACK_FLAKE_CONTENTS.to_string();
     };
@@ -108,7 +222,71 @@ pub(crate) async fn load_flake(
         }
     }
 
-    Ok((contents, parsed))
+    (contents, parsed)
+}
+
+/// Check that an edit didn't break the flake: the new contents must still parse, and still be an
+/// attrset with every top-level attribute the original had.
+pub(crate) fn verify_edited_flake(
+    original: &nixel::Expression,
+    new_flake_contents: &str,
+) -> Result<(), FhError> {
+    let parsed = nixel::parse(new_flake_contents.to_string());
+
+    if let Some(error) = find_parse_error(&parsed.expression) {
+        return Err(FhError::InvalidEdit(format!(
+            "{} at line {}, column {}",
+            error.message, error.span.start.line, error.span.start.column
+        )));
+    }
+
+    let nixel::Expression::Map(new_map) = &*parsed.expression else {
+        return Err(FhError::InvalidEdit(String::from(
+            "it is no longer an attribute set",
+        )));
+    };
+    let nixel::Expression::Map(original_map) = original else {
+        return Ok(());
+    };
+
+    let new_attrs = toplevel_attr_names(new_map);
+    if let Some(missing) = toplevel_attr_names(original_map)
+        .into_iter()
+        .find(|name| !new_attrs.contains(name))
+    {
+        return Err(FhError::InvalidEdit(format!(
+            "the top-level `{missing}` attribute disappeared"
+        )));
+    }
+
+    Ok(())
+}
+
+fn find_parse_error(expr: &nixel::Expression) -> Option<&nixel::Error> {
+    match expr {
+        nixel::Expression::Error(error) => Some(error),
+        nixel::Expression::Map(map) => map.bindings.iter().find_map(|binding| match binding {
+            nixel::Binding::KeyValue(kv) => find_parse_error(&kv.to),
+            nixel::Binding::Inherit(_) => None,
+        }),
+        nixel::Expression::Function(function) => find_parse_error(&function.body),
+        nixel::Expression::LetIn(let_in) => find_parse_error(&let_in.target),
+        nixel::Expression::List(list) => list.elements.iter().find_map(find_parse_error),
+        _ => None,
+    }
+}
+
+fn toplevel_attr_names(map: &nixel::Map) -> Vec<String> {
+    map.bindings
+        .iter()
+        .filter_map(|binding| match binding {
+            nixel::Binding::KeyValue(kv) => match kv.from.first()? {
+                nixel::Part::Raw(raw) => Some(raw.content.trim().to_string()),
+                _ => None,
+            },
+            nixel::Binding::Inherit(_) => None,
+        })
+        .collect()
 }
 
 #[tracing::instrument(skip_all)]
@@ -182,6 +360,14 @@ async fn infer_flake_input_name_url(
     }
 }
 
+// The API address, org, project, and version of a `project_and_url` lookup
+type ProjectKey = (String, String, String, Option<String>);
+
+// Lookups that already succeeded during this run. `fh convert` asks about the same flakes over and
+// over when many inputs come from the same org, and the answer won't change mid-run.
+static PROJECT_AND_URL_CACHE: OnceLock<Mutex<HashMap<ProjectKey, ProjectCanonicalNames>>> =
+    OnceLock::new();
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn get_flakehub_project_and_url(
     api_addr: &url::Url,
@@ -189,5 +375,195 @@ pub(crate) async fn get_flakehub_project_and_url(
     project: &str,
     version: Option<&str>,
 ) -> color_eyre::Result<(String, url::Url)> {
-    FlakeHubClient::project_and_url(api_addr.as_ref(), org, project, version).await
+    let project = get_flakehub_project(api_addr, org, project, version).await?;
+
+    Ok((project.project, project.pretty_download_url))
+}
+
+// Like `get_flakehub_project_and_url`, along with where the project's source lives.
+#[tracing::instrument(skip_all)]
+pub(super) async fn get_flakehub_project(
+    api_addr: &url::Url,
+    org: &str,
+    project: &str,
+    version: Option<&str>,
+) -> color_eyre::Result<ProjectCanonicalNames> {
+    let cache = PROJECT_AND_URL_CACHE.get_or_init(Default::default);
+    let key = (
+        api_addr.to_string(),
+        org.to_string(),
+        project.to_string(),
+        version.map(ToString::to_string),
+    );
+
+    if let Some(cached) = cache.lock().expect("cache lock poisoned").get(&key) {
+        tracing::debug!("Using cached FlakeHub metadata for {org}/{project}");
+        return Ok(cached.clone());
+    }
+
+    let project_and_url =
+        FlakeHubClient::project_and_url(api_addr.as_ref(), org, project, version).await?;
+    cache
+        .lock()
+        .expect("cache lock poisoned")
+        .insert(key, project_and_url.clone());
+
+    Ok(project_and_url)
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn no_op_edits_keep_trailing_whitespace() {
+        let dir = tempfile::tempdir().unwrap();
+        let flake_path = dir.path().join("flake.nix");
+
+        for original in [
+            "{\n  inputs.nixpkgs.url = \"github:NixOS/nixpkgs\";\n}\n",
+            "{\n  inputs.nixpkgs.url = \"github:NixOS/nixpkgs\";\n}",
+            "{\n  inputs.nixpkgs.url = \"github:NixOS/nixpkgs\";\n}\n\n",
+        ] {
+            tokio::fs::write(&flake_path, original).await.unwrap();
+            let (flake_contents, parsed) = super::load_flake(&flake_path, false).await.unwrap();
+
+            let new_flake_contents = super::flake::upsert_flake_input(
+                &parsed.expression,
+                String::from("nixpkgs"),
+                url::Url::parse("github:NixOS/nixpkgs").unwrap(),
+                flake_contents.clone(),
+                ["inputs", "nixpkgs", "url"].map(ToString::to_string).into(),
+                super::flake::InputsInsertionLocation::Top,
+            )
+            .unwrap();
+            super::write_edited_flake(&flake_path, &flake_contents, &new_flake_contents)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                tokio::fs::read_to_string(&flake_path).await.unwrap(),
+                original
+            );
+        }
+
+        // Edits that add a newline of their own don't leave one behind
+        assert_eq!(
+            super::with_trailing_whitespace_of("{ }", "{ a = 1; }\n"),
+            "{ a = 1; }"
+        );
+    }
+
+    #[tokio::test]
+    async fn project_and_url_lookups_are_cached() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let router = axum::Router::new().route(
+            "/f/:org/:project",
+            axum::routing::get(
+                move |axum::extract::Path((org, project)): axum::extract::Path<(String, String)>| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        axum::Json(serde_json::json!({
+                            "project": project,
+                            "pretty_download_url": format!("https://flakehub.com/f/{org}/{project}/*.tar.gz"),
+                        }))
+                    }
+                },
+            ),
+        );
+        let test_server = crate::cli::cmd::test_server(router);
+        let server_url = test_server.server_address().unwrap();
+
+        for project in ["nixpkgs", "nixpkgs", "home-manager", "nixpkgs"] {
+            let (name, _) =
+                super::get_flakehub_project_and_url(&server_url, "NixOS", project, None)
+                    .await
+                    .unwrap();
+            assert_eq!(name, project);
+        }
+
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn verify_edited_flake() {
+        let original = nixel::parse(super::FALLBACK_FLAKE_CONTENTS.to_string());
+
+        let edited = super::FALLBACK_FLAKE_CONTENTS.replace(
+            "outputs",
+            "inputs.nixpkgs.url = \"https://flakehub.com/f/NixOS/nixpkgs/*\";\n\n  outputs",
+        );
+        super::verify_edited_flake(&original.expression, &edited).unwrap();
+
+        // An edit that clobbered a closing quote
+        let unparseable = edited.replace("/*\";", "/*;");
+        assert!(matches!(
+            super::verify_edited_flake(&original.expression, &unparseable),
+            Err(crate::cli::error::FhError::InvalidEdit(_))
+        ));
+
+        // An edit whose span was off and ate the `outputs` attribute
+        let lost_outputs = edited.replace("outputs = { ... } @ inputs: { };", "");
+        let Err(crate::cli::error::FhError::InvalidEdit(message)) =
+            super::verify_edited_flake(&original.expression, &lost_outputs)
+        else {
+            panic!("an edit that dropped `outputs` was accepted");
+        };
+        assert!(message.contains("`outputs`"));
+    }
+
+    #[tokio::test]
+    async fn load_flake_from_reader() {
+        let flake_contents = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/samples/flake1.test.nix"
+        ));
+
+        let (contents, parsed) = super::load_flake_from_reader(flake_contents.as_bytes(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(contents, flake_contents);
+        assert!(matches!(*parsed.expression, nixel::Expression::Map(_)));
+    }
+
+    #[tokio::test]
+    async fn load_empty_flake_from_reader() {
+        let (contents, _) = super::load_flake_from_reader(&b"  \n"[..], false)
+            .await
+            .unwrap();
+
+        assert_eq!(contents, super::FALLBACK_FLAKE_CONTENTS);
+    }
+
+    #[tokio::test]
+    async fn load_non_utf8_flake() {
+        let dir = tempfile::tempdir().unwrap();
+        let flake_path = dir.path().join("flake.nix");
+        tokio::fs::write(&flake_path, b"{\n  description = \"caf\xe9\";\n}\n")
+            .await
+            .unwrap();
+
+        let Err(err) = super::load_flake(&flake_path, false).await else {
+            panic!("expected loading a non-UTF-8 flake to fail");
+        };
+        match err.downcast_ref::<super::FhError>() {
+            Some(super::FhError::InvalidUtf8(path, offset)) => {
+                assert_eq!(path, &flake_path.display().to_string());
+                assert_eq!(*offset, 22);
+            }
+            _ => panic!("expected an invalid UTF-8 error, got: {err:?}"),
+        }
+
+        let (contents, _) = super::load_flake(&flake_path, true).await.unwrap();
+        assert!(contents.contains("caf\u{FFFD}"));
+    }
+
+    #[test]
+    fn dash_is_stdin() {
+        assert!(super::is_stdin(std::path::Path::new("-")));
+        assert!(!super::is_stdin(std::path::Path::new("./flake.nix")));
+    }
 }
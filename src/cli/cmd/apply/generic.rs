@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+pub(super) struct Generic {
+    /// The FlakeHub output reference for the configuration.
+    /// References must take the form {org}/{flake}/{version_req}#{attr_path}; there's no default
+    /// attribute path for generic configurations.
+    /// With `--flake`, this is a local flake reference like `./my-config#deploy` instead.
+    #[clap(value_parser = parse_output_ref_with_attr_path)]
+    pub(super) output_ref: String,
+
+    /// The activation script to run, relative to the profile, like `bin/activate`.
+    #[clap(long, value_name = "PATH", value_parser = parse_relative_path)]
+    pub(super) activate_path: PathBuf,
+
+    /// An argument to pass to the activation script, like `switch`.
+    #[clap(long)]
+    pub(super) action: Option<String>,
+
+    /// The profile to add the configuration to. Without one, the activation script runs from a
+    /// temporary profile.
+    #[clap(long)]
+    pub(super) profile: Option<PathBuf>,
+
+    /// Add the configuration to the profile as root (using sudo if necessary), and ask for
+    /// confirmation before applying it.
+    #[clap(long)]
+    pub(super) require_root: bool,
+}
+
+impl super::ApplyType for Generic {
+    fn get_ref(&self) -> &str {
+        &self.output_ref
+    }
+
+    fn default_ref(&self) -> String {
+        // `output_ref` always has an attribute path, so this is never used
+        String::new()
+    }
+
+    fn profile_path(&self) -> Option<&std::path::Path> {
+        self.profile.as_deref()
+    }
+
+    fn requires_root(&self) -> bool {
+        self.require_root
+    }
+
+    fn relative_path(&self) -> &std::path::Path {
+        &self.activate_path
+    }
+
+    fn action(&self) -> Option<String> {
+        self.action.clone()
+    }
+}
+
+fn parse_output_ref_with_attr_path(output_ref: &str) -> Result<String, String> {
+    match output_ref.split_once('#') {
+        Some((_, attr_path)) if !attr_path.is_empty() => Ok(output_ref.to_string()),
+        _ => Err(String::from(
+            "generic configurations have no default attribute path, so the reference needs one, \
+            like {org}/{flake}/{version_req}#{attr_path}",
+        )),
+    }
+}
+
+fn parse_relative_path(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    if path.is_absolute() || path.file_name().is_none() {
+        return Err(String::from(
+            "the activation path must be a file relative to the profile, like `bin/activate`",
+        ));
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn generic_args() {
+        assert!(super::parse_output_ref_with_attr_path("omnicorp/deploy/0.1#deploy").is_ok());
+        assert!(super::parse_output_ref_with_attr_path("omnicorp/deploy/0.1").is_err());
+        assert!(super::parse_output_ref_with_attr_path("omnicorp/deploy/0.1#").is_err());
+
+        assert!(super::parse_relative_path("bin/activate").is_ok());
+        assert!(super::parse_relative_path("/bin/activate").is_err());
+        assert!(super::parse_relative_path("").is_err());
+    }
+}
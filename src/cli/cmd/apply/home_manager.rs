@@ -4,7 +4,9 @@ use clap::Parser;
 pub(super) struct HomeManager {
     /// The FlakeHub output reference for the Home Manager configuration.
     /// References must take one of two forms: {org}/{flake}/{version_req}#{attr_path} or {org}/{flake}/{version_req}.
-    /// If the latter, the attribute path defaults to homeConfigurations.{whoami}.
+    /// If the latter, the attribute paths homeConfigurations.{whoami}@{hostname},
+    /// homeConfigurations.{whoami}, and homeConfigurations.{hostname} are tried in that order.
+    /// With `--flake`, this is a local flake reference like `./my-config` instead.
     pub(super) output_ref: String,
 }
 
@@ -17,6 +19,22 @@ impl super::ApplyType for HomeManager {
         format!("homeConfigurations.{}", whoami::username())
     }
 
+    // Home Manager's own `--flake` support looks for `user@host` before `user`
+    fn default_refs(&self) -> Vec<String> {
+        let username = whoami::username();
+        let hostname = gethostname::gethostname().to_string_lossy().to_string();
+
+        vec![
+            format!("homeConfigurations.{username}@{hostname}"),
+            self.default_ref(),
+            format!("homeConfigurations.{hostname}"),
+        ]
+    }
+
+    fn build_attr(&self) -> Option<&str> {
+        Some("activationPackage")
+    }
+
     fn profile_path(&self) -> Option<&std::path::Path> {
         None
     }
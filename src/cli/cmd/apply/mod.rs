@@ -1,8 +1,10 @@
+mod generic;
 mod home_manager;
 mod nix_darwin;
 mod nixos;
 
 use std::{
+    io::IsTerminal as _,
     os::unix::prelude::PermissionsExt,
     path::{Path, PathBuf},
     process::{ExitCode, Stdio},
@@ -13,9 +15,16 @@ use color_eyre::eyre::Context;
 use tempfile::{tempdir, TempDir};
 use tokio::io::AsyncWriteExt as _;
 
-use crate::cli::{cmd::nix_command, error::FhError};
+use crate::{
+    cli::{
+        cmd::{init::prompt::Prompt, is_root_user, nix_command_captured, nix_process, SudoChoice},
+        error::FhError,
+    },
+    info_println,
+    shared::progress::{ProgressFormat, Reporter, Spinner},
+};
 
-use self::{home_manager::HomeManager, nix_darwin::NixDarwin, nixos::NixOs};
+use self::{generic::Generic, home_manager::HomeManager, nix_darwin::NixDarwin, nixos::NixOs};
 
 use super::{CommandExecute, FlakeHubClient};
 
@@ -49,6 +58,52 @@ pub(crate) struct ApplySubcommand {
     #[clap(long, default_value_t = TokenChoice::Always)]
     use_scoped_token: TokenChoice,
 
+    /// Apply the configuration without asking for confirmation first.
+    /// Required when applying to a system profile without an interactive terminal.
+    #[clap(long, short)]
+    yes: bool,
+
+    /// Only check that the configuration would apply: resolve it and fetch its closure into a
+    /// temporary profile, which is discarded afterwards. Nothing is activated.
+    #[clap(long)]
+    verify: bool,
+
+    /// Resolve the configuration and print what applying it would do, without fetching it,
+    /// changing any profile, or running its activation script.
+    #[clap(long, conflicts_with = "verify")]
+    dry_run: bool,
+
+    /// Treat the output reference as a local flake reference, like
+    /// `./my-config#nixosConfigurations.host`, and build it with Nix instead of resolving it on
+    /// FlakeHub. The attribute path defaults the same way it does for FlakeHub references, and
+    /// the configuration's own build attribute (like `config.system.build.toplevel`) is built.
+    #[clap(long, global = true)]
+    flake: bool,
+
+    /// Pass `--impure` to `nix build` when adding the configuration to the profile.
+    #[clap(long)]
+    impure: bool,
+
+    /// An extra argument to pass to `nix build` when adding the configuration to the profile.
+    /// Can be repeated. Flags like `--impure` can make the result depend on the machine it's
+    /// applied on, so use this with care.
+    #[clap(long = "nix-arg", value_name = "ARG", allow_hyphen_values = true)]
+    nix_args: Vec<String>,
+
+    /// How to report progress: as log messages, or as newline-delimited JSON events on stderr.
+    #[clap(long, default_value_t = ProgressFormat::Human)]
+    progress_format: ProgressFormat,
+
+    /// Always run Nix with sudo to add the configuration to the profile, even when fh wouldn't
+    /// otherwise think it necessary.
+    #[clap(long, conflicts_with = "no_sudo")]
+    sudo: bool,
+
+    /// Never run Nix with sudo, even for a system profile when fh isn't running as root, like in
+    /// containers or with rootless Nix.
+    #[clap(long)]
+    no_sudo: bool,
+
     #[clap(from_global)]
     api_addr: url::Url,
 
@@ -57,10 +112,17 @@ pub(crate) struct ApplySubcommand {
 
     #[clap(from_global)]
     cache_addr: url::Url,
+
+    #[clap(from_global)]
+    print_commands: bool,
 }
 
 #[derive(Subcommand)]
 enum System {
+    /// Resolve the store path for any configuration, and run the activation script given on the
+    /// command line
+    Generic(Generic),
+
     /// Resolve the store path for a Home Manager configuration and run its activation script
     HomeManager(HomeManager),
 
@@ -77,6 +139,17 @@ pub trait ApplyType {
 
     fn default_ref(&self) -> String;
 
+    /// The attribute paths to try, in order, when the output reference doesn't have one.
+    fn default_refs(&self) -> Vec<String> {
+        vec![self.default_ref()]
+    }
+
+    /// The attribute of a configuration that builds it, for when `--flake` builds it locally. FlakeHub
+    /// resolves configurations to this on its own.
+    fn build_attr(&self) -> Option<&str> {
+        None
+    }
+
     fn profile_path(&self) -> Option<&Path>;
 
     fn requires_root(&self) -> bool;
@@ -90,36 +163,111 @@ pub trait ApplyType {
 impl CommandExecute for ApplySubcommand {
     async fn execute(self) -> color_eyre::Result<ExitCode> {
         let applyer: Box<&(dyn ApplyType + Send + Sync)> = match &self.system {
+            System::Generic(generic) => Box::new(generic),
             System::HomeManager(home_manager) => Box::new(home_manager),
             System::NixOs(nixos) => Box::new(nixos),
             System::NixDarwin(nix_darwin) => Box::new(nix_darwin),
         };
 
-        let output_ref = {
-            parse_output_ref(
+        let mut extra_nix_args = self.nix_args.clone();
+        if self.impure {
+            extra_nix_args.push(String::from("--impure"));
+        }
+        // Catch these before spending time resolving and copying anything
+        validate_extra_nix_args(&extra_nix_args)?;
+
+        let progress = Reporter::new(self.progress_format);
+
+        // Holds the `nix copy --out-link` (or local `nix build --out-link`) GC root, if any, until
+        // the path is in the profile
+        let mut copy_gc_root: Option<TempDir> = None;
+
+        let (output_ref, store_path, token) = if self.flake {
+            let flake_ref = local_flake_ref_with_defaults(
+                applyer.get_ref(),
+                &applyer.default_refs(),
+                applyer.build_attr(),
+                self.print_commands,
+            )
+            .await?;
+
+            progress.phase("build", 0, &format!("Building {flake_ref}"));
+            let (store_path, gc_root) =
+                build_local_flake(&flake_ref, &extra_nix_args, self.print_commands).await?;
+            copy_gc_root = Some(gc_root);
+            tracing::debug!("Successfully built {} to path {}", &flake_ref, &store_path);
+
+            (flake_ref, store_path, None)
+        } else {
+            progress.phase(
+                "resolve",
+                0,
+                &format!("Resolving output reference {}", applyer.get_ref()),
+            );
+
+            let (output_ref, resolved_path) = resolve_with_defaults(
+                self.api_addr.as_ref(),
                 &self.frontend_addr,
                 applyer.get_ref(),
-                &applyer.default_ref(),
-            )?
+                &applyer.default_refs(),
+                self.use_scoped_token == TokenChoice::Always,
+            )
+            .await?;
+            tracing::debug!(
+                "Successfully resolved reference {} to path {}",
+                &output_ref,
+                &resolved_path.store_path
+            );
+
+            (
+                output_ref.to_string(),
+                resolved_path.store_path,
+                resolved_path.token,
+            )
         };
 
-        tracing::info!(%output_ref, "Resolving output reference");
+        // Verifying never touches the real profile, so there's nothing to confirm
+        let profile_path = if self.verify {
+            None
+        } else {
+            applyer.profile_path()
+        };
 
-        let resolved_path = FlakeHubClient::resolve(
-            self.api_addr.as_ref(),
-            &output_ref,
-            self.use_scoped_token == TokenChoice::Always,
-        )
-        .await?;
-        tracing::debug!(
-            "Successfully resolved reference {} to path {}",
-            &output_ref,
-            &resolved_path.store_path
-        );
+        if self.dry_run {
+            print!(
+                "{}",
+                dry_run_plan(
+                    &output_ref,
+                    &store_path,
+                    profile_path,
+                    *applyer,
+                    self.sudo_choice(applyer.requires_root() && !self.verify),
+                )
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        if applyer.requires_root() && !self.yes && !self.verify {
+            let target = profile_path
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| String::from("a temporary profile"));
+
+            if !std::io::stdin().is_terminal() {
+                return Err(color_eyre::eyre::eyre!(
+                    "refusing to apply {store_path} to {target} without confirmation; pass `--yes` to \
+                    apply it non-interactively"
+                ));
+            }
 
-        let profile_path = applyer.profile_path();
+            if !Prompt::bool(&format!("Apply {store_path} to {target}?")) {
+                info_println!("Not applying {store_path}");
+                return Ok(ExitCode::SUCCESS);
+            }
+        }
 
-        match resolved_path.token {
+        match token {
+            // A local build is already in the store, so there's nothing to copy
+            _ if self.flake => {}
             Some(token) => {
                 if self.use_scoped_token == TokenChoice::Always {
                     let mut nix_args = vec![
@@ -129,7 +277,7 @@ impl CommandExecute for ApplySubcommand {
                         "0".to_string(),
                         "--from".to_string(),
                         self.cache_addr.to_string(),
-                        resolved_path.store_path.clone(),
+                        store_path.clone(),
                     ];
 
                     let dir = tempdir()?;
@@ -155,6 +303,14 @@ impl CommandExecute for ApplySubcommand {
                     let display = temp_netrc_path.display().to_string();
                     nix_args.extend_from_slice(&["--netrc-file".to_string(), display]);
 
+                    let copy_supports_out_link = crate::shared::nix_info::nix_info()
+                        .await
+                        .is_some_and(|info| info.copy_supports_out_link());
+                    if copy_supports_out_link {
+                        let out_link = dir.path().join("result").display().to_string();
+                        nix_args.extend_from_slice(&["--out-link".to_string(), out_link]);
+                    }
+
                     // NOTE(cole-h): Theoretically, this could be garbage collected immediately after we
                     // copy it. There's no good way to prevent this at this point in time because:
                     //
@@ -176,13 +332,30 @@ impl CommandExecute for ApplySubcommand {
                     // I will not entertain it, and holding a handle to it requires it to exist in the
                     // first place (so there's still a small window of time where it can be GC'd)
                     //
-                    // This will be resolved when https://github.com/NixOS/nix/pull/11657 makes it into
-                    // a Nix release.
-                    nix_command(&nix_args, false)
+                    // https://github.com/NixOS/nix/pull/11657 resolves this by adding `--out-link` to
+                    // `nix copy`, so on Nix versions that have it we keep that GC root around until the
+                    // path has been added to the profile.
+                    progress.phase(
+                        "copy",
+                        25,
+                        &format!("Copying {store_path} from FlakeHub Cache"),
+                    );
+                    // JSON progress events are already reporting this phase, and a spinner would
+                    // garble them
+                    let spinner = (self.progress_format == ProgressFormat::Human).then(|| {
+                        Spinner::start(format!("Copying {store_path} from FlakeHub Cache..."))
+                    });
+                    nix_command_captured(&nix_args, SudoChoice::Never, self.print_commands)
                         .await
                         .wrap_err("failed to copy resolved store path with Nix")?;
+                    drop(spinner);
 
-                    dir.close()?;
+                    if copy_supports_out_link {
+                        tokio::fs::remove_file(&temp_netrc_path).await?;
+                        copy_gc_root = Some(dir);
+                    } else {
+                        dir.close()?;
+                    }
                 } else {
                     tracing::warn!(
                         "Received a scoped token from FlakeHub, but we didn't request one! Ignoring."
@@ -198,15 +371,36 @@ impl CommandExecute for ApplySubcommand {
             }
         }
 
-        let (profile_path, _tempdir) = apply_path_to_profile(
+        progress.phase("build", 50, &format!("Adding {store_path} to the profile"));
+        let (profile_path, temp_profile) = apply_path_to_profile(
             profile_path,
-            &resolved_path.store_path,
-            applyer.requires_root(),
+            &store_path,
+            self.sudo_choice(applyer.requires_root() && !self.verify),
+            self.print_commands,
+            &extra_nix_args,
         )
         .await?;
 
+        if let Some(dir) = copy_gc_root {
+            dir.close()?;
+        }
+
+        if self.verify {
+            if let Some(dir) = temp_profile {
+                dir.close()?;
+            }
+
+            progress.done(&format!(
+                "Verified that {output_ref} resolves to {store_path} and that its closure can be \
+                fetched"
+            ));
+
+            return Ok(ExitCode::SUCCESS);
+        }
+
         let script_path = profile_path.join(applyer.relative_path());
 
+        progress.phase("activate", 75, &format!("Activating {store_path}"));
         run_script(
             script_path,
             applyer.action(),
@@ -218,10 +412,72 @@ impl CommandExecute for ApplySubcommand {
         )
         .await?;
 
+        progress.done(&format!("Applied {store_path}"));
+
         Ok(ExitCode::SUCCESS)
     }
 }
 
+impl ApplySubcommand {
+    // Whether to use sudo for a Nix command that `needs_root`: `--sudo` and `--no-sudo` override
+    // fh's own judgement
+    fn sudo_choice(&self, needs_root: bool) -> SudoChoice {
+        if self.sudo {
+            SudoChoice::Always
+        } else if self.no_sudo || !needs_root {
+            SudoChoice::Never
+        } else {
+            SudoChoice::Auto
+        }
+    }
+}
+
+// Resolves `output_ref`, trying each of `default_paths` in turn if it doesn't have an attribute path
+// of its own, and listing every one that was tried if none of them exist.
+async fn resolve_with_defaults(
+    api_addr: &str,
+    frontend_addr: &url::Url,
+    output_ref: &str,
+    default_paths: &[String],
+    include_token: bool,
+) -> color_eyre::Result<(super::FlakeOutputRef, super::resolve::ResolvedPath)> {
+    let candidates = if output_ref.contains('#') {
+        &default_paths[..default_paths.len().min(1)]
+    } else {
+        default_paths
+    };
+
+    let mut tried = Vec::new();
+    for default_path in candidates {
+        let parsed = parse_output_ref(frontend_addr, output_ref, default_path)?;
+
+        match FlakeHubClient::resolve(api_addr, &parsed, include_token).await {
+            Ok(resolved_path) => return Ok((parsed, resolved_path)),
+            Err(FhError::NotFound(kind, _))
+                if kind == "output reference" && candidates.len() > 1 =>
+            {
+                tracing::debug!("{parsed} doesn't exist");
+                tried.push(parsed.attr_path);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(no_default_attr_path(output_ref, &tried))
+}
+
+fn no_default_attr_path(output_ref: &str, tried: &[String]) -> color_eyre::Report {
+    color_eyre::eyre::eyre!(
+        "{output_ref} has none of the default attribute paths: tried {}. Give the right one \
+        explicitly, like `{output_ref}#{{attr_path}}`",
+        tried
+            .iter()
+            .map(|attr_path| format!("`{attr_path}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
 // This function enables you to provide simplified paths:
 //
 // fh apply nixos omnicorp/systems/0.1
@@ -248,6 +504,162 @@ fn parse_output_ref(
     parsed.try_into()
 }
 
+// What `fh apply --dry-run` reports instead of applying anything. Only adding the store path to
+// the profile goes through `sudo`; the activation script runs as the current user.
+fn dry_run_plan(
+    output_ref: &str,
+    store_path: &str,
+    profile_path: Option<&Path>,
+    applyer: &dyn ApplyType,
+    sudo: SudoChoice,
+) -> String {
+    let profile = profile_path
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| String::from("a temporary profile"));
+    let profile_command = format!(
+        "{}nix build --profile {} {store_path}",
+        if sudo.escalates(is_root_user()) {
+            "sudo "
+        } else {
+            ""
+        },
+        profile_path
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| String::from("<temporary profile>"))
+    );
+
+    // Without a profile to add it to, the script runs from a temporary profile that points
+    // straight at the store path
+    let script_path = profile_path
+        .unwrap_or(Path::new(store_path))
+        .join(applyer.relative_path());
+    let activation = match applyer.action() {
+        Some(action) => format!("{} {action}", script_path.display()),
+        None => script_path.display().to_string(),
+    };
+
+    format!(
+        "Would apply: {output_ref}\n\
+        Store path: {store_path}\n\
+        Profile: {profile}\n\
+        Profile command: {profile_command}\n\
+        Activation command: {activation}\n"
+    )
+}
+
+// The local counterpart of `resolve_with_defaults`: picks the first of `default_paths` that the
+// flake has if `flake_ref` doesn't have an attribute path of its own, and points it at the
+// attribute that builds the configuration.
+async fn local_flake_ref_with_defaults(
+    flake_ref: &str,
+    default_paths: &[String],
+    build_attr: Option<&str>,
+    print_command: bool,
+) -> color_eyre::Result<String> {
+    if flake_ref.contains('#') || default_paths.len() < 2 {
+        let default_path = default_paths
+            .first()
+            .map(String::as_str)
+            .unwrap_or_default();
+        return Ok(local_flake_ref(flake_ref, default_path, build_attr)?);
+    }
+
+    for default_path in default_paths {
+        let candidate = local_flake_ref(flake_ref, default_path, None)?;
+
+        if local_flake_has_attr(&candidate, print_command).await? {
+            return Ok(local_flake_ref(flake_ref, default_path, build_attr)?);
+        }
+        tracing::debug!("{candidate} doesn't exist");
+    }
+
+    Err(no_default_attr_path(flake_ref, default_paths))
+}
+
+// Adds the default attribute path to a local flake reference that doesn't have one, just like
+// `parse_output_ref` does for FlakeHub references, followed by `build_attr` if it isn't there
+// already.
+fn local_flake_ref(
+    flake_ref: &str,
+    default_path: &str,
+    build_attr: Option<&str>,
+) -> Result<String, FhError> {
+    let (flake, attr_path) = match flake_ref.split('#').collect::<Vec<_>>()[..] {
+        [flake, attr_path] if !flake.is_empty() && !attr_path.is_empty() => (flake, attr_path),
+        [flake] if !flake.is_empty() => (flake, default_path),
+        _ => return Err(FhError::MalformedOutputRef(flake_ref.to_string())),
+    };
+
+    match build_attr {
+        Some(build_attr) if !attr_path.ends_with(&format!(".{build_attr}")) => {
+            Ok(format!("{flake}#{attr_path}.{build_attr}"))
+        }
+        _ => Ok(format!("{flake}#{attr_path}")),
+    }
+}
+
+// Whether a local flake has the attribute that `flake_ref` points at, without evaluating it.
+async fn local_flake_has_attr(flake_ref: &str, print_command: bool) -> color_eyre::Result<bool> {
+    let mut cmd = nix_process("nix")?;
+    cmd.args(["--extra-experimental-features", "nix-command flakes"])
+        .args(["eval", flake_ref, "--apply", "_: true"])
+        .stdin(std::process::Stdio::null());
+    let cmd_str = format!("{:?}", cmd.as_std());
+    tracing::debug!("Running: {:?}", cmd_str);
+    if print_command {
+        crate::shared::progress::eprintln_above_spinner(&cmd_str);
+    }
+
+    let output = cmd
+        .output()
+        .await
+        .wrap_err_with(|| format!("failed to evaluate {flake_ref} with Nix"))?;
+
+    if output.status.success() {
+        return Ok(true);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("does not provide attribute") {
+        Ok(false)
+    } else {
+        Err(FhError::FailedNixCommand(cmd_str, Some(stderr.trim_end().to_string())).into())
+    }
+}
+
+// Builds a configuration from a local flake, returning its store path along with the directory
+// holding the GC root that keeps it alive until it's in the profile.
+async fn build_local_flake(
+    flake_ref: &str,
+    extra_nix_args: &[String],
+    print_command: bool,
+) -> color_eyre::Result<(String, TempDir)> {
+    let dir = tempdir()?;
+    let out_link = dir.path().join("result");
+
+    let mut args = vec![
+        "build".to_string(),
+        "--print-build-logs".to_string(),
+        "--out-link".to_string(),
+        out_link
+            .to_str()
+            .ok_or(FhError::InvalidProfile)?
+            .to_string(),
+    ];
+    args.extend_from_slice(extra_nix_args);
+    args.push(flake_ref.to_string());
+
+    nix_command_captured(&args, SudoChoice::Never, print_command)
+        .await
+        .wrap_err_with(|| format!("failed to build {flake_ref} with Nix"))?;
+
+    let store_path = tokio::fs::read_link(&out_link)
+        .await
+        .wrap_err_with(|| format!("`nix build` didn't create a result for {flake_ref}"))?;
+
+    Ok((store_path.display().to_string(), dir))
+}
+
 async fn run_script(
     script_path: PathBuf,
     action: Option<String>,
@@ -302,7 +714,9 @@ async fn run_script(
 async fn apply_path_to_profile(
     input_profile_path: Option<&Path>,
     store_path: &str,
-    sudo_if_necessary: bool,
+    sudo: SudoChoice,
+    print_command: bool,
+    extra_nix_args: &[String],
 ) -> Result<(PathBuf, Option<TempDir>), FhError> {
     let temp_handle: Option<TempDir>;
 
@@ -323,28 +737,10 @@ async fn apply_path_to_profile(
         profile_path
     };
 
-    nix_command(
-        &[
-            "build".to_string(),
-            // Don't create a result symlink in the current directory for the profile being installed.
-            // This is verified to not introduce a race condition against an eager garbage collection.
-            "--no-link".to_string(),
-            "--print-build-logs".to_string(),
-            // `--max-jobs 0` ensures that `nix build` doesn't really *build* anything
-            // and acts more as a fetch operation
-            "--max-jobs".to_string(),
-            "0".to_string(),
-            "--option".to_string(),
-            "narinfo-cache-negative-ttl".to_string(),
-            "0".to_string(),
-            "--profile".to_string(),
-            profile_path
-                .to_str()
-                .ok_or(FhError::InvalidProfile)?
-                .to_string(),
-            store_path.to_string(),
-        ],
-        sudo_if_necessary,
+    nix_command_captured(
+        &nix_build_args(&profile_path, store_path, extra_nix_args)?,
+        sudo,
+        print_command,
     )
     .await
     .wrap_err("failed to build resolved store path with Nix")?;
@@ -360,9 +756,326 @@ async fn apply_path_to_profile(
     Ok((profile_path, temp_handle))
 }
 
+// Flags that fh sets itself when building the profile, which extra arguments can't override
+const FIXED_NIX_BUILD_FLAGS: &[&str] =
+    &["--no-link", "--out-link", "--profile", "--max-jobs", "-j"];
+
+fn validate_extra_nix_args(extra_nix_args: &[String]) -> Result<(), FhError> {
+    match extra_nix_args
+        .iter()
+        .find(|arg| FIXED_NIX_BUILD_FLAGS.contains(&arg.split('=').next().unwrap_or(arg)))
+    {
+        Some(arg) => Err(FhError::InvalidNixArg(arg.to_string())),
+        None => Ok(()),
+    }
+}
+
+fn nix_build_args(
+    profile_path: &Path,
+    store_path: &str,
+    extra_nix_args: &[String],
+) -> Result<Vec<String>, FhError> {
+    validate_extra_nix_args(extra_nix_args)?;
+
+    let mut args = vec![
+        "build".to_string(),
+        // Don't create a result symlink in the current directory for the profile being installed.
+        // This is verified to not introduce a race condition against an eager garbage collection.
+        "--no-link".to_string(),
+        "--print-build-logs".to_string(),
+        // `--max-jobs 0` ensures that `nix build` doesn't really *build* anything
+        // and acts more as a fetch operation
+        "--max-jobs".to_string(),
+        "0".to_string(),
+        "--option".to_string(),
+        "narinfo-cache-negative-ttl".to_string(),
+        "0".to_string(),
+        "--profile".to_string(),
+        profile_path
+            .to_str()
+            .ok_or(FhError::InvalidProfile)?
+            .to_string(),
+    ];
+    // Extra arguments go after the fixed ones, but before the installable
+    args.extend_from_slice(extra_nix_args);
+    args.push(store_path.to_string());
+
+    Ok(args)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_output_ref;
+    use axum::{extract::Path, response::IntoResponse};
+
+    use super::{parse_output_ref, ApplyType as _, SudoChoice};
+    use crate::cli::{cmd::FlakeHubClient, error::FhError};
+
+    // Only knows about omnicorp/systems 0.1.5, and only when it's asked for exactly
+    async fn output(
+        Path((org, project, version, attr_path)): Path<(String, String, String, String)>,
+    ) -> axum::response::Response {
+        if (org.as_str(), project.as_str(), version.as_str()) != ("omnicorp", "systems", "=0.1.5") {
+            return axum::http::StatusCode::NOT_FOUND.into_response();
+        }
+
+        axum::Json(serde_json::json!({
+            "attribute_path": attr_path,
+            "store_path": "/nix/store/xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx-nixos-system-0.1.5",
+            "token": null,
+        }))
+        .into_response()
+    }
+
+    fn test_server() -> axum_test::TestServer {
+        let router = axum::Router::new().route(
+            "/f/:org/:project/:version/output/:attr_path",
+            axum::routing::get(output),
+        );
+        crate::cli::cmd::test_server(router)
+    }
+
+    #[tokio::test]
+    async fn exact_version_apply_resolves_that_version() {
+        let test_server = test_server();
+        let server_url = test_server.server_address().unwrap();
+        let frontend_addr = url::Url::parse("https://flakehub.com/f").unwrap();
+
+        let output_ref = parse_output_ref(
+            &frontend_addr,
+            "omnicorp/systems/0.1.5",
+            "nixosConfigurations.hostname",
+        )
+        .unwrap();
+        let resolved = FlakeHubClient::resolve(server_url.as_str(), &output_ref, false)
+            .await
+            .unwrap();
+        assert!(resolved.store_path.ends_with("-nixos-system-0.1.5"));
+
+        let output_ref = parse_output_ref(
+            &frontend_addr,
+            "omnicorp/systems/0.1.6",
+            "nixosConfigurations.hostname",
+        )
+        .unwrap();
+        let Err(err) = FlakeHubClient::resolve(server_url.as_str(), &output_ref, false).await
+        else {
+            panic!("resolved a version that doesn't exist");
+        };
+        assert!(matches!(err, FhError::NotFound(ref kind, ref version)
+            if kind == "version" && version == "omnicorp/systems/0.1.6"));
+    }
+
+    #[tokio::test]
+    async fn default_paths_are_tried_in_order() {
+        // Only has a configuration for the bare user name
+        async fn output(
+            Path((_, _, _, attr_path)): Path<(String, String, String, String)>,
+        ) -> axum::response::Response {
+            if attr_path != "homeConfigurations.me" {
+                return axum::http::StatusCode::NOT_FOUND.into_response();
+            }
+
+            axum::Json(serde_json::json!({
+                "attribute_path": attr_path,
+                "store_path": "/nix/store/xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx-home-manager-generation",
+                "token": null,
+            }))
+            .into_response()
+        }
+
+        let router = axum::Router::new().route(
+            "/f/:org/:project/:version/output/:attr_path",
+            axum::routing::get(output),
+        );
+        let test_server = crate::cli::cmd::test_server(router);
+        let server_url = test_server.server_address().unwrap();
+        let frontend_addr = url::Url::parse("https://flakehub.com/f").unwrap();
+
+        let defaults = [
+            String::from("homeConfigurations.me@host"),
+            String::from("homeConfigurations.me"),
+            String::from("homeConfigurations.host"),
+        ];
+
+        let (output_ref, _) = super::resolve_with_defaults(
+            server_url.as_str(),
+            &frontend_addr,
+            "omnicorp/homes/0.1",
+            &defaults,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(output_ref.attr_path, "homeConfigurations.me");
+
+        let Err(err) = super::resolve_with_defaults(
+            server_url.as_str(),
+            &frontend_addr,
+            "omnicorp/homes/0.1",
+            &defaults[..1],
+            false,
+        )
+        .await
+        else {
+            panic!("resolved an attribute path that doesn't exist");
+        };
+        assert!(err.to_string().contains("not found"));
+
+        let defaults = [
+            String::from("homeConfigurations.me@host"),
+            String::from("homeConfigurations.host"),
+        ];
+        let Err(err) = super::resolve_with_defaults(
+            server_url.as_str(),
+            &frontend_addr,
+            "omnicorp/homes/0.1",
+            &defaults,
+            false,
+        )
+        .await
+        else {
+            panic!("resolved an attribute path that doesn't exist");
+        };
+        assert!(err
+            .to_string()
+            .contains("tried `homeConfigurations.me@host`, `homeConfigurations.host`"));
+    }
+
+    #[test]
+    fn extra_nix_build_args() {
+        let profile = std::path::Path::new("/nix/var/nix/profiles/system");
+        let store_path = "/nix/store/xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx-nixos-system";
+
+        let args = super::nix_build_args(
+            profile,
+            store_path,
+            &[String::from("--impure"), String::from("--show-trace")],
+        )
+        .unwrap();
+        let profile_index = args.iter().position(|arg| arg == "--profile").unwrap();
+        assert_eq!(
+            &args[profile_index + 2..],
+            ["--impure", "--show-trace", store_path]
+        );
+
+        for fixed in ["--profile", "--no-link", "--max-jobs=4"] {
+            assert!(matches!(
+                super::nix_build_args(profile, store_path, &[String::from(fixed)]),
+                Err(FhError::InvalidNixArg(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn dry_run_plan() {
+        let nixos = super::nixos::NixOs {
+            output_ref: String::from("omnicorp/systems/0.1"),
+            action: super::nixos::NixOsAction::Boot,
+        };
+        let store_path = "/nix/store/xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx-nixos-system-0.1.5";
+
+        let plan = super::dry_run_plan(
+            "omnicorp/systems/0.1#nixosConfigurations.host",
+            store_path,
+            nixos.profile_path(),
+            &nixos,
+            SudoChoice::Always,
+        );
+        assert!(plan.contains(&format!("Store path: {store_path}\n")));
+        assert!(plan.contains("Profile: /nix/var/nix/profiles/system\n"));
+        assert!(plan.contains(&format!(
+            "Profile command: sudo nix build --profile /nix/var/nix/profiles/system {store_path}\n"
+        )));
+        // The activation script never runs through sudo
+        assert!(plan.contains(
+            "Activation command: /nix/var/nix/profiles/system/bin/switch-to-configuration boot\n"
+        ));
+
+        let home_manager = super::home_manager::HomeManager {
+            output_ref: String::from("omnicorp/homes/0.1"),
+        };
+        let plan = super::dry_run_plan(
+            "omnicorp/homes/0.1#homeConfigurations.me",
+            store_path,
+            None,
+            &home_manager,
+            SudoChoice::Never,
+        );
+        assert!(plan.contains("Profile: a temporary profile\n"));
+        assert!(plan.contains(&format!(
+            "Profile command: nix build --profile <temporary profile> {store_path}\n"
+        )));
+        assert!(plan.contains(&format!("Activation command: {store_path}/activate\n")));
+    }
+
+    #[test]
+    fn local_flake_ref_builds_the_configuration() {
+        let nixos = super::nixos::NixOs {
+            output_ref: String::from("./my-config"),
+            action: super::nixos::NixOsAction::Switch,
+        };
+        let home_manager = super::home_manager::HomeManager {
+            output_ref: String::from("./my-config"),
+        };
+
+        for (applyer, input, expect) in [
+            (
+                &nixos as &dyn super::ApplyType,
+                "./my-config#nixosConfigurations.host",
+                "./my-config#nixosConfigurations.host.config.system.build.toplevel",
+            ),
+            (
+                &nixos,
+                "./my-config#nixosConfigurations.host.config.system.build.toplevel",
+                "./my-config#nixosConfigurations.host.config.system.build.toplevel",
+            ),
+            (
+                &home_manager,
+                "./my-config#homeConfigurations.me",
+                "./my-config#homeConfigurations.me.activationPackage",
+            ),
+        ] {
+            assert_eq!(
+                super::local_flake_ref(input, &applyer.default_ref(), applyer.build_attr())
+                    .unwrap(),
+                expect
+            );
+        }
+
+        assert_eq!(
+            super::local_flake_ref("./my-config", &nixos.default_ref(), nixos.build_attr())
+                .unwrap(),
+            format!(
+                "./my-config#{}.config.system.build.toplevel",
+                nixos.default_ref()
+            )
+        );
+    }
+
+    #[test]
+    fn local_flake_ref() {
+        for (input, expect) in [
+            ("./my-config", "./my-config#DefaultFooBar"),
+            (
+                "./my-config#nixosConfigurations.host",
+                "./my-config#nixosConfigurations.host",
+            ),
+            ("/etc/nixos", "/etc/nixos#DefaultFooBar"),
+            (
+                "git+https://example.com/configs",
+                "git+https://example.com/configs#DefaultFooBar",
+            ),
+        ] {
+            assert_eq!(
+                super::local_flake_ref(input, "DefaultFooBar", None).unwrap(),
+                expect
+            );
+        }
+
+        for input in ["", "./my-config#", "#foo", "a#b#c"] {
+            assert!(super::local_flake_ref(input, "DefaultFooBar", None).is_err());
+        }
+    }
 
     #[test]
     fn test_parse_output_ref() {
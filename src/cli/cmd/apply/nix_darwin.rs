@@ -6,6 +6,7 @@ pub(super) struct NixDarwin {
     /// References must take one of two forms: {org}/{flake}/{version_req}#{attr_path} or {org}/{flake}/{version_req}.
     /// If the latter, the attribute path defaults to darwinConfigurations.{devicename}.system, where devicename
     /// is the output of scutil --get LocalHostName.
+    /// With `--flake`, this is a local flake reference like `./my-config` instead.
     pub(super) output_ref: String,
 
     #[arg(
@@ -26,6 +27,10 @@ impl super::ApplyType for NixDarwin {
         format!("darwinConfigurations.{}", whoami::devicename())
     }
 
+    fn build_attr(&self) -> Option<&str> {
+        Some("system")
+    }
+
     fn profile_path(&self) -> Option<&std::path::Path> {
         Some(&self.profile)
     }
@@ -7,6 +7,7 @@ pub(super) struct NixOs {
     /// The FlakeHub output reference to apply to the system profile.
     /// References must take one of two forms: {org}/{flake}/{version_req}#{attr_path} or {org}/{flake}/{version_req}.
     /// If the latter, the attribute path defaults to nixosConfigurations.{hostname}.
+    /// With `--flake`, this is a local flake reference like `./my-config` instead.
     pub(super) output_ref: String,
 
     /// The command to run from the profile's switch-to-configuration script.
@@ -27,6 +28,10 @@ impl super::ApplyType for NixOs {
         )
     }
 
+    fn build_attr(&self) -> Option<&str> {
+        Some("config.system.build.toplevel")
+    }
+
     fn profile_path(&self) -> Option<&std::path::Path> {
         Some(std::path::Path::new("/nix/var/nix/profiles/system"))
     }
@@ -0,0 +1,122 @@
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::eyre;
+
+use super::login::{configure_user_cache, CACHE_PUBLIC_KEYS};
+use super::{print_json, CommandExecute};
+use crate::info_println;
+
+/// Set up Nix to use FlakeHub Cache.
+#[derive(Debug, Parser)]
+pub(crate) struct CacheSubcommand {
+    #[command(subcommand)]
+    cmd: Subcommands,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+
+    #[clap(from_global)]
+    cache_addr: url::Url,
+
+    #[clap(from_global)]
+    frontend_addr: url::Url,
+}
+
+#[derive(Debug, Subcommand)]
+enum Subcommands {
+    /// Add FlakeHub Cache to your nix.conf and your FlakeHub token to your netrc, using the token
+    /// that you're already logged in with, like one enrolled with determinate-nixd.
+    Configure,
+    /// Print the FlakeHub Cache substituter and its public keys as nix.conf settings, for adding
+    /// them to Nix configurations that fh can't write to.
+    Keys {
+        /// Output the substituter and public keys as JSON.
+        #[arg(long, env = "FH_OUTPUT_JSON")]
+        json: bool,
+    },
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CacheKeys<'a> {
+    substituter: &'a url::Url,
+    public_keys: &'a [&'a str],
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for CacheSubcommand {
+    #[tracing::instrument(skip_all)]
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        match self.cmd {
+            Subcommands::Configure => {
+                let token = crate::cli::token::read_token().await?.ok_or_else(|| {
+                    eyre!(
+                        "no FlakeHub token was found; run `fh login`, or set `{}`, first",
+                        crate::cli::token::TOKEN_ENV_VAR
+                    )
+                })?;
+                tracing::debug!(
+                    "Using the FlakeHub token from the `{}` token source",
+                    token.source
+                );
+
+                configure_user_cache(
+                    &self.frontend_addr,
+                    &self.api_addr,
+                    &self.cache_addr,
+                    &token.token,
+                )
+                .await?;
+
+                info_println!(
+                    "Configured Nix to use FlakeHub Cache at {}",
+                    self.cache_addr
+                );
+            }
+            Subcommands::Keys { json } => {
+                if json {
+                    print_json(CacheKeys {
+                        substituter: &self.cache_addr,
+                        public_keys: CACHE_PUBLIC_KEYS,
+                    })?;
+                } else {
+                    print!("{}", nix_config_snippet(&self.cache_addr));
+                }
+            }
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn nix_config_snippet(cache_addr: &url::Url) -> String {
+    format!(
+        "extra-substituters = {cache_addr}\n\
+        extra-trusted-public-keys = {keys}\n",
+        keys = CACHE_PUBLIC_KEYS.join(" "),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn nix_config_snippet() {
+        let cache_addr = url::Url::parse("https://cache.flakehub.com").unwrap();
+        let snippet = super::nix_config_snippet(&cache_addr);
+
+        let config = nix_config_parser::NixConfig::parse_string(snippet, None).unwrap();
+        assert_eq!(
+            config.settings().get("extra-substituters").unwrap(),
+            "https://cache.flakehub.com/"
+        );
+        assert_eq!(
+            config
+                .settings()
+                .get("extra-trusted-public-keys")
+                .unwrap()
+                .split_whitespace()
+                .collect::<Vec<_>>(),
+            super::CACHE_PUBLIC_KEYS
+        );
+    }
+}
@@ -1,10 +1,88 @@
-use std::{io::stdout, process::ExitCode};
+use std::{
+    io::stdout,
+    io::Write as _,
+    path::Path,
+    process::ExitCode,
+    time::{Duration, SystemTime},
+};
 
-use crate::cli::Cli;
+use crate::cli::{error::FhError, Cli};
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, Shell};
+use serde::{Deserialize, Serialize};
 
-use super::CommandExecute;
+use super::{
+    list::{Flake, Org},
+    CommandExecute, FlakeHubClient,
+};
+
+/// The longest a completion waits for FlakeHub, in seconds. Completion runs while the user waits
+/// on a Tab press, so it gives up quickly (and without retrying) rather than offering nothing
+/// after a long pause.
+pub(crate) const COMPLETION_TIMEOUT_SECS: u64 = 3;
+
+// Listing every flake is too slow to do on each Tab press, so the list is kept this long
+const FLAKES_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+// Where the list of flakes is kept, under the XDG cache directory
+const FLAKES_CACHE_FILE: &str = "fh/completion-flakes.json";
+
+// The most versions to offer when completing a version constraint
+const MAX_VERSION_COMPLETIONS: usize = 100;
+
+// The most orgs or flakes to offer when completing a flake name
+const MAX_FLAKE_COMPLETIONS: usize = 100;
+
+// The argument spec that clap generates for `fh list versions`'s constraint
+const ZSH_CONSTRAINT_SPEC: &str = "':constraint -- The version constraint as a string:_default'";
+
+// The argument specs that clap generates for the flake names of `fh list releases`,
+// `fh list versions`, and `fh open`
+const ZSH_FLAKE_SPECS: &[&str] = &[
+    "':flake -- The flake for which you want to list releases:_default'",
+    "':flake -- The flake for which you want to list compatible versions:_default'",
+    "':flake_ref -- The flake to open, like `NixOS/nixpkgs`, or `NixOS/nixpkgs/0.2405.0` for a specific release:_default'",
+];
+
+const BASH_VERSION_COMPLETIONS: &str = r#"
+_fh_with_versions() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    if [[ ${COMP_CWORD} -eq 4 && "${COMP_WORDS[1]}" == "list" && "${COMP_WORDS[2]}" == "versions" ]]; then
+        COMPREPLY=( $(compgen -W "$(fh __complete-versions "${COMP_WORDS[3]}" 2>/dev/null)" -- "${cur}") )
+        return 0
+    fi
+    if [[ ${COMP_CWORD} -eq 3 && "${COMP_WORDS[1]}" == "list" && ( "${COMP_WORDS[2]}" == "releases" || "${COMP_WORDS[2]}" == "versions" ) ]] \
+        || [[ ${COMP_CWORD} -eq 2 && "${COMP_WORDS[1]}" == "open" ]]; then
+        COMPREPLY=( $(compgen -W "$(fh __complete-flakes "${cur}" 2>/dev/null)" -- "${cur}") )
+        # Orgs are offered as `org/`, to be followed by the flake's name
+        compopt -o nospace
+        return 0
+    fi
+    _fh "$@"
+}
+
+complete -F _fh_with_versions -o bashdefault -o default fh
+"#;
+
+const ZSH_VERSION_COMPLETIONS: &str = r#"
+_fh_versions() {
+    local -a versions
+    versions=(${(f)"$(fh __complete-versions ${words[CURRENT-1]} 2>/dev/null)"})
+    compadd -a versions
+}
+
+_fh_flakes() {
+    local -a flakes
+    flakes=(${(f)"$(fh __complete-flakes ${words[CURRENT]} 2>/dev/null)"})
+    compadd -S '' -a flakes
+}
+"#;
+
+const FISH_VERSION_COMPLETIONS: &str = r#"
+complete -c fh -n "__fish_fh_using_subcommand list; and __fish_seen_subcommand_from versions; and test (count (commandline -opc)) -eq 4" -f -a "(fh __complete-versions (commandline -opc)[4] 2>/dev/null)"
+complete -c fh -n "__fish_fh_using_subcommand list; and __fish_seen_subcommand_from releases versions; and test (count (commandline -opc)) -eq 3" -f -a "(fh __complete-flakes (commandline -ct) 2>/dev/null)"
+complete -c fh -n "__fish_fh_using_subcommand open; and test (count (commandline -opc)) -eq 2" -f -a "(fh __complete-flakes (commandline -ct) 2>/dev/null)"
+"#;
 
 /// Prints completion for shells to use.
 #[derive(Parser)]
@@ -18,8 +96,312 @@ pub(crate) struct CompletionSubcommand {
 impl CommandExecute for CompletionSubcommand {
     async fn execute(self) -> color_eyre::Result<ExitCode> {
         let cli = &mut Cli::command();
-        generate(self.shell, cli, cli.get_name().to_string(), &mut stdout());
+        let mut script = Vec::new();
+        generate(self.shell, cli, cli.get_name().to_string(), &mut script);
+
+        let script = add_version_completions(self.shell, String::from_utf8(script)?);
+        stdout().write_all(script.as_bytes())?;
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Prints the versions of a flake, for completing `fh list versions`.
+#[derive(Parser)]
+pub(crate) struct CompleteVersionsSubcommand {
+    /// The flake whose versions to print, like `NixOS/nixpkgs`.
+    flake: String,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for CompleteVersionsSubcommand {
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        // Completion should never print errors into the user's command line, so anything that goes
+        // wrong just means there's nothing to offer
+        let Ok(flake) = Flake::try_from(self.flake) else {
+            return Ok(ExitCode::SUCCESS);
+        };
+        let Ok(versions) = FlakeHubClient::versions(
+            self.api_addr.as_ref(),
+            &flake.org,
+            &flake.project,
+            "*",
+            Some(MAX_VERSION_COMPLETIONS),
+            None,
+        )
+        .await
+        else {
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        let mut simplified: Vec<semver::Version> =
+            versions.into_iter().map(|v| v.simplified_version).collect();
+        simplified.sort_by(|a, b| b.cmp(a));
+        simplified.dedup();
+
+        for version in simplified.into_iter().take(MAX_VERSION_COMPLETIONS) {
+            println!("{version}");
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Prints the orgs or flakes on FlakeHub that start with a partial flake name, for completing
+/// flake names.
+#[derive(Parser)]
+pub(crate) struct CompleteFlakesSubcommand {
+    /// What's been typed so far: part of an org's name, or `{org}/` and part of a flake's name.
+    #[clap(default_value = "")]
+    partial: String,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for CompleteFlakesSubcommand {
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        // As with versions, failing to fetch anything just means there's nothing to offer
+        let candidates = match self.partial.split_once('/') {
+            None => FlakeHubClient::orgs(self.api_addr.as_ref(), None)
+                .await
+                .map(|orgs| org_candidates(&self.partial, orgs)),
+            Some((org, project)) => cached_flakes(self.api_addr.as_ref())
+                .await
+                .map(|flakes| flake_candidates(org, project, flakes)),
+        };
+
+        for candidate in candidates.unwrap_or_default() {
+            println!("{candidate}");
+        }
 
         Ok(ExitCode::SUCCESS)
     }
 }
+
+#[derive(Deserialize, Serialize)]
+struct FlakesCache {
+    api_addr: String,
+    flakes: Vec<Flake>,
+}
+
+// Every flake on FlakeHub, from the cache if it's recent enough. Failing to read or write the cache
+// only means fetching the flakes again next time.
+async fn cached_flakes(api_addr: &str) -> Result<Vec<Flake>, FhError> {
+    let cache_path = xdg::BaseDirectories::new()
+        .ok()
+        .and_then(|xdg| xdg.place_cache_file(FLAKES_CACHE_FILE).ok());
+
+    if let Some(flakes) = cache_path
+        .as_deref()
+        .and_then(|path| read_flakes_cache(path, api_addr, SystemTime::now()))
+    {
+        return Ok(flakes);
+    }
+
+    let flakes = FlakeHubClient::flakes(api_addr, None, None).await?;
+
+    if let Some(path) = cache_path {
+        write_flakes_cache(&path, api_addr, &flakes);
+    }
+
+    Ok(flakes)
+}
+
+// The cached flakes, as long as they came from `api_addr` less than `FLAKES_CACHE_TTL` before `now`.
+fn read_flakes_cache(path: &Path, api_addr: &str, now: SystemTime) -> Option<Vec<Flake>> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    if now.duration_since(modified).ok()? > FLAKES_CACHE_TTL {
+        return None;
+    }
+
+    let cache: FlakesCache = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+
+    (cache.api_addr == api_addr).then_some(cache.flakes)
+}
+
+fn write_flakes_cache(path: &Path, api_addr: &str, flakes: &[Flake]) {
+    let cache = FlakesCache {
+        api_addr: api_addr.to_string(),
+        flakes: flakes.to_vec(),
+    };
+
+    if let Err(e) = serde_json::to_vec(&cache)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(path, json))
+    {
+        tracing::debug!("Couldn't cache the flakes for completion: {e}");
+    }
+}
+
+// The orgs whose names start with `partial`, as `{org}/`, ready for the flake's name.
+fn org_candidates(partial: &str, orgs: Vec<Org>) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    let mut candidates: Vec<String> = orgs
+        .into_iter()
+        .filter(|org| org.name.to_lowercase().starts_with(&partial))
+        .map(|org| format!("{}/", org.name))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates.truncate(MAX_FLAKE_COMPLETIONS);
+
+    candidates
+}
+
+// The flakes in `org` whose names start with `partial`, as `{org}/{project}`.
+fn flake_candidates(org: &str, partial: &str, flakes: Vec<Flake>) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    let mut candidates: Vec<String> = flakes
+        .into_iter()
+        .filter(|flake| {
+            flake.org.eq_ignore_ascii_case(org)
+                && flake.project.to_lowercase().starts_with(&partial)
+        })
+        .map(|flake| format!("{}/{}", flake.org, flake.project))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates.truncate(MAX_FLAKE_COMPLETIONS);
+
+    candidates
+}
+
+// Teach the generated scripts to offer a flake's versions for `fh list versions <flake> <TAB>`, and
+// FlakeHub's orgs and flakes wherever a flake's name goes.
+fn add_version_completions(shell: Shell, mut script: String) -> String {
+    match shell {
+        Shell::Bash => script.push_str(BASH_VERSION_COMPLETIONS),
+        Shell::Zsh => {
+            script = script.replacen(
+                ZSH_CONSTRAINT_SPEC,
+                &ZSH_CONSTRAINT_SPEC.replace("_default", "_fh_versions"),
+                1,
+            );
+            for spec in ZSH_FLAKE_SPECS {
+                script = script.replace(spec, &spec.replace(":_default'", ":_fh_flakes'"));
+            }
+
+            // The helper has to be defined before the script's trailing call to `_fh`
+            let after_compdef = script.find('\n').map_or(script.len(), |i| i + 1);
+            script.insert_str(after_compdef, ZSH_VERSION_COMPLETIONS);
+        }
+        Shell::Fish => script.push_str(FISH_VERSION_COMPLETIONS),
+        _ => {}
+    }
+
+    script
+}
+
+#[cfg(test)]
+mod test {
+    use clap::CommandFactory;
+    use clap_complete::Shell;
+
+    use crate::cli::Cli;
+
+    fn completions(shell: Shell) -> String {
+        let cli = &mut Cli::command();
+        let mut script = Vec::new();
+        clap_complete::generate(shell, cli, "fh", &mut script);
+
+        super::add_version_completions(shell, String::from_utf8(script).unwrap())
+    }
+
+    #[test]
+    fn version_completions() {
+        let bash = completions(Shell::Bash);
+        assert!(bash.contains("fh __complete-versions"));
+        assert!(bash
+            .trim_end()
+            .ends_with("complete -F _fh_with_versions -o bashdefault -o default fh"));
+
+        let zsh = completions(Shell::Zsh);
+        assert!(zsh.starts_with("#compdef fh\n"));
+        assert!(zsh.contains("_fh_versions() {"));
+        assert!(
+            zsh.contains("':constraint -- The version constraint as a string:_fh_versions'"),
+            "clap's zsh output changed; update ZSH_CONSTRAINT_SPEC"
+        );
+
+        for spec in super::ZSH_FLAKE_SPECS {
+            assert!(
+                zsh.contains(&spec.replace(":_default'", ":_fh_flakes'")),
+                "clap's zsh output changed; update ZSH_FLAKE_SPECS"
+            );
+        }
+
+        let fish = completions(Shell::Fish);
+        assert!(fish.contains("fh __complete-versions"));
+        assert!(fish.contains("fh __complete-flakes"));
+
+        // Other shells are left as clap generates them
+        assert!(!completions(Shell::Elvish).contains("_fh_versions"));
+    }
+
+    #[test]
+    fn flakes_cache() {
+        use std::time::{Duration, SystemTime};
+
+        use crate::cli::cmd::list::Flake;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("completion-flakes.json");
+        let flakes = [Flake {
+            org: String::from("NixOS"),
+            project: String::from("nixpkgs"),
+        }];
+        let api_addr = "https://api.flakehub.com/";
+
+        assert!(super::read_flakes_cache(&path, api_addr, SystemTime::now()).is_none());
+
+        super::write_flakes_cache(&path, api_addr, &flakes);
+        let cached = super::read_flakes_cache(&path, api_addr, SystemTime::now()).unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].project, "nixpkgs");
+
+        // Flakes from another FlakeHub, and ones that are too old, aren't used
+        assert!(
+            super::read_flakes_cache(&path, "http://localhost:8080/", SystemTime::now()).is_none()
+        );
+        let later = SystemTime::now() + super::FLAKES_CACHE_TTL + Duration::from_secs(1);
+        assert!(super::read_flakes_cache(&path, api_addr, later).is_none());
+    }
+
+    #[test]
+    fn flake_candidates() {
+        use crate::cli::cmd::list::{Flake, Org};
+
+        let orgs = ["NixOS", "nix-community", "DeterminateSystems"]
+            .map(|name| Org {
+                name: String::from(name),
+            })
+            .into_iter()
+            .collect();
+        assert_eq!(
+            super::org_candidates("nix", orgs),
+            ["NixOS/", "nix-community/"]
+        );
+
+        let flakes = [
+            ("NixOS", "nixpkgs"),
+            ("NixOS", "nix"),
+            ("NixOS", "hydra"),
+            ("nix-community", "nixvim"),
+        ]
+        .map(|(org, project)| Flake {
+            org: String::from(org),
+            project: String::from(project),
+        })
+        .into_iter()
+        .collect();
+        assert_eq!(
+            super::flake_candidates("nixos", "nix", flakes),
+            ["NixOS/nix", "NixOS/nixpkgs"]
+        );
+    }
+}
@@ -1,13 +1,16 @@
-use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::{ExitCode, Stdio};
 
 use clap::Parser;
 use color_eyre::eyre::Context;
 use once_cell::sync::Lazy;
+use serde::Serialize;
 use tracing::{span, Level};
 
-use super::{nix_command, CommandExecute};
+use super::{
+    nix_command_captured, print_json, CommandExecute, ProjectCanonicalNames, SourceHost, SudoChoice,
+};
 
 // match {nixos,nixpkgs,release}-YY.MM branches
 static RELEASE_BRANCH_REGEX: Lazy<regex::Regex> = Lazy::new(|| {
@@ -34,7 +37,7 @@ const FLAKE_COMPAT_CONTENTS_PREFIX: &str = r#"(import
 /// Convert flake inputs to FlakeHub when possible.
 #[derive(Debug, Parser)]
 pub(crate) struct ConvertSubcommand {
-    /// The flake.nix to convert.
+    /// The flake.nix to convert, or `-` to read it from stdin (implies `--dry-run`).
     #[clap(long, default_value = "./flake.nix")]
     pub(crate) flake_path: PathBuf,
 
@@ -42,23 +45,59 @@ pub(crate) struct ConvertSubcommand {
     #[clap(long)]
     pub(crate) dry_run: bool,
 
+    /// Replace any invalid UTF-8 in the flake.nix instead of refusing to edit it.
+    #[clap(long)]
+    pub(crate) lossy: bool,
+
+    /// Write the new flake.nix even if it no longer parses.
+    #[clap(long)]
+    pub(crate) force: bool,
+
+    /// Keep the currently locked revisions of converted inputs by rewriting their entries in
+    /// flake.lock, rather than letting `nix flake lock` fetch the latest matching releases.
+    #[clap(long)]
+    pub(crate) relock_preserve_revs: bool,
+
+    /// Print a JSON report of each input's old URL, new URL, and whether it was converted,
+    /// instead of the new flake.nix contents.
+    #[clap(long)]
+    pub(crate) json: bool,
+
+    /// Only convert this input. May be given more than once.
+    #[clap(long = "only", value_name = "INPUT", conflicts_with = "exclude")]
+    pub(crate) only: Vec<String>,
+
+    /// Leave this input as it is. May be given more than once.
+    #[clap(long = "exclude", value_name = "INPUT")]
+    pub(crate) exclude: Vec<String>,
+
     #[clap(from_global)]
     api_addr: url::Url,
+
+    #[clap(from_global)]
+    print_commands: bool,
 }
 
 #[async_trait::async_trait]
 impl CommandExecute for ConvertSubcommand {
     #[tracing::instrument(skip_all)]
-    async fn execute(self) -> color_eyre::Result<ExitCode> {
-        if !self.flake_path.exists() {
+    async fn execute(mut self) -> color_eyre::Result<ExitCode> {
+        let from_stdin = crate::cli::cmd::add::is_stdin(&self.flake_path);
+        // There's no file to write the result back to when the flake came from stdin
+        if from_stdin {
+            self.dry_run = true;
+        }
+
+        if !from_stdin && !self.flake_path.exists() {
             return Err(color_eyre::eyre::eyre!(
                 "the flake at {} did not exist",
                 self.flake_path.display()
             ));
         }
 
-        let (flake_contents, parsed) = crate::cli::cmd::add::load_flake(&self.flake_path).await?;
-        let (new_flake_contents, flake_compat_input_name) = self
+        let (flake_contents, parsed) =
+            crate::cli::cmd::add::load_flake(&self.flake_path, self.lossy).await?;
+        let (new_flake_contents, flake_compat_input_name, skipped) = self
             .convert_inputs_to_flakehub(&parsed.expression, &flake_contents)
             .await?;
         let new_flake_contents = self
@@ -78,16 +117,74 @@ impl CommandExecute for ConvertSubcommand {
             new_flake_contents
         };
 
-        if self.dry_run {
-            println!("{new_flake_contents}");
+        if self.json {
+            let mut report = ConversionReport::default();
+            report.add(
+                &self.flake_path,
+                input_conversions(
+                    &parsed.expression,
+                    &nixel::parse(new_flake_contents.clone()).expression,
+                )?,
+            );
+            print_json(report)?;
         } else {
-            tokio::fs::write(self.flake_path, new_flake_contents).await?;
+            if self.dry_run {
+                println!("{new_flake_contents}");
+            }
+
+            // `--quiet` leaves out the summary, so don't bother working it out
+            if !crate::cli::instrumentation::quiet() {
+                print_summary(
+                    input_conversions(
+                        &parsed.expression,
+                        &nixel::parse(new_flake_contents.clone()).expression,
+                    )?,
+                    &skipped,
+                );
+            }
+        }
+
+        if !self.dry_run {
+            if !self.force {
+                crate::cli::cmd::add::verify_edited_flake(&parsed.expression, &new_flake_contents)?;
+            }
+
+            crate::cli::cmd::add::write_edited_flake(
+                &self.flake_path,
+                &flake_contents,
+                &new_flake_contents,
+            )
+            .await?;
+
+            if self.relock_preserve_revs {
+                let lock_path = self.flake_path.with_file_name("flake.lock");
+
+                if lock_path.exists() {
+                    let parsed = nixel::parse(new_flake_contents);
+                    let mut lock = crate::shared::flake_lock::FlakeLock::read(&lock_path).await?;
 
+                    if preserve_locked_revs(&parsed.expression, &mut lock)? {
+                        lock.write(&lock_path).await?;
+                    }
+                } else {
+                    tracing::info!(
+                        "{} doesn't exist, so there are no locked revisions to preserve",
+                        lock_path.display()
+                    );
+                }
+            }
+
+            // Any inputs whose lock entries were rewritten above already match, so this only
+            // locks the inputs that are new or couldn't be rewritten
             tracing::debug!("Running: nix flake lock");
 
-            nix_command(&["flake".to_string(), "lock".to_string()], false)
-                .await
-                .wrap_err("failed to create missing lock file entries")?;
+            nix_command_captured(
+                &["flake".to_string(), "lock".to_string()],
+                SudoChoice::Never,
+                self.print_commands,
+            )
+            .await
+            .wrap_err("failed to create missing lock file entries")?;
         }
 
         Ok(ExitCode::SUCCESS)
@@ -95,13 +192,24 @@ impl CommandExecute for ConvertSubcommand {
 }
 
 impl ConvertSubcommand {
+    // Whether `--only` and `--exclude` allow converting `input_name`.
+    fn should_convert(&self, input_name: &str) -> bool {
+        let name = input_name.to_string();
+        if !self.only.is_empty() {
+            return self.only.contains(&name);
+        }
+
+        !self.exclude.contains(&name)
+    }
+
     #[tracing::instrument(skip_all)]
     async fn convert_inputs_to_flakehub(
         &self,
         expr: &nixel::Expression,
         flake_contents: &str,
-    ) -> color_eyre::Result<(String, Option<String>)> {
+    ) -> color_eyre::Result<(String, Option<String>, BTreeMap<String, SkipReason>)> {
         let mut new_flake_contents = flake_contents.to_string();
+        let mut skipped = BTreeMap::new();
 
         let all_toplevel_inputs = crate::cli::cmd::add::flake::find_all_attrsets_by_path(
             expr,
@@ -130,6 +238,12 @@ impl ConvertSubcommand {
                 continue;
             };
 
+            if !self.should_convert(&input_name) {
+                tracing::debug!("skipping {input_name} because of --only or --exclude");
+                skipped.insert(input_name, SkipReason::Filtered);
+                continue;
+            }
+
             let span = span!(Level::DEBUG, "processing_input", %input_name);
             let _span_guard = span.enter();
 
@@ -161,8 +275,19 @@ impl ConvertSubcommand {
             tracing::trace!("Parsed URL: {:?}", maybe_parsed_url);
 
             let new_input_url = match maybe_parsed_url {
-                Some(parsed_url) => convert_input_to_flakehub(&self.api_addr, parsed_url).await?,
-                None => None,
+                Some(parsed_url) => {
+                    match convert_input_to_flakehub(&self.api_addr, parsed_url).await? {
+                        Ok(new_input_url) => Some(new_input_url),
+                        Err(reason) => {
+                            skipped.insert(input_name.clone(), reason);
+                            None
+                        }
+                    }
+                }
+                None => {
+                    skipped.insert(input_name.clone(), SkipReason::NoUrl);
+                    None
+                }
             };
 
             if let Some(new_input_url) = new_input_url {
@@ -187,7 +312,7 @@ impl ConvertSubcommand {
             }
         }
 
-        Ok((new_flake_contents, flake_compat_input_name))
+        Ok((new_flake_contents, flake_compat_input_name, skipped))
     }
 
     #[tracing::instrument(skip_all)]
@@ -198,6 +323,10 @@ impl ConvertSubcommand {
     ) -> color_eyre::Result<String> {
         let mut new_flake_contents = flake_contents.to_string();
         let input_name = String::from(NIXPKGS_IMPLICIT_INPUT_NAME);
+        if !self.should_convert(&input_name) {
+            return Ok(new_flake_contents);
+        }
+
         let outputs_attr = crate::cli::cmd::add::flake::find_first_attrset_by_path(
             expr,
             Some(["outputs".into()].into()),
@@ -214,6 +343,17 @@ impl ConvertSubcommand {
             return Ok(new_flake_contents);
         }
 
+        // If a differently-named input already points at FlakeHub's Nixpkgs, inserting the
+        // implicit input would leave the flake with two Nixpkgs sources. We re-parse the contents
+        // since converting inputs may have changed their URLs.
+        let converted = nixel::parse(new_flake_contents.clone());
+        if let Some(existing_input_name) = find_flakehub_nixpkgs_input(&converted.expression)? {
+            tracing::info!(
+                "Not adding an explicit `{input_name}` input because `{existing_input_name}` already points at FlakeHub's Nixpkgs"
+            );
+            return Ok(new_flake_contents);
+        }
+
         // - has no nixpkgs in inputs but does have it in flake.lock, add it to flakehub.com/f/nixos/nixpkgs/0.1.0.tar.gz
         if let Some(outputs_attr) = outputs_attr {
             if let nixel::Expression::Function(f) = &*outputs_attr.to {
@@ -391,6 +531,276 @@ impl ConvertSubcommand {
     }
 }
 
+#[tracing::instrument(skip_all)]
+fn find_flakehub_nixpkgs_input(expr: &nixel::Expression) -> color_eyre::Result<Option<String>> {
+    let input_name = input_urls(expr)?
+        .into_iter()
+        .find(|(_, url)| is_flakehub_nixpkgs_url(url))
+        .map(|(input_name, _)| input_name);
+
+    Ok(input_name)
+}
+
+// Every input that has a `url` that we can parse, by input name.
+fn input_urls(expr: &nixel::Expression) -> color_eyre::Result<Vec<(String, url::Url)>> {
+    let input_urls = input_url_values(expr)?
+        .into_iter()
+        .filter_map(|(input_name, url)| Some((input_name, url.parse::<url::Url>().ok()?)))
+        .collect();
+
+    Ok(input_urls)
+}
+
+// Every input that has a `url`, by input name, exactly as written in the flake.
+fn input_url_values(expr: &nixel::Expression) -> color_eyre::Result<Vec<(String, String)>> {
+    let all_toplevel_inputs = crate::cli::cmd::add::flake::find_all_attrsets_by_path(
+        expr,
+        Some(["inputs".into()].into()),
+    )?;
+    let all_inputs = crate::cli::cmd::add::flake::collect_all_inputs(all_toplevel_inputs)?;
+    let mut input_urls = Vec::new();
+
+    for input in all_inputs.iter() {
+        let Some(input_name) = input.from.iter().find_map(|part| match part {
+            nixel::Part::Raw(raw) => {
+                let content = raw.content.trim().to_string();
+
+                if ["inputs", "url"].contains(&content.as_ref()) {
+                    None
+                } else {
+                    Some(content)
+                }
+            }
+            _ => None,
+        }) else {
+            continue;
+        };
+
+        if let Some(url) = find_input_value_by_path(&input.to, ["url".into()].into())? {
+            input_urls.push((input_name, url));
+        }
+    }
+
+    Ok(input_urls)
+}
+
+/// What `fh convert` or `fh eject` did to the inputs of each flake it touched, by flake path.
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub(crate) struct ConversionReport {
+    flakes: BTreeMap<String, Vec<InputConversion>>,
+}
+
+impl ConversionReport {
+    pub(crate) fn add(&mut self, flake_path: &Path, inputs: Vec<InputConversion>) {
+        self.flakes.insert(flake_path.display().to_string(), inputs);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct InputConversion {
+    pub(crate) input: String,
+    pub(crate) old_url: Option<String>,
+    pub(crate) new_url: Option<String>,
+    pub(crate) status: ConversionStatus,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ConversionStatus {
+    /// The input's URL was rewritten.
+    Converted,
+    /// The input was left as it was.
+    Unchanged,
+    /// The input didn't exist before, like an explicit Nixpkgs input.
+    Added,
+    /// The input no longer has a URL.
+    Removed,
+}
+
+/// Why an input wasn't converted.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SkipReason {
+    /// `--only` or `--exclude` left it out.
+    Filtered,
+    /// It has no URL, or one that isn't a string.
+    NoUrl,
+    /// It's already a FlakeHub input.
+    AlreadyOnFlakeHub,
+    /// fh doesn't know how to convert URLs like it.
+    UnsupportedScheme(String),
+    /// fh knows the kind of URL, but the flake isn't published to FlakeHub.
+    NotOnFlakeHub,
+    /// The repository's path isn't `owner/repository`, like a repository in a GitLab subgroup.
+    NotOwnerAndRepository(String),
+    /// A FlakeHub project with the same name as this GitLab repository is published from
+    /// somewhere else.
+    NotPublishedFromGitLab(String),
+    /// The FlakeHub project is published from a different subdirectory of the repository than the
+    /// one the input points at.
+    SubdirectoryMismatch {
+        /// The subdirectory the input points at, as described by `describe_subdirectory`.
+        input: String,
+        /// The subdirectory the FlakeHub project is published from, described the same way.
+        published: String,
+    },
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Filtered => write!(f, "left out by --only or --exclude"),
+            Self::NoUrl => write!(f, "no URL that fh can read"),
+            Self::AlreadyOnFlakeHub => write!(f, "already on FlakeHub"),
+            Self::UnsupportedScheme(scheme) => write!(f, "fh can't convert `{scheme}` URLs"),
+            Self::NotOnFlakeHub => write!(f, "not found on FlakeHub"),
+            Self::NotOwnerAndRepository(path) => {
+                write!(f, "`{path}` isn't an owner/repository path")
+            }
+            Self::NotPublishedFromGitLab(project) => {
+                write!(f, "{project} on FlakeHub isn't published from GitLab")
+            }
+            Self::SubdirectoryMismatch { input, published } => write!(
+                f,
+                "points at {input}, but FlakeHub's release is published from {published}"
+            ),
+        }
+    }
+}
+
+#[derive(tabled::Tabled)]
+struct SummaryRow {
+    #[tabled(rename = "Input")]
+    input: String,
+    #[tabled(rename = "Result")]
+    result: String,
+    #[tabled(rename = "Details")]
+    details: String,
+}
+
+// Prints a table of what happened to each input to stderr, so that it doesn't mix with a
+// `--dry-run`'s flake.nix on stdout.
+fn print_summary(conversions: Vec<InputConversion>, skipped: &BTreeMap<String, SkipReason>) {
+    let rows = summary_rows(conversions, skipped);
+    if rows.is_empty() {
+        return;
+    }
+
+    let mut table = tabled::Table::new(rows);
+    table.with(super::DEFAULT_STYLE.clone());
+    eprintln!("{table}");
+}
+
+fn summary_rows(
+    conversions: Vec<InputConversion>,
+    skipped: &BTreeMap<String, SkipReason>,
+) -> Vec<SummaryRow> {
+    let mut rows: Vec<SummaryRow> = conversions
+        .into_iter()
+        .map(|conversion| {
+            let old_url = conversion.old_url.unwrap_or_default();
+            let new_url = conversion.new_url.unwrap_or_default();
+            let (result, details) = match conversion.status {
+                ConversionStatus::Converted => ("converted", format!("{old_url} -> {new_url}")),
+                ConversionStatus::Added => ("added", new_url),
+                ConversionStatus::Removed => ("removed", old_url),
+                ConversionStatus::Unchanged => match skipped.get(&conversion.input) {
+                    Some(reason) => ("skipped", reason.to_string()),
+                    None => ("unchanged", old_url),
+                },
+            };
+
+            SummaryRow {
+                input: conversion.input,
+                result: result.to_string(),
+                details,
+            }
+        })
+        .collect();
+
+    // Inputs without a URL don't show up in the conversions at all
+    for (input, reason) in skipped {
+        if !rows.iter().any(|row| row.input == *input) {
+            rows.push(SummaryRow {
+                input: input.clone(),
+                result: String::from("skipped"),
+                details: reason.to_string(),
+            });
+        }
+    }
+
+    rows
+}
+
+// Compares every input's URL before and after a conversion.
+pub(crate) fn input_conversions(
+    before: &nixel::Expression,
+    after: &nixel::Expression,
+) -> color_eyre::Result<Vec<InputConversion>> {
+    let before = input_url_values(before)?;
+    let mut after: BTreeMap<String, String> = input_url_values(after)?.into_iter().collect();
+    let mut conversions = Vec::new();
+
+    for (input, old_url) in before {
+        let new_url = after.remove(&input);
+        let status = match &new_url {
+            Some(new_url) if *new_url == old_url => ConversionStatus::Unchanged,
+            Some(_) => ConversionStatus::Converted,
+            None => ConversionStatus::Removed,
+        };
+
+        conversions.push(InputConversion {
+            input,
+            old_url: Some(old_url),
+            new_url,
+            status,
+        });
+    }
+
+    for (input, new_url) in after {
+        conversions.push(InputConversion {
+            input,
+            old_url: None,
+            new_url: Some(new_url),
+            status: ConversionStatus::Added,
+        });
+    }
+
+    Ok(conversions)
+}
+
+// Points the lock entries of every FlakeHub input at its new URL while keeping the revision it's
+// locked to. Returns whether the lock changed.
+#[tracing::instrument(skip_all)]
+fn preserve_locked_revs(
+    expr: &nixel::Expression,
+    lock: &mut crate::shared::flake_lock::FlakeLock,
+) -> color_eyre::Result<bool> {
+    let mut changed = false;
+
+    for (input_name, url) in input_urls(expr)? {
+        if !is_flakehub_url(&url) {
+            continue;
+        }
+
+        if lock.set_root_input_tarball_url(&input_name, &url)? {
+            tracing::debug!("Rewrote the flake.lock entry for `{input_name}` to {url}");
+            changed = true;
+        }
+    }
+
+    Ok(changed)
+}
+
+fn is_flakehub_url(url: &url::Url) -> bool {
+    matches!(url.host_str(), Some("flakehub.com" | "api.flakehub.com"))
+        && url.path().starts_with("/f/")
+}
+
+fn is_flakehub_nixpkgs_url(url: &url::Url) -> bool {
+    is_flakehub_url(url) && url.path().to_lowercase().starts_with("/f/nixos/nixpkgs/")
+}
+
 // FIXME: only supports strings for now
 #[tracing::instrument(skip_all)]
 // TODO: return the span as well
@@ -462,12 +872,10 @@ pub(crate) fn find_input_value_by_path(
                         }
                     }
                     nixel::Binding::Inherit(inherit) => {
-                        let start = &inherit.span.start;
-                        return Err(color_eyre::eyre::eyre!(
-                            "`inherit` not supported (at {}:{})",
-                            start.line,
-                            start.column
-                        ));
+                        crate::cli::cmd::add::flake::check_inherit(
+                            inherit,
+                            attr_path.front().map(String::as_str),
+                        )?;
                     }
                 }
             }
@@ -501,65 +909,51 @@ pub(crate) fn find_input_value_by_path(
     Ok(found_value)
 }
 
+// The FlakeHub URL for an input, or why it can't be converted to one.
 #[tracing::instrument(skip_all)]
 async fn convert_input_to_flakehub(
     api_addr: &url::Url,
     parsed_url: url::Url,
-) -> color_eyre::Result<Option<url::Url>> {
-    let mut url = None;
-
+) -> color_eyre::Result<Result<url::Url, SkipReason>> {
     match parsed_url.host() {
         // A URL like `https://github.com/...`
         Some(host) => {
             if host == url::Host::Domain("api.flakehub.com") {
                 let mut mod_url = parsed_url.clone();
                 mod_url.set_host(Some("flakehub.com"))?;
-                url = Some(mod_url);
+                Ok(Ok(mod_url))
+            } else if is_flakehub_url(&parsed_url) {
+                Ok(Err(SkipReason::AlreadyOnFlakeHub))
+            } else if host == url::Host::Domain("gitlab.com") && parsed_url.scheme() == "git+https"
+            {
+                convert_gitlab_input_to_flakehub(parsed_url, api_addr).await
             } else {
-                match parsed_url.scheme() {
-                    "https" => {
-                        tracing::debug!("https://... urls are not yet implented");
-                    }
-                    scheme => {
-                        tracing::debug!("unimplemented url scheme {scheme}");
-                    }
-                }
+                tracing::debug!("unimplemented url scheme {}", parsed_url.scheme());
+                Ok(Err(SkipReason::UnsupportedScheme(
+                    parsed_url.scheme().to_string(),
+                )))
             }
         }
         // A URL like `github:nixos/nixpkgs`
         None => match parsed_url.scheme() {
-            "github" => {
-                url = convert_github_input_to_flakehub(parsed_url, api_addr).await?;
-            }
+            "github" => convert_github_input_to_flakehub(parsed_url, api_addr).await,
+            "gitlab" => convert_gitlab_input_to_flakehub(parsed_url, api_addr).await,
             scheme => {
                 tracing::debug!("unimplemented flake input scheme {scheme}");
+                Ok(Err(SkipReason::UnsupportedScheme(scheme.to_string())))
             }
         },
     }
-
-    Ok(url)
 }
 
 #[tracing::instrument(skip_all)]
-async fn convert_github_input_to_flakehub(
+pub(crate) async fn convert_github_input_to_flakehub(
     parsed_url: url::Url,
     api_addr: &url::Url,
-) -> color_eyre::Result<Option<url::Url>> {
-    let mut url = None;
-
-    let (org, project, maybe_version_or_branch) =
-        match parsed_url.path().split('/').collect::<Vec<_>>()[..] {
-            // `nixos/nixpkgs/nixos-23.05`
-            [org, project, maybe_version_or_branch] => {
-                (org, project, Some(maybe_version_or_branch))
-            }
-            // `nixos/nixpkgs`
-            [org, project] => (org, project, None),
-            _ => Err(color_eyre::eyre::eyre!(
-                "flakehub input did not match the expected format of `org/project` or
-                `org/project/version`"
-            ))?,
-        };
+) -> color_eyre::Result<Result<url::Url, SkipReason>> {
+    let mut flakehub_project = None;
+
+    let (org, project, maybe_version_or_branch) = split_input_path(parsed_url.path())?;
 
     match maybe_version_or_branch {
         Some(version_or_branch) => {
@@ -569,7 +963,7 @@ async fn convert_github_input_to_flakehub(
                     .strip_prefix('v')
                     .unwrap_or(version_or_branch),
             ) {
-                if let Ok((_, flakehub_url)) = crate::cli::cmd::add::get_flakehub_project_and_url(
+                if let Ok(found) = crate::cli::cmd::add::get_flakehub_project(
                     api_addr,
                     org,
                     project,
@@ -577,7 +971,7 @@ async fn convert_github_input_to_flakehub(
                 )
                 .await
                 {
-                    url = Some(flakehub_url);
+                    flakehub_project = Some(found);
                 }
             // - has nixpkgs:
             } else if (org.to_lowercase().as_ref(), project.to_lowercase().as_ref())
@@ -594,16 +988,15 @@ async fn convert_github_input_to_flakehub(
                 match branch {
                     //   - nixpkgs-unstable and nixos-unstable -> flakehub.com/f/nixos/nixpkgs/0.1.0.tar.gz
                     "nixpkgs-unstable" | "nixos-unstable" => {
-                        if let Ok((_, flakehub_url)) =
-                            crate::cli::cmd::add::get_flakehub_project_and_url(
-                                api_addr,
-                                org,
-                                project,
-                                Some("0.1.0"),
-                            )
-                            .await
+                        if let Ok(found) = crate::cli::cmd::add::get_flakehub_project(
+                            api_addr,
+                            org,
+                            project,
+                            Some("0.1.0"),
+                        )
+                        .await
                         {
-                            url = Some(flakehub_url);
+                            flakehub_project = Some(found);
                         }
                     }
                     _ => {
@@ -619,16 +1012,15 @@ async fn convert_github_input_to_flakehub(
                             // NixOS 20.03 and later have a flake.nix
                             if year >= 20 && month >= 3 {
                                 let version = format!("0.{year_str}{month_str}.0");
-                                if let Ok((_, flakehub_url)) =
-                                    crate::cli::cmd::add::get_flakehub_project_and_url(
-                                        api_addr,
-                                        org,
-                                        project,
-                                        Some(&version),
-                                    )
-                                    .await
+                                if let Ok(found) = crate::cli::cmd::add::get_flakehub_project(
+                                    api_addr,
+                                    org,
+                                    project,
+                                    Some(&version),
+                                )
+                                .await
                                 {
-                                    url = Some(flakehub_url);
+                                    flakehub_project = Some(found);
                                 }
                             }
                         } else {
@@ -645,38 +1037,181 @@ async fn convert_github_input_to_flakehub(
         }
         None => {
             // github:{org}/{repo} -> flakehub.com/f/{org}/{repo}/x.y.z.tar.gz (where x.y.z is the currently-latest version)
-            if let Ok((_, flakehub_url)) =
-                crate::cli::cmd::add::get_flakehub_project_and_url(api_addr, org, project, None)
-                    .await
+            if let Ok(found) =
+                crate::cli::cmd::add::get_flakehub_project(api_addr, org, project, None).await
             {
-                url = Some(flakehub_url);
+                flakehub_project = Some(found);
             } else {
                 tracing::debug!("didn't have {org}/{project} uploaded");
             }
         }
     }
 
-    Ok(url)
+    Ok(match flakehub_project {
+        Some(project) => in_subdirectory_of(&parsed_url, project),
+        None => Err(SkipReason::NotOnFlakeHub),
+    })
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn convert_gitlab_input_to_flakehub(
+    parsed_url: url::Url,
+    api_addr: &url::Url,
+) -> color_eyre::Result<Result<url::Url, SkipReason>> {
+    let (org, project, maybe_version_or_branch) = if parsed_url.host().is_some() {
+        // `git+https://gitlab.com/owner/repo.git?ref=v1.0.0`
+        let path = parsed_url.path().trim_start_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let git_ref = parsed_url
+            .query_pairs()
+            .find_map(|(key, value)| (key == "ref").then(|| value.into_owned()));
+
+        // FlakeHub projects are named `owner/repository`, so there's nothing for a GitLab subgroup
+        // like `group/subgroup/repository` to match
+        let [org, project] = path.split('/').collect::<Vec<_>>()[..] else {
+            tracing::debug!("{parsed_url} isn't an owner/repository path, skipping");
+            return Ok(Err(SkipReason::NotOwnerAndRepository(path.to_string())));
+        };
+        (org.to_string(), project.to_string(), git_ref)
+    } else {
+        // `gitlab:owner/repo/v1.0.0`
+        let (org, project, maybe_version_or_branch) = split_input_path(parsed_url.path())?;
+        (
+            org.to_string(),
+            project.to_string(),
+            maybe_version_or_branch.map(String::from),
+        )
+    };
+
+    // Only release tags have a matching FlakeHub release; branches can't be pinned to one
+    let version = match maybe_version_or_branch {
+        Some(version_or_branch) => {
+            match semver::Version::parse(
+                version_or_branch
+                    .strip_prefix('v')
+                    .unwrap_or(&version_or_branch),
+            ) {
+                Ok(version) => Some(version.to_string()),
+                Err(_) => {
+                    tracing::debug!("input was not of the form [org]/[project]/[semver], skipping");
+                    return Ok(Err(SkipReason::NotOnFlakeHub));
+                }
+            }
+        }
+        None => None,
+    };
+
+    match crate::cli::cmd::add::get_flakehub_project(api_addr, &org, &project, version.as_deref())
+        .await
+    {
+        // FlakeHub projects are named by owner and repository alone, so a project with the same
+        // name may well come from somewhere else
+        Ok(flakehub_project) if flakehub_project.source_host != Some(SourceHost::Gitlab) => {
+            Ok(Err(SkipReason::NotPublishedFromGitLab(format!(
+                "{org}/{project}"
+            ))))
+        }
+        Ok(flakehub_project) => Ok(in_subdirectory_of(&parsed_url, flakehub_project)),
+        Err(_) => {
+            tracing::debug!("{org}/{project} from GitLab isn't on FlakeHub, leaving it as is");
+            Ok(Err(SkipReason::NotOnFlakeHub))
+        }
+    }
+}
+
+// The FlakeHub URL for an input, as long as the FlakeHub project was published from the same
+// subdirectory that the input's `?dir=` points at (or from the root of the repository, without
+// one). A FlakeHub release holds only the flake it was published from, so there's no `?dir=` to
+// carry over to it.
+fn in_subdirectory_of(
+    input_url: &url::Url,
+    flakehub_project: ProjectCanonicalNames,
+) -> Result<url::Url, SkipReason> {
+    fn normalize(subdir: Option<&str>) -> &str {
+        subdir.unwrap_or_default().trim_matches('/')
+    }
+
+    let input_subdir = input_url
+        .query_pairs()
+        .find_map(|(key, value)| (key == "dir").then(|| value.into_owned()));
+    let input_subdir = normalize(input_subdir.as_deref());
+    let project_subdir = normalize(flakehub_project.source_subdirectory.as_deref());
+
+    if input_subdir != project_subdir {
+        return Err(SkipReason::SubdirectoryMismatch {
+            input: describe_subdirectory(input_subdir),
+            published: describe_subdirectory(project_subdir),
+        });
+    }
+
+    Ok(flakehub_project.pretty_download_url)
+}
+
+fn describe_subdirectory(subdir: &str) -> String {
+    if subdir.is_empty() {
+        String::from("the repository root")
+    } else {
+        format!("`{subdir}`")
+    }
+}
+
+// Splits an input path like `nixos/nixpkgs` or `nixos/nixpkgs/nixos-23.05` into the org, the
+// project, and the version or branch (if any).
+fn split_input_path(path: &str) -> color_eyre::Result<(&str, &str, Option<&str>)> {
+    match path.split('/').collect::<Vec<_>>()[..] {
+        // `nixos/nixpkgs/nixos-23.05`
+        [org, project, maybe_version_or_branch] => {
+            Ok((org, project, Some(maybe_version_or_branch)))
+        }
+        // `nixos/nixpkgs`
+        [org, project] => Ok((org, project, None)),
+        _ => Err(color_eyre::eyre::eyre!(
+            "flakehub input did not match the expected format of `org/project` or
+            `org/project/version`"
+        )),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use axum::{extract::Path, response::IntoResponse};
 
+    // Everything in the `gitlaborg` org is published from GitLab, and everything else from GitHub
+    fn source_host(org: &str) -> &'static str {
+        if org == "gitlaborg" {
+            "gitlab"
+        } else {
+            "github"
+        }
+    }
+
+    // Projects named `monorepo` are published from its `subflake` directory
+    fn source_subdirectory(project: &str) -> Option<&'static str> {
+        (project == "monorepo").then_some("subflake")
+    }
+
     async fn version(
         Path((org, project, version)): Path<(String, String, String)>,
     ) -> axum::response::Response {
         axum::Json(serde_json::json!({
             "project": project,
             "pretty_download_url": format!("http://flakehub-localhost/f/{org}/{project}/{version}.tar.gz"),
+            "source_host": source_host(&org),
+            "source_subdirectory": source_subdirectory(&project),
         }))
         .into_response()
     }
 
     async fn no_version(Path((org, project)): Path<(String, String)>) -> axum::response::Response {
+        if org == "unpublished" {
+            return axum::http::StatusCode::NOT_FOUND.into_response();
+        }
+
         axum::Json(serde_json::json!({
             "project": project,
             "pretty_download_url": format!("http://flakehub-localhost/f/{org}/{project}/*.tar.gz"),
+            "source_host": source_host(&org),
+            "source_subdirectory": source_subdirectory(&project),
         }))
         .into_response()
     }
@@ -690,15 +1225,24 @@ mod test {
             .route("/f/:org/:project", axum::routing::get(no_version))
     }
 
+    // A `--dry-run` conversion against the FlakeHub at `api_addr`, for tests to adjust
+    fn subcommand(api_addr: url::Url) -> super::ConvertSubcommand {
+        super::ConvertSubcommand {
+            flake_path: "".into(),
+            dry_run: true,
+            lossy: false,
+            force: false,
+            relock_preserve_revs: false,
+            json: false,
+            only: vec![],
+            exclude: vec![],
+            api_addr,
+            print_commands: false,
+        }
+    }
+
     fn test_server() -> axum_test::TestServer {
-        let test_server_config = axum_test::TestServerConfig::builder()
-            .http_transport()
-            .build();
-        axum_test::TestServer::new_with_config(
-            test_router().into_make_service(),
-            test_server_config,
-        )
-        .unwrap()
+        crate::cli::cmd::test_server(test_router())
     }
 
     #[tokio::test]
@@ -710,8 +1254,7 @@ mod test {
         let input_url = url::Url::parse("github:someorg/somerepo").unwrap();
         let tarball_url = super::convert_input_to_flakehub(&server_url, input_url)
             .await
-            .ok()
-            .flatten()
+            .unwrap()
             .unwrap();
         assert_eq!(tarball_url.path(), "/f/someorg/somerepo/*.tar.gz");
     }
@@ -725,23 +1268,254 @@ mod test {
         let input_url = url::Url::parse("github:nixos/nixpkgs/nixos-23.05").unwrap();
         let tarball_url = super::convert_input_to_flakehub(&server_url, input_url)
             .await
-            .ok()
-            .flatten()
+            .unwrap()
             .unwrap();
         assert_eq!(tarball_url.path(), "/f/nixos/nixpkgs/0.2305.0.tar.gz");
     }
 
+    #[tokio::test]
+    async fn flake_utils_to_flakehub() {
+        let test_server = test_server();
+        let server_addr = test_server.server_address();
+        let server_url = server_addr.unwrap();
+
+        let input_url = url::Url::parse("github:numtide/flake-utils").unwrap();
+        let tarball_url = super::convert_input_to_flakehub(&server_url, input_url)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tarball_url.path(), "/f/numtide/flake-utils/*.tar.gz");
+
+        let input_url = url::Url::parse("github:numtide/flake-utils/v1.0.0").unwrap();
+        let tarball_url = super::convert_input_to_flakehub(&server_url, input_url)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tarball_url.path(), "/f/numtide/flake-utils/1.0.0.tar.gz");
+    }
+
+    #[tokio::test]
+    async fn subdirectories_must_match() {
+        let test_server = test_server();
+        let server_addr = test_server.server_address();
+        let server_url = server_addr.unwrap();
+
+        for (input_url, expected) in [
+            (
+                "github:someorg/monorepo?dir=subflake",
+                "http://flakehub-localhost/f/someorg/monorepo/*.tar.gz",
+            ),
+            (
+                "github:someorg/monorepo/v1.0.0?dir=subflake/",
+                "http://flakehub-localhost/f/someorg/monorepo/1.0.0.tar.gz",
+            ),
+            (
+                "git+https://gitlab.com/gitlaborg/monorepo.git?ref=v1.0.0&dir=subflake",
+                "http://flakehub-localhost/f/gitlaborg/monorepo/1.0.0.tar.gz",
+            ),
+        ] {
+            let tarball_url =
+                super::convert_input_to_flakehub(&server_url, url::Url::parse(input_url).unwrap())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            assert_eq!(tarball_url.as_str(), expected, "converting {input_url}");
+        }
+
+        // The FlakeHub project holds a different flake than the one these point at
+        for (input_url, input, published) in [
+            (
+                "github:someorg/somerepo?dir=subflake",
+                "`subflake`",
+                "the repository root",
+            ),
+            (
+                "github:someorg/monorepo",
+                "the repository root",
+                "`subflake`",
+            ),
+            ("github:someorg/monorepo?dir=other", "`other`", "`subflake`"),
+            (
+                "gitlab:gitlaborg/somerepo/v1.0.0?dir=nested/subflake",
+                "`nested/subflake`",
+                "the repository root",
+            ),
+        ] {
+            let converted =
+                super::convert_input_to_flakehub(&server_url, url::Url::parse(input_url).unwrap())
+                    .await
+                    .unwrap();
+            assert_eq!(
+                converted,
+                Err(super::SkipReason::SubdirectoryMismatch {
+                    input: input.to_string(),
+                    published: published.to_string(),
+                }),
+                "converting {input_url}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn gitlab_to_flakehub() {
+        let test_server = test_server();
+        let server_addr = test_server.server_address();
+        let server_url = server_addr.unwrap();
+
+        for (input_url, path) in [
+            (
+                "gitlab:gitlaborg/somerepo",
+                "/f/gitlaborg/somerepo/*.tar.gz",
+            ),
+            (
+                "gitlab:gitlaborg/somerepo/v1.0.0",
+                "/f/gitlaborg/somerepo/1.0.0.tar.gz",
+            ),
+            (
+                "git+https://gitlab.com/gitlaborg/somerepo.git?ref=v1.0.0",
+                "/f/gitlaborg/somerepo/1.0.0.tar.gz",
+            ),
+            (
+                "git+https://gitlab.com/gitlaborg/somerepo",
+                "/f/gitlaborg/somerepo/*.tar.gz",
+            ),
+        ] {
+            let tarball_url =
+                super::convert_input_to_flakehub(&server_url, url::Url::parse(input_url).unwrap())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            assert_eq!(tarball_url.path(), path, "converting {input_url}");
+        }
+
+        // Branches, projects that aren't on FlakeHub, and FlakeHub projects of the same name that
+        // aren't from GitLab are left alone
+        let not_from_gitlab =
+            super::SkipReason::NotPublishedFromGitLab(String::from("someorg/somerepo"));
+        for (input_url, reason) in [
+            ("gitlab:someorg/somerepo", &not_from_gitlab),
+            (
+                "git+https://gitlab.com/someorg/somerepo.git?ref=v1.0.0",
+                &not_from_gitlab,
+            ),
+            (
+                "gitlab:gitlaborg/somerepo/main",
+                &super::SkipReason::NotOnFlakeHub,
+            ),
+            (
+                "gitlab:unpublished/somerepo",
+                &super::SkipReason::NotOnFlakeHub,
+            ),
+            (
+                "git+https://gitlab.com/gitlaborg/somerepo.git?ref=main",
+                &super::SkipReason::NotOnFlakeHub,
+            ),
+        ] {
+            let converted =
+                super::convert_input_to_flakehub(&server_url, url::Url::parse(input_url).unwrap())
+                    .await
+                    .unwrap();
+            assert_eq!(converted.as_ref(), Err(reason), "converting {input_url}");
+        }
+
+        // Repositories in subgroups are skipped rather than misread or treated as errors
+        for (input_url, path) in [
+            (
+                "git+https://gitlab.com/gitlaborg/sub/somerepo.git?ref=v1.0.0",
+                "gitlaborg/sub/somerepo",
+            ),
+            (
+                "git+https://gitlab.com/gitlaborg/sub/nested/somerepo.git",
+                "gitlaborg/sub/nested/somerepo",
+            ),
+        ] {
+            let converted =
+                super::convert_input_to_flakehub(&server_url, url::Url::parse(input_url).unwrap())
+                    .await
+                    .unwrap();
+            assert_eq!(
+                converted,
+                Err(super::SkipReason::NotOwnerAndRepository(path.to_string())),
+                "converting {input_url}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flake9_convert() {
+        let test_server = test_server();
+        let server_addr = test_server.server_address();
+        let server_url = server_addr.unwrap();
+
+        let convert = subcommand(server_url);
+        let flake_contents = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/samples/flake9.test.nix"
+        ));
+        let flake_contents = flake_contents.to_string();
+        let parsed = nixel::parse(flake_contents.clone());
+
+        let (new_flake_contents, _, _) = convert
+            .convert_inputs_to_flakehub(&parsed.expression, &flake_contents)
+            .await
+            .unwrap();
+
+        assert!(new_flake_contents
+            .contains(r#"url = "http://flakehub-localhost/f/numtide/flake-utils/*.tar.gz";"#));
+        assert!(new_flake_contents.contains(
+            r#"flake-utils-pinned.url = "http://flakehub-localhost/f/numtide/flake-utils/1.0.0.tar.gz";"#
+        ));
+        // `follows` on flake-utils must survive the conversion untouched
+        assert!(new_flake_contents.contains(r#"      inputs.systems.follows = "systems";"#));
+        assert!(new_flake_contents
+            .contains(r#"flake-utils-pinned.inputs.systems.follows = "systems";"#));
+        assert!(!new_flake_contents.contains("github:numtide/flake-utils"));
+    }
+
+    #[tokio::test]
+    async fn only_and_exclude_filter_inputs() {
+        let test_server = test_server();
+        let server_url = test_server.server_address().unwrap();
+
+        let flake_contents = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/samples/flake9.test.nix"
+        ));
+        let parsed = nixel::parse(flake_contents.to_string());
+
+        for (only, exclude) in [
+            (vec![String::from("flake-utils-pinned")], vec![]),
+            (
+                vec![],
+                vec![String::from("flake-utils"), String::from("nixpkgs")],
+            ),
+        ] {
+            let convert = super::ConvertSubcommand {
+                only,
+                exclude,
+                ..subcommand(server_url.clone())
+            };
+
+            let (new_flake_contents, _, _) = convert
+                .convert_inputs_to_flakehub(&parsed.expression, flake_contents)
+                .await
+                .unwrap();
+
+            assert!(new_flake_contents.contains(
+                r#"flake-utils-pinned.url = "http://flakehub-localhost/f/numtide/flake-utils/1.0.0.tar.gz";"#
+            ));
+            assert!(new_flake_contents.contains(r#"url = "github:numtide/flake-utils";"#));
+            assert!(new_flake_contents.contains("github:NixOS/nixpkgs/nixos-23.05"));
+        }
+    }
+
     #[tokio::test]
     async fn test_flake1_convert() {
         let test_server = test_server();
         let server_addr = test_server.server_address();
         let server_url = server_addr.unwrap();
 
-        let convert = super::ConvertSubcommand {
-            flake_path: "".into(),
-            dry_run: true,
-            api_addr: server_url,
-        };
+        let convert = subcommand(server_url);
         let flake_contents = include_str!(concat!(
             env!("CARGO_MANIFEST_DIR"),
             "/samples/flake1.test.nix"
@@ -749,7 +1523,7 @@ mod test {
         let flake_contents = flake_contents.to_string();
         let parsed = nixel::parse(flake_contents.clone());
 
-        let (new_flake_contents, flake_compat_input_name) = convert
+        let (new_flake_contents, flake_compat_input_name, _) = convert
             .convert_inputs_to_flakehub(&parsed.expression, &flake_contents)
             .await
             .unwrap();
@@ -783,11 +1557,7 @@ mod test {
         let server_addr = test_server.server_address();
         let server_url = server_addr.unwrap();
 
-        let convert = super::ConvertSubcommand {
-            flake_path: "".into(),
-            dry_run: true,
-            api_addr: server_url,
-        };
+        let convert = subcommand(server_url);
         let flake_contents = r#"
 {
   description = "cole-h's NixOS configuration";
@@ -802,7 +1572,7 @@ mod test {
         let flake_contents = flake_contents.to_string();
         let parsed = nixel::parse(flake_contents.clone());
 
-        let (new_flake_contents, _) = convert
+        let (new_flake_contents, _, _) = convert
             .convert_inputs_to_flakehub(&parsed.expression, &flake_contents)
             .await
             .unwrap();
@@ -811,6 +1581,232 @@ mod test {
             .contains(r#"nixpkgs.url = "http://flakehub-localhost/f/NixOS/nixpkgs/*.tar.gz";"#));
     }
 
+    #[tokio::test]
+    async fn test_no_duplicate_implicit_nixpkgs() {
+        let test_server = test_server();
+        let server_addr = test_server.server_address();
+        let server_url = server_addr.unwrap();
+
+        let convert = subcommand(server_url);
+        let flake_contents = r#"
+{
+  inputs = {
+    pkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.2305.*.tar.gz";
+  };
+
+  outputs = { self, nixpkgs, ... } @ inputs: { };
+}
+"#;
+        let flake_contents = flake_contents.to_string();
+        let parsed = nixel::parse(flake_contents.clone());
+
+        let new_flake_contents = convert
+            .make_implicit_nixpkgs_explicit(&parsed.expression, &flake_contents)
+            .await
+            .unwrap();
+
+        assert_eq!(new_flake_contents, flake_contents);
+        assert!(!new_flake_contents.contains("nixpkgs.url"));
+    }
+
+    #[test]
+    fn test_preserve_locked_revs() {
+        let flake_contents = r#"
+{
+  inputs = {
+    nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.2305.*.tar.gz";
+    fh = {
+      url = "https://flakehub.com/f/DeterminateSystems/fh/*.tar.gz";
+      inputs.nixpkgs.follows = "nixpkgs";
+    };
+    nixpkgs-follower.follows = "nixpkgs";
+  };
+
+  outputs = inputs: { };
+}
+"#;
+        let parsed = nixel::parse(flake_contents.to_string());
+        let mut lock = crate::shared::flake_lock::FlakeLock::parse(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/samples/lock1.test.json"
+        )))
+        .unwrap();
+        let rendered_before = lock.render().unwrap();
+
+        assert!(super::preserve_locked_revs(&parsed.expression, &mut lock).unwrap());
+
+        let rendered: serde_json::Value = serde_json::from_str(&lock.render().unwrap()).unwrap();
+        let before: serde_json::Value = serde_json::from_str(&rendered_before).unwrap();
+        for node in ["nixpkgs", "fh"] {
+            assert_eq!(
+                rendered["nodes"][node]["locked"],
+                before["nodes"][node]["locked"]
+            );
+            assert_eq!(rendered["nodes"][node]["original"]["type"], "tarball");
+        }
+        assert_eq!(
+            rendered["nodes"]["fh"]["original"]["url"],
+            "https://flakehub.com/f/DeterminateSystems/fh/%2A.tar.gz"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flake1_conversion_report() {
+        let test_server = test_server();
+        let server_url = test_server.server_address().unwrap();
+
+        let convert = super::ConvertSubcommand {
+            json: true,
+            ..subcommand(server_url)
+        };
+        let flake_contents = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/samples/flake1.test.nix"
+        ));
+        let flake_contents = flake_contents.to_string();
+        let parsed = nixel::parse(flake_contents.clone());
+
+        let (new_flake_contents, _, _) = convert
+            .convert_inputs_to_flakehub(&parsed.expression, &flake_contents)
+            .await
+            .unwrap();
+        let new_flake_contents = convert
+            .make_implicit_nixpkgs_explicit(&parsed.expression, &new_flake_contents)
+            .await
+            .unwrap();
+
+        let conversions = super::input_conversions(
+            &parsed.expression,
+            &nixel::parse(new_flake_contents).expression,
+        )
+        .unwrap();
+
+        let nixpkgs = conversions
+            .iter()
+            .find(|conversion| conversion.input == "nixpkgs")
+            .unwrap();
+        assert_eq!(nixpkgs.status, super::ConversionStatus::Converted);
+        assert_eq!(
+            nixpkgs.old_url.as_deref(),
+            Some("github:nixos/nixpkgs/nixos-23.05")
+        );
+        assert!(nixpkgs
+            .new_url
+            .as_deref()
+            .unwrap()
+            .starts_with("http://flakehub-localhost/f/nixos/nixpkgs/"));
+
+        let mut report = super::ConversionReport::default();
+        report.add(std::path::Path::new("flake.nix"), conversions);
+        let report = serde_json::to_value(report).unwrap();
+        assert!(report["flake.nix"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|input| input["input"] == "nixpkgs" && input["status"] == "converted"));
+    }
+
+    #[test]
+    fn summary_rows() {
+        use std::collections::BTreeMap;
+
+        use super::{ConversionStatus, InputConversion, SkipReason};
+
+        let conversion = |input: &str, old_url: &str, new_url: &str, status| InputConversion {
+            input: input.to_string(),
+            old_url: Some(old_url.to_string()),
+            new_url: Some(new_url.to_string()),
+            status,
+        };
+        let conversions = vec![
+            conversion(
+                "nixpkgs",
+                "github:NixOS/nixpkgs/nixos-24.11",
+                "https://flakehub.com/f/NixOS/nixpkgs/0.2411.*",
+                ConversionStatus::Converted,
+            ),
+            conversion(
+                "private",
+                "github:someone/private",
+                "github:someone/private",
+                ConversionStatus::Unchanged,
+            ),
+            conversion(
+                "local",
+                "path:./local",
+                "path:./local",
+                ConversionStatus::Unchanged,
+            ),
+        ];
+        let skipped = BTreeMap::from([
+            (String::from("private"), SkipReason::NotOnFlakeHub),
+            (
+                String::from("local"),
+                SkipReason::UnsupportedScheme(String::from("path")),
+            ),
+            (String::from("computed"), SkipReason::NoUrl),
+        ]);
+
+        let rows: Vec<_> = super::summary_rows(conversions, &skipped)
+            .into_iter()
+            .map(|row| (row.input, row.result, row.details))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "nixpkgs",
+                    "converted",
+                    "github:NixOS/nixpkgs/nixos-24.11 -> https://flakehub.com/f/NixOS/nixpkgs/0.2411.*"
+                ),
+                ("private", "skipped", "not found on FlakeHub"),
+                ("local", "skipped", "fh can't convert `path` URLs"),
+                ("computed", "skipped", "no URL that fh can read"),
+            ]
+            .map(|(input, result, details)| (
+                input.to_string(),
+                result.to_string(),
+                details.to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn input_conversions_added_and_unchanged() {
+        let before = nixel::parse(
+            r#"{ inputs.fh.url = "https://flakehub.com/f/DeterminateSystems/fh/*.tar.gz"; outputs = { nixpkgs, ... }: { }; }"#
+                .to_string(),
+        );
+        let after = nixel::parse(
+            r#"{ inputs.nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.1.tar.gz"; inputs.fh.url = "https://flakehub.com/f/DeterminateSystems/fh/*.tar.gz"; outputs = { nixpkgs, ... }: { }; }"#
+                .to_string(),
+        );
+
+        let conversions = super::input_conversions(&before.expression, &after.expression).unwrap();
+
+        assert_eq!(
+            conversions,
+            vec![
+                super::InputConversion {
+                    input: "fh".to_string(),
+                    old_url: Some(
+                        "https://flakehub.com/f/DeterminateSystems/fh/*.tar.gz".to_string()
+                    ),
+                    new_url: Some(
+                        "https://flakehub.com/f/DeterminateSystems/fh/*.tar.gz".to_string()
+                    ),
+                    status: super::ConversionStatus::Unchanged,
+                },
+                super::InputConversion {
+                    input: "nixpkgs".to_string(),
+                    old_url: None,
+                    new_url: Some("https://flakehub.com/f/NixOS/nixpkgs/0.1.tar.gz".to_string()),
+                    status: super::ConversionStatus::Added,
+                },
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn old_flakehub_to_new_flakehub() {
         let test_server = test_server();
@@ -821,8 +1817,7 @@ mod test {
             url::Url::parse("https://api.flakehub.com/f/NixOS/nixpkgs/0.1.514192.tar.gz").unwrap();
         let tarball_url = super::convert_input_to_flakehub(&server_url, input_url)
             .await
-            .ok()
-            .flatten()
+            .unwrap()
             .unwrap();
         assert_eq!(
             tarball_url.host().unwrap(),
@@ -832,5 +1827,21 @@ mod test {
             tarball_url.host().unwrap(),
             url::Host::Domain("api.flakehub.com")
         );
+
+        // Inputs that already use the new address are left as they are
+        let input_url = url::Url::parse("https://flakehub.com/f/NixOS/nixpkgs/*").unwrap();
+        assert_eq!(
+            super::convert_input_to_flakehub(&server_url, input_url)
+                .await
+                .unwrap(),
+            Err(super::SkipReason::AlreadyOnFlakeHub)
+        );
+        let input_url = url::Url::parse("path:./local").unwrap();
+        assert_eq!(
+            super::convert_input_to_flakehub(&server_url, input_url)
+                .await
+                .unwrap(),
+            Err(super::SkipReason::UnsupportedScheme(String::from("path")))
+        );
     }
 }
@@ -0,0 +1,520 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use super::init::command_exists;
+use super::login::{dnixd_uds, looks_like_jwt, DnixdUnavailable, CACHE_PUBLIC_KEYS};
+use super::status::DEFAULT_WARN_WITHIN_DAYS;
+use super::{nix_process, print_json, CommandExecute, FlakeHubClient};
+
+// The oldest Nix that can use flakes at all
+const MIN_FLAKES_NIX_VERSION: semver::Version = semver::Version::new(2, 4, 0);
+
+// The global Nix configuration files, in the order Nix reads them. Determinate Nix includes
+// nix.custom.conf from nix.conf.
+const SYSTEM_NIX_CONFIG_PATHS: &[&str] = &["/etc/nix/nix.conf", "/etc/nix/nix.custom.conf"];
+
+/// Check for common problems with how fh, Nix, and FlakeHub are set up on this machine.
+#[derive(Debug, Parser)]
+pub(crate) struct DoctorSubcommand {
+    /// Output the results as JSON, for example to attach to a support ticket.
+    #[arg(long, env = "FH_OUTPUT_JSON")]
+    json: bool,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+
+    #[clap(from_global)]
+    cache_addr: url::Url,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            CheckStatus::Pass => "pass",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "fail",
+        };
+
+        if !crate::cli::color::stdout() {
+            write!(f, "{label}")
+        } else {
+            match self {
+                CheckStatus::Pass => write!(f, "{}", label.green()),
+                CheckStatus::Warn => write!(f, "{}", label.yellow()),
+                CheckStatus::Fail => write!(f, "{}", label.red()),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl ToString) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            message: message.to_string(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl ToString, hint: impl ToString) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            message: message.to_string(),
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl ToString, hint: impl ToString) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            message: message.to_string(),
+            hint: Some(hint.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[{}] {}: {}", self.status, self.name, self.message)?;
+        if let Some(hint) = &self.hint {
+            writeln!(f, "       {hint}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for DoctorSubcommand {
+    #[tracing::instrument(skip_all)]
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let mut checks = vec![check_nix().await];
+        checks.extend(self.check_token().await);
+
+        let nix_configs = read_nix_configs();
+        checks.extend(check_cache_config(&nix_configs, &self.cache_addr));
+        checks.push(check_netrc_file(&nix_configs));
+        checks.push(check_dnixd().await);
+
+        let failed = checks.iter().any(|check| check.status == CheckStatus::Fail);
+
+        if self.json {
+            print_json(&checks)?;
+        } else {
+            for check in &checks {
+                print!("{check}");
+            }
+        }
+
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+}
+
+impl DoctorSubcommand {
+    // Whether there's a token that looks right, and whether FlakeHub accepts it.
+    async fn check_token(&self) -> Vec<Check> {
+        const TOKEN: &str = "FlakeHub token";
+        const LOGIN: &str = "FlakeHub login";
+
+        let token = match crate::cli::token::read_token().await {
+            Ok(Some(token)) => token,
+            Ok(None) => {
+                return vec![Check::fail(
+                    TOKEN,
+                    "no token was found",
+                    "Run `fh login` to log in to FlakeHub.",
+                )]
+            }
+            Err(e) => {
+                return vec![Check::fail(
+                    TOKEN,
+                    e,
+                    "Run `fh login`, or choose another `--token-source`.",
+                )]
+            }
+        };
+
+        if !looks_like_jwt(&token.token) {
+            return vec![Check::fail(
+                TOKEN,
+                format!(
+                    "the token from the `{}` token source isn't a JWT",
+                    token.source
+                ),
+                "Run `fh login` and paste the token again; it may have been garbled.",
+            )];
+        }
+
+        let token_check = Check::pass(
+            TOKEN,
+            format!("found in the `{}` token source", token.source),
+        );
+        let login_check =
+            match FlakeHubClient::auth_status(self.api_addr.as_ref(), &token.token).await {
+                Ok(status) => {
+                    match status.expiry_warning(
+                        chrono::Duration::days(DEFAULT_WARN_WITHIN_DAYS.into()),
+                        chrono::Local::now(),
+                    ) {
+                        Some(warning) => Check::warn(
+                            LOGIN,
+                            format!("logged in as {}", status.gh_name),
+                            warning,
+                        ),
+                        None => Check::pass(LOGIN, format!("logged in as {}", status.gh_name)),
+                    }
+                }
+                Err(e) => Check::fail(
+                    LOGIN,
+                    format!("{e:#}"),
+                    "Run `fh login` to get a new token, or `fh ping` to check that FlakeHub is reachable.",
+                ),
+            };
+
+        vec![token_check, login_check]
+    }
+}
+
+// Whether Nix is installed, and new enough for flakes.
+async fn check_nix() -> Check {
+    const NAME: &str = "Nix";
+
+    let Some(info) = crate::shared::nix_info::nix_info().await else {
+        let message = if command_exists("nix") {
+            "`nix --version` didn't report a version that fh understands"
+        } else {
+            "Nix isn't installed, or isn't on your PATH"
+        };
+        return Check::fail(
+            NAME,
+            message,
+            "Install Nix with the Determinate Nix Installer: https://docs.determinate.systems",
+        );
+    };
+
+    let version = match &info.determinate_version {
+        Some(determinate_version) => format!("Determinate Nix {determinate_version}"),
+        None if info.determinate => format!("Determinate Nix ({})", info.version),
+        None => format!("Nix {}", info.version),
+    };
+
+    if info.version < MIN_FLAKES_NIX_VERSION {
+        return Check::fail(
+            NAME,
+            format!("{version} doesn't support flakes"),
+            format!("Upgrade to Nix {MIN_FLAKES_NIX_VERSION} or later."),
+        );
+    }
+
+    // Determinate Nix has flakes turned on by default. Other Nix only has them when they're
+    // turned on in its configuration; fh turns them on for its own Nix commands either way.
+    if !info.determinate {
+        if let Some(features) = experimental_features().await {
+            if !features.iter().any(|feature| feature == "flakes") {
+                return Check::warn(
+                    NAME,
+                    format!("{version} doesn't have the `flakes` experimental feature turned on"),
+                    "Add `experimental-features = nix-command flakes` to your nix.conf.",
+                );
+            }
+        }
+    }
+
+    Check::pass(NAME, version)
+}
+
+// The experimental features that Nix has turned on, with all of its configuration applied, or
+// `None` if Nix couldn't say. Older Nix only has `nix show-config`, which shows every setting.
+async fn experimental_features() -> Option<Vec<String>> {
+    for subcommand in [
+        &["config", "show", "experimental-features"][..],
+        &["show-config"],
+    ] {
+        let Ok(mut cmd) = nix_process("nix") else {
+            return None;
+        };
+        let output = cmd
+            .args(["--extra-experimental-features", "nix-command"])
+            .args(subcommand)
+            .output()
+            .await
+            .ok()?;
+
+        if output.status.success() {
+            return Some(parse_experimental_features(&String::from_utf8_lossy(
+                &output.stdout,
+            )));
+        }
+    }
+
+    None
+}
+
+// The experimental features in the output of `nix config show experimental-features`, which is just
+// the value, or of `nix show-config`, which has an `experimental-features = ...` line.
+fn parse_experimental_features(output: &str) -> Vec<String> {
+    let value = output
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once('=')?;
+            (name.trim() == "experimental-features").then_some(value)
+        })
+        .unwrap_or(output);
+
+    value.split_whitespace().map(String::from).collect()
+}
+
+// The user's nix.conf and the global ones that exist, along with where each came from.
+fn read_nix_configs() -> Vec<(PathBuf, nix_config_parser::NixConfig)> {
+    let user_nix_config = xdg::BaseDirectories::new()
+        .ok()
+        .map(|xdg| xdg.get_config_file("nix/nix.conf"));
+
+    user_nix_config
+        .into_iter()
+        .chain(SYSTEM_NIX_CONFIG_PATHS.iter().map(PathBuf::from))
+        .filter_map(|path| {
+            let config = nix_config_parser::NixConfig::parse_file(&path).ok()?;
+            Some((path, config))
+        })
+        .collect()
+}
+
+// Every value of any of `settings`, across all of the configuration files.
+fn setting_values<'a>(
+    nix_configs: &'a [(PathBuf, nix_config_parser::NixConfig)],
+    settings: &[&str],
+) -> Vec<&'a str> {
+    nix_configs
+        .iter()
+        .flat_map(|(_, config)| {
+            settings
+                .iter()
+                .filter_map(|setting| config.settings().get(*setting))
+        })
+        .flat_map(|value| value.split_whitespace())
+        .collect()
+}
+
+// Whether Nix is configured to substitute from FlakeHub Cache, and to trust its keys.
+fn check_cache_config(
+    nix_configs: &[(PathBuf, nix_config_parser::NixConfig)],
+    cache_addr: &url::Url,
+) -> Vec<Check> {
+    const SUBSTITUTER: &str = "FlakeHub Cache substituter";
+    const KEYS: &str = "FlakeHub Cache public keys";
+    const HINT: &str =
+        "Run `fh cache configure`, or add the settings from `fh cache keys` to your Nix configuration.";
+
+    // Compare without trailing slashes, which Nix doesn't care about
+    let cache_addr = cache_addr.as_str().trim_end_matches('/');
+    let is_cache = |substituter: &&str| substituter.trim_end_matches('/') == cache_addr;
+
+    let substituter_check = if setting_values(nix_configs, &["substituters", "extra-substituters"])
+        .iter()
+        .any(is_cache)
+    {
+        Check::pass(SUBSTITUTER, format!("{cache_addr} is a substituter"))
+    } else if setting_values(
+        nix_configs,
+        &["trusted-substituters", "extra-trusted-substituters"],
+    )
+    .iter()
+    .any(is_cache)
+    {
+        Check::warn(
+            SUBSTITUTER,
+            format!("{cache_addr} is trusted, but isn't used as a substituter"),
+            format!("Add `extra-substituters = {cache_addr}` to your nix.conf."),
+        )
+    } else {
+        Check::fail(
+            SUBSTITUTER,
+            format!("{cache_addr} isn't configured as a substituter"),
+            HINT,
+        )
+    };
+
+    let keys = setting_values(
+        nix_configs,
+        &["trusted-public-keys", "extra-trusted-public-keys"],
+    );
+    let missing = CACHE_PUBLIC_KEYS
+        .iter()
+        .filter(|key| !keys.contains(key))
+        .count();
+    let keys_check = if missing == 0 {
+        Check::pass(KEYS, "all of them are trusted")
+    } else if missing < CACHE_PUBLIC_KEYS.len() {
+        Check::warn(
+            KEYS,
+            format!("{missing} of {} aren't trusted", CACHE_PUBLIC_KEYS.len()),
+            HINT,
+        )
+    } else {
+        Check::fail(KEYS, "none of them are trusted", HINT)
+    };
+
+    vec![substituter_check, keys_check]
+}
+
+// Whether the first `netrc-file` that Nix would use exists.
+fn check_netrc_file(nix_configs: &[(PathBuf, nix_config_parser::NixConfig)]) -> Check {
+    const NAME: &str = "netrc-file";
+
+    let netrc_file = nix_configs.iter().find_map(|(config_path, config)| {
+        Some((config_path, config.settings().get("netrc-file")?))
+    });
+
+    match netrc_file {
+        None => Check::warn(
+            NAME,
+            "no nix.conf sets `netrc-file`",
+            "Nix can't fetch private flakes or use FlakeHub Cache without one; run `fh login`.",
+        ),
+        Some((config_path, netrc_file)) if Path::new(netrc_file).exists() => Check::pass(
+            NAME,
+            format!("{netrc_file} (from {})", config_path.display()),
+        ),
+        Some((config_path, netrc_file)) => Check::fail(
+            NAME,
+            format!(
+                "{netrc_file} (from {}) doesn't exist",
+                config_path.display()
+            ),
+            "Run `fh login` to write it again.",
+        ),
+    }
+}
+
+// Whether determinate-nixd is running and answering, if it's installed at all.
+async fn check_dnixd() -> Check {
+    const NAME: &str = "determinate-nixd";
+
+    match dnixd_uds().await {
+        Ok(_) => Check::pass(NAME, "reachable"),
+        Err(DnixdUnavailable::Absent(_)) => {
+            Check::pass(NAME, "not running; fh configures Nix itself")
+        }
+        Err(err @ DnixdUnavailable::Unhealthy(_)) => {
+            Check::fail(NAME, err, "Restart determinate-nixd, or check its logs.")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::CheckStatus;
+
+    fn config(path: &str, contents: &str) -> (PathBuf, nix_config_parser::NixConfig) {
+        (
+            PathBuf::from(path),
+            nix_config_parser::NixConfig::parse_string(contents.to_string(), None).unwrap(),
+        )
+    }
+
+    #[test]
+    fn experimental_features() {
+        assert_eq!(
+            super::parse_experimental_features("nix-command flakes\n"),
+            ["nix-command", "flakes"]
+        );
+        assert_eq!(
+            super::parse_experimental_features(
+                "download-attempts = 5\nexperimental-features = nix-command\nextra-platforms = \n"
+            ),
+            ["nix-command"]
+        );
+        assert!(super::parse_experimental_features("experimental-features = \n").is_empty());
+    }
+
+    #[test]
+    fn cache_config() {
+        let cache_addr = url::Url::parse("https://cache.flakehub.com").unwrap();
+        let statuses = |configs: &[_]| {
+            super::check_cache_config(configs, &cache_addr)
+                .into_iter()
+                .map(|check| check.status)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(statuses(&[]), [CheckStatus::Fail, CheckStatus::Fail]);
+
+        // Settings can come from any of the files
+        let keys = super::CACHE_PUBLIC_KEYS.join(" ");
+        assert_eq!(
+            statuses(&[
+                config(
+                    "/home/me/.config/nix/nix.conf",
+                    "extra-substituters = https://cache.flakehub.com/\n"
+                ),
+                config(
+                    "/etc/nix/nix.conf",
+                    &format!("extra-trusted-public-keys = {keys}\n")
+                ),
+            ]),
+            [CheckStatus::Pass, CheckStatus::Pass]
+        );
+
+        assert_eq!(
+            statuses(&[config(
+                "/etc/nix/nix.conf",
+                &format!(
+                    "extra-trusted-substituters = https://cache.flakehub.com\n\
+                    extra-trusted-public-keys = {}\n",
+                    super::CACHE_PUBLIC_KEYS[0]
+                )
+            )]),
+            [CheckStatus::Warn, CheckStatus::Warn]
+        );
+    }
+
+    #[test]
+    fn netrc_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let netrc_path = dir.path().join("netrc");
+        let configs = [
+            config("/etc/nix/nix.conf", "experimental-features = flakes\n"),
+            config(
+                "/home/me/.config/nix/nix.conf",
+                &format!("netrc-file = {}\n", netrc_path.display()),
+            ),
+        ];
+
+        assert_eq!(super::check_netrc_file(&[]).status, CheckStatus::Warn);
+        assert_eq!(super::check_netrc_file(&configs).status, CheckStatus::Fail);
+
+        std::fs::write(&netrc_path, "").unwrap();
+        assert_eq!(super::check_netrc_file(&configs).status, CheckStatus::Pass);
+    }
+}
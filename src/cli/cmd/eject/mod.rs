@@ -3,10 +3,16 @@ use std::path::PathBuf;
 use std::process::ExitCode;
 
 use clap::Parser;
+use color_eyre::eyre::WrapErr;
 use once_cell::sync::Lazy;
 use tracing::{span, Level};
 
-use super::{CommandExecute, FlakeHubClient, ProjectMetadata};
+use super::add::flake::{rename_flake_input, InputRename};
+use super::convert::{input_conversions, ConversionReport};
+use super::{
+    local_flake_dir, nix_command, print_json, CommandExecute, FlakeHubClient, ProjectMetadata,
+    SourceHost, SudoChoice,
+};
 
 static ROLLING_RELEASE_BUILD_META_REGEX: Lazy<regex::Regex> =
     Lazy::new(|| regex::Regex::new(r"(rev)-.{40}").unwrap());
@@ -14,10 +20,10 @@ static RELEASE_VERSION_REGEX: Lazy<regex::Regex> = Lazy::new(|| {
     regex::Regex::new(r"0\.(?<year>[[:digit:]]{2})(?<month>[[:digit:]]{2}).+").unwrap()
 });
 
-/// Convert flake inputs from FlakeHub back to GitHub.
+/// Convert flake inputs from FlakeHub back to where their source lives, like GitHub.
 #[derive(Debug, Parser)]
 pub(crate) struct EjectSubcommand {
-    /// The flake.nix to convert.
+    /// The flake.nix to convert, or `-` to read it from stdin (implies `--dry-run`).
     #[clap(long, default_value = "./flake.nix")]
     pub(crate) flake_path: PathBuf,
 
@@ -25,34 +31,105 @@ pub(crate) struct EjectSubcommand {
     #[clap(long)]
     pub(crate) dry_run: bool,
 
+    /// Replace any invalid UTF-8 in the flake.nix instead of refusing to edit it.
+    #[clap(long)]
+    pub(crate) lossy: bool,
+
+    /// Write the new flake.nix even if it no longer parses.
+    #[clap(long)]
+    pub(crate) force: bool,
+
+    /// Rename an input while ejecting it, as `old=new`. The input's `follows` and its argument in
+    /// the `outputs` function are renamed too. May be given more than once.
+    #[clap(long = "rename", value_name = "OLD=NEW")]
+    pub(crate) renames: Vec<InputRename>,
+
+    /// Print a JSON report of each input's old URL, new URL, and whether it was ejected, instead
+    /// of the new flake.nix contents.
+    #[clap(long)]
+    pub(crate) json: bool,
+
+    /// Run `nix flake lock` after writing the new flake.nix. Check the guessed versions first;
+    /// they might not match the tags upstream.
+    #[clap(long)]
+    pub(crate) lock: bool,
+
     #[clap(from_global)]
     api_addr: url::Url,
+
+    #[clap(from_global)]
+    print_commands: bool,
 }
 
 #[async_trait::async_trait]
 impl CommandExecute for EjectSubcommand {
     #[tracing::instrument(skip_all)]
-    async fn execute(self) -> color_eyre::Result<ExitCode> {
-        if !self.flake_path.exists() {
+    async fn execute(mut self) -> color_eyre::Result<ExitCode> {
+        let from_stdin = crate::cli::cmd::add::is_stdin(&self.flake_path);
+        // There's no file to write the result back to when the flake came from stdin
+        if from_stdin {
+            self.dry_run = true;
+        }
+
+        if !from_stdin && !self.flake_path.exists() {
             return Err(color_eyre::eyre::eyre!(
                 "the flake at {} did not exist",
                 self.flake_path.display()
             ));
         }
 
-        let (flake_contents, parsed) = crate::cli::cmd::add::load_flake(&self.flake_path).await?;
-        let new_flake_contents = self
+        let (flake_contents, parsed) =
+            crate::cli::cmd::add::load_flake(&self.flake_path, self.lossy).await?;
+        let mut new_flake_contents = self
             .eject_inputs_to_github(&parsed.expression, &flake_contents)
             .await?;
+        for rename in &self.renames {
+            let parsed = nixel::parse(new_flake_contents.clone());
+            new_flake_contents =
+                rename_flake_input(&parsed.expression, &new_flake_contents, rename)?;
+        }
 
-        if self.dry_run {
+        if self.json {
+            let mut report = ConversionReport::default();
+            report.add(
+                &self.flake_path,
+                input_conversions(
+                    &parsed.expression,
+                    &nixel::parse(new_flake_contents.clone()).expression,
+                )?,
+            );
+            print_json(report)?;
+        } else if self.dry_run {
             println!("{new_flake_contents}");
-        } else {
-            tokio::fs::write(self.flake_path, new_flake_contents).await?;
+        }
+
+        if !self.dry_run {
+            if !self.force {
+                crate::cli::cmd::add::verify_edited_flake(&parsed.expression, &new_flake_contents)?;
+            }
+
+            crate::cli::cmd::add::write_edited_flake(
+                &self.flake_path,
+                &flake_contents,
+                &new_flake_contents,
+            )
+            .await?;
             // NOTE: We don't auto-lock like we do in `fh convert` because this is a lossy process.
             // We don't know if the version was a tag like `v1.0.0` or if it was just `1.0.0` (or
             // any other format). So, we do a best effort attempt of assuming `1.0.0` and letting
-            // the user fix it up if that was wrong.
+            // the user fix it up if that was wrong. `--lock` is for users who trust the guess.
+            if self.lock {
+                let args = vec![
+                    "flake".to_string(),
+                    "lock".to_string(),
+                    local_flake_dir(&self.flake_path),
+                ];
+
+                tracing::debug!("Running: nix {}", args.join(" "));
+                nix_command(&args, SudoChoice::Never, self.print_commands)
+                    .await
+                    .wrap_err("failed to lock the ejected flake")?;
+            }
         }
 
         Ok(ExitCode::SUCCESS)
@@ -107,7 +184,7 @@ impl EjectSubcommand {
             tracing::trace!("Parsed URL: {:?}", maybe_parsed_url);
 
             let new_input_url = match maybe_parsed_url {
-                Some(parsed_url) => eject_input_to_github(&self.api_addr, parsed_url).await?,
+                Some(parsed_url) => eject_input_to_source(&self.api_addr, parsed_url).await?,
                 None => None,
             };
 
@@ -138,7 +215,7 @@ impl EjectSubcommand {
 }
 
 #[tracing::instrument(skip_all)]
-async fn eject_input_to_github(
+async fn eject_input_to_source(
     api_addr: &url::Url,
     parsed_url: url::Url,
 ) -> color_eyre::Result<Option<url::Url>> {
@@ -147,7 +224,7 @@ async fn eject_input_to_github(
     if let Some(host) = parsed_url.host() {
         // A URL like `https://flakehub.com/...`
         if host == url::Host::Domain("flakehub.com") {
-            url = Some(eject_flakehub_input_to_github(parsed_url, api_addr).await?);
+            url = Some(eject_flakehub_input_to_source(parsed_url, api_addr).await?);
         }
     }
 
@@ -155,7 +232,7 @@ async fn eject_input_to_github(
 }
 
 #[tracing::instrument(skip_all)]
-async fn eject_flakehub_input_to_github(
+async fn eject_flakehub_input_to_source(
     parsed_url: url::Url,
     api_addr: &url::Url,
 ) -> color_eyre::Result<url::Url> {
@@ -174,6 +251,7 @@ async fn eject_flakehub_input_to_github(
         source_github_owner_repo_pair,
         source_subdirectory,
         version,
+        source_host,
     } = FlakeHubClient::metadata(api_addr.as_ref(), org, project, version).await?;
 
     let maybe_version_or_branch = match source_github_owner_repo_pair.to_lowercase().as_str() {
@@ -205,7 +283,22 @@ async fn eject_flakehub_input_to_github(
         }
     };
 
-    let mut new_url = format!("github:{source_github_owner_repo_pair}");
+    let mut new_url = match source_host {
+        Some(SourceHost::Gitlab) => format!("gitlab:{source_github_owner_repo_pair}"),
+        Some(SourceHost::Sourcehut) => {
+            // SourceHut owners are always written with a leading `~`
+            let owner_repo_pair = source_github_owner_repo_pair.trim_start_matches('~');
+            format!("sourcehut:~{owner_repo_pair}")
+        }
+        Some(SourceHost::Github) | None => format!("github:{source_github_owner_repo_pair}"),
+        Some(SourceHost::Unknown) => {
+            tracing::warn!(
+                "FlakeHub doesn't say where {org}/{project} came from in a way fh understands; \
+                assuming it's on GitHub"
+            );
+            format!("github:{source_github_owner_repo_pair}")
+        }
+    };
     if let Some(version_or_branch) = maybe_version_or_branch {
         new_url.push('/');
         new_url.push_str(&version_or_branch);
@@ -254,12 +347,17 @@ mod test {
                 (format!("{org}/{project}"), None)
             };
 
-        axum::Json(serde_json::json!({
+        let mut metadata = serde_json::json!({
             "source_github_owner_repo_pair": source_github_owner_repo_pair,
             "source_subdirectory": source_subdirectory,
             "version": version,
-        }))
-        .into_response()
+        });
+        // Orgs named after a forge are hosted there
+        if ["gitlab", "sourcehut", "codeberg"].contains(&org.as_str()) {
+            metadata["source_host"] = serde_json::json!(org);
+        }
+
+        axum::Json(metadata).into_response()
     }
 
     fn test_router() -> axum::Router {
@@ -270,14 +368,7 @@ mod test {
     }
 
     pub fn test_server() -> axum_test::TestServer {
-        let test_server_config = axum_test::TestServerConfig::builder()
-            .http_transport()
-            .build();
-        axum_test::TestServer::new_with_config(
-            test_router().into_make_service(),
-            test_server_config,
-        )
-        .unwrap()
+        crate::cli::cmd::test_server(test_router())
     }
 
     #[tokio::test]
@@ -288,7 +379,7 @@ mod test {
 
         let input_url =
             url::Url::parse("https://flakehub.com/f/someorg/somerepo/*.tar.gz").unwrap();
-        let github_url = super::eject_input_to_github(&server_url, input_url)
+        let github_url = super::eject_input_to_source(&server_url, input_url)
             .await
             .ok()
             .flatten()
@@ -304,7 +395,7 @@ mod test {
 
         let input_url =
             url::Url::parse("https://flakehub.com/f/someorg/somerepo/1.0.0.tar.gz").unwrap();
-        let github_url = super::eject_input_to_github(&server_url, input_url)
+        let github_url = super::eject_input_to_source(&server_url, input_url)
             .await
             .ok()
             .flatten()
@@ -320,7 +411,7 @@ mod test {
 
         let input_url =
             url::Url::parse("https://flakehub.com/f/nixos/nixpkgs/0.2311.*.tar.gz").unwrap();
-        let github_url = super::eject_input_to_github(&server_url, input_url)
+        let github_url = super::eject_input_to_source(&server_url, input_url)
             .await
             .ok()
             .flatten()
@@ -328,6 +419,35 @@ mod test {
         assert_eq!(github_url.to_string(), "github:nixos/nixpkgs/nixos-23.11");
     }
 
+    #[tokio::test]
+    async fn flakehub_to_other_forges() {
+        let test_server = test_server();
+        let server_url = test_server.server_address().unwrap();
+
+        for (input_url, source_url) in [
+            (
+                "https://flakehub.com/f/gitlab/somerepo/1.0.0.tar.gz",
+                "gitlab:gitlab/somerepo/1.0.0",
+            ),
+            (
+                "https://flakehub.com/f/sourcehut/somerepo/*.tar.gz",
+                "sourcehut:~sourcehut/somerepo",
+            ),
+            // Unknown forges fall back to GitHub
+            (
+                "https://flakehub.com/f/codeberg/somerepo/1.0.0.tar.gz",
+                "github:codeberg/somerepo/1.0.0",
+            ),
+        ] {
+            let ejected =
+                super::eject_input_to_source(&server_url, url::Url::parse(input_url).unwrap())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            assert_eq!(ejected.to_string(), source_url);
+        }
+    }
+
     #[tokio::test]
     async fn test_flake8_eject() {
         let test_server = test_server();
@@ -337,7 +457,13 @@ mod test {
         let eject = super::EjectSubcommand {
             flake_path: "".into(),
             dry_run: true,
+            lossy: false,
+            force: false,
+            renames: vec![],
+            json: false,
+            lock: false,
             api_addr: server_url,
+            print_commands: false,
         };
         let flake_contents = include_str!(concat!(
             env!("CARGO_MANIFEST_DIR"),
@@ -358,4 +484,55 @@ mod test {
         assert!(new_flake_contents.contains("github:edolstra/nix-warez?dir=blender"));
         assert!(new_flake_contents.contains("github:nix-community/home-manager/release-23.05"));
     }
+
+    #[tokio::test]
+    async fn eject_and_rename() {
+        let test_server = test_server();
+        let server_url = test_server.server_address().unwrap();
+
+        let eject = super::EjectSubcommand {
+            flake_path: "".into(),
+            dry_run: true,
+            lossy: false,
+            force: false,
+            renames: vec!["nixpkgs=nixpkgs-stable".parse().unwrap()],
+            json: false,
+            lock: false,
+            api_addr: server_url,
+            print_commands: false,
+        };
+        let flake_contents = r#"{
+  inputs.nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.2305.*.tar.gz";
+  inputs.fh.url = "https://flakehub.com/f/DeterminateSystems/fh/0.0.0.tar.gz";
+  inputs.fh.inputs.nixpkgs.follows = "nixpkgs";
+
+  outputs = { self, nixpkgs, fh }: { };
+}
+"#
+        .to_string();
+        let parsed = nixel::parse(flake_contents.clone());
+
+        let ejected = eject
+            .eject_inputs_to_github(&parsed.expression, &flake_contents)
+            .await
+            .unwrap();
+        let renamed = crate::cli::cmd::add::flake::rename_flake_input(
+            &nixel::parse(ejected.clone()).expression,
+            &ejected,
+            &eject.renames[0],
+        )
+        .unwrap();
+
+        assert_eq!(
+            renamed,
+            r#"{
+  inputs.nixpkgs-stable.url = "github:NixOS/nixpkgs/nixos-23.05";
+  inputs.fh.url = "github:DeterminateSystems/fh/0.0.0";
+  inputs.fh.inputs.nixpkgs.follows = "nixpkgs-stable";
+
+  outputs = { self, nixpkgs-stable, fh }: { };
+}
+"#
+        );
+    }
 }
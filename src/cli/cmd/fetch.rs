@@ -0,0 +1,334 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, WrapErr};
+use tempfile::tempdir;
+use tokio::io::AsyncWriteExt as _;
+
+use super::{
+    nix_command_captured, parse_flake_output_ref, CommandExecute, FlakeHubClient, SudoChoice,
+};
+use crate::cli::error::FhError;
+use crate::info_println;
+use crate::shared::progress::Spinner;
+
+/// Fetch a FlakeHub flake output into the local Nix store and create a GC root for it.
+#[derive(Debug, Parser)]
+pub(crate) struct FetchSubcommand {
+    /// The FlakeHub flake reference to fetch, followed by the symlink to create, which points at
+    /// the fetched store path and keeps it from being garbage collected. Give several pairs to
+    /// fetch several outputs.
+    /// References must be of this form: {org}/{flake}/{version_req}#{attr_path}
+    #[arg(required = true, num_args = 2.., value_names = ["FLAKE_REF", "TARGET_LINK"])]
+    refs_and_links: Vec<String>,
+
+    /// Copy the closure even if the target link already points at the resolved store path.
+    #[clap(long)]
+    force: bool,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+
+    #[clap(from_global)]
+    cache_addr: url::Url,
+
+    #[clap(from_global)]
+    frontend_addr: url::Url,
+
+    #[clap(from_global)]
+    print_commands: bool,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for FetchSubcommand {
+    #[tracing::instrument(skip_all)]
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let pairs = ref_link_pairs(&self.refs_and_links)?;
+
+        // Every fetch shares a single netrc, which is rewritten with each output's token
+        let dir = tempdir()?;
+        let netrc_path = dir.path().join("netrc");
+
+        // A single fetch fails the way it always has; with several, the rest are still attempted
+        if let [(flake_ref, target_link)] = &pairs[..] {
+            self.fetch(flake_ref, target_link, &netrc_path).await?;
+            dir.close()?;
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut failures = Vec::new();
+        for (flake_ref, target_link) in &pairs {
+            if let Err(e) = self.fetch(flake_ref, target_link, &netrc_path).await {
+                failures.push((flake_ref, e));
+            }
+        }
+        dir.close()?;
+
+        if failures.is_empty() {
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        eprintln!(
+            "Failed to fetch {} of {} outputs:",
+            failures.len(),
+            pairs.len()
+        );
+        for (flake_ref, e) in failures {
+            eprintln!("* {flake_ref}: {e:#}");
+        }
+
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+impl FetchSubcommand {
+    // Resolves `flake_ref` and points `target_link` at its store path, unless it already does.
+    async fn fetch(
+        &self,
+        flake_ref: &str,
+        target_link: &Path,
+        netrc_path: &Path,
+    ) -> color_eyre::Result<()> {
+        let output_ref = parse_flake_output_ref(&self.frontend_addr, flake_ref)?;
+
+        let resolved_path =
+            FlakeHubClient::resolve(self.api_addr.as_ref(), &output_ref, true).await?;
+        tracing::debug!(
+            "Successfully resolved reference {} to path {}",
+            &output_ref,
+            &resolved_path.store_path
+        );
+
+        if !self.force && is_up_to_date(target_link, &resolved_path.store_path).await {
+            info_println!(
+                "{} is up to date ({})",
+                target_link.display(),
+                resolved_path.store_path
+            );
+            return Ok(());
+        }
+
+        let Some(token) = resolved_path.token else {
+            return Err(eyre!("FlakeHub did not return a restricted token!"));
+        };
+
+        self.copy_closure_with_gc_root(&resolved_path.store_path, &token, target_link, netrc_path)
+            .await?;
+        verify_gc_root(target_link, &resolved_path.store_path).await?;
+
+        println!("{}", resolved_path.store_path);
+
+        Ok(())
+    }
+
+    // Substitutes `store_path` from FlakeHub Cache using the scoped `token`, and points
+    // `target_link` at it.
+    async fn copy_closure_with_gc_root(
+        &self,
+        store_path: &str,
+        token: &str,
+        target_link: &Path,
+        netrc_path: &Path,
+    ) -> color_eyre::Result<()> {
+        let mut netrc = tokio::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .mode(0o600)
+            .open(netrc_path)
+            .await?;
+        netrc
+            .write_all(
+                format!(
+                    "machine {} login flakehub password {token}\n",
+                    self.cache_addr.host_str().expect("valid host"),
+                )
+                .as_bytes(),
+            )
+            .await
+            .wrap_err("writing restricted netrc file")?;
+
+        let target_link = target_link
+            .to_str()
+            .ok_or_else(|| eyre!("{} is not valid UTF-8", target_link.display()))?
+            .to_string();
+
+        let mut nix_args = vec![
+            "copy".to_string(),
+            "--option".to_string(),
+            "narinfo-cache-negative-ttl".to_string(),
+            "0".to_string(),
+            "--from".to_string(),
+            self.cache_addr.to_string(),
+            "--netrc-file".to_string(),
+            netrc_path.display().to_string(),
+        ];
+
+        let copy_supports_out_link = crate::shared::nix_info::nix_info()
+            .await
+            .is_some_and(|info| info.copy_supports_out_link());
+        if copy_supports_out_link {
+            nix_args.extend_from_slice(&["--out-link".to_string(), target_link.clone()]);
+        }
+        nix_args.push(store_path.to_string());
+
+        let spinner = Spinner::start(format!("Copying {store_path} from FlakeHub Cache..."));
+        nix_command_captured(&nix_args, SudoChoice::Never, self.print_commands)
+            .await
+            .wrap_err("failed to copy resolved store path with Nix")?;
+        drop(spinner);
+
+        if !copy_supports_out_link {
+            // The path is already valid, so this only registers the GC root
+            nix_command_captured(
+                &[
+                    "build".to_string(),
+                    "--max-jobs".to_string(),
+                    "0".to_string(),
+                    "--out-link".to_string(),
+                    target_link,
+                    store_path.to_string(),
+                ],
+                SudoChoice::Never,
+                self.print_commands,
+            )
+            .await
+            .wrap_err("failed to create a GC root for the resolved store path")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Makes sure that Nix really did leave a symlink at `target_link` that points into the store that
+// `store_path` is in.
+async fn verify_gc_root(target_link: &Path, store_path: &str) -> Result<(), FhError> {
+    let not_created =
+        || FhError::GcRootNotCreated(target_link.display().to_string(), store_path.to_string());
+
+    let is_symlink = tokio::fs::symlink_metadata(target_link)
+        .await
+        .is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if !is_symlink {
+        return Err(not_created());
+    }
+
+    let store_dir = Path::new(store_path)
+        .parent()
+        .unwrap_or(Path::new("/nix/store"));
+    match tokio::fs::read_link(target_link).await {
+        Ok(target) if target.starts_with(store_dir) => Ok(()),
+        _ => Err(not_created()),
+    }
+}
+
+// Splits the positional arguments into (flake reference, target link) pairs.
+fn ref_link_pairs(refs_and_links: &[String]) -> color_eyre::Result<Vec<(String, PathBuf)>> {
+    // An odd trailing argument is an error
+    let pairs = refs_and_links.chunks_exact(2);
+    if let [unpaired] = pairs.remainder() {
+        return Err(eyre!(
+            "`{unpaired}` has no target link; flake references and target links must come in pairs"
+        ));
+    }
+
+    Ok(pairs
+        .map(|pair| (pair[0].clone(), PathBuf::from(&pair[1])))
+        .collect())
+}
+
+// Whether `target_link` already points at `store_path`, and that path is still in the store.
+async fn is_up_to_date(target_link: &Path, store_path: &str) -> bool {
+    match tokio::fs::read_link(target_link).await {
+        Ok(existing) => {
+            existing == Path::new(store_path)
+                && tokio::fs::try_exists(store_path)
+                    .await
+                    .is_ok_and(|exists| exists)
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::is_up_to_date;
+
+    #[test]
+    fn ref_link_pairs() {
+        let args = ["a/b/*#x", "./x", "c/d/*#y", "./y"].map(String::from);
+        assert_eq!(
+            super::ref_link_pairs(&args).unwrap(),
+            [
+                (String::from("a/b/*#x"), PathBuf::from("./x")),
+                (String::from("c/d/*#y"), PathBuf::from("./y")),
+            ]
+        );
+
+        let err = super::ref_link_pairs(&args[..3]).unwrap_err();
+        assert!(err.to_string().contains("`c/d/*#y` has no target link"));
+    }
+
+    #[tokio::test]
+    async fn verify_gc_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_dir = dir.path().join("store");
+        let store_path = store_dir.join("xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx-hello-2.12.1");
+        std::fs::create_dir_all(&store_path).unwrap();
+        let store_path = store_path.to_str().unwrap();
+
+        let target_link = dir.path().join("result");
+        assert!(super::verify_gc_root(&target_link, store_path)
+            .await
+            .is_err());
+
+        // A regular file where the link should be isn't a GC root
+        std::fs::write(&target_link, "").unwrap();
+        assert!(super::verify_gc_root(&target_link, store_path)
+            .await
+            .is_err());
+        std::fs::remove_file(&target_link).unwrap();
+
+        std::os::unix::fs::symlink(dir.path(), &target_link).unwrap();
+        assert!(super::verify_gc_root(&target_link, store_path)
+            .await
+            .is_err());
+        std::fs::remove_file(&target_link).unwrap();
+
+        std::os::unix::fs::symlink(store_path, &target_link).unwrap();
+        assert!(super::verify_gc_root(&target_link, store_path)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn up_to_date_when_link_points_at_existing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir
+            .path()
+            .join("xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx-hello-2.12.1");
+        let other_path = dir
+            .path()
+            .join("yyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy-hello-2.12.2");
+        std::fs::create_dir(&store_path).unwrap();
+        let store_path = store_path.to_str().unwrap();
+        let other_path = other_path.to_str().unwrap();
+
+        let target_link = dir.path().join("result");
+        assert!(!is_up_to_date(&target_link, store_path).await);
+
+        std::os::unix::fs::symlink(store_path, &target_link).unwrap();
+        assert!(is_up_to_date(&target_link, store_path).await);
+        assert!(!is_up_to_date(&target_link, other_path).await);
+
+        // A link to a path that has since been garbage collected needs fetching again
+        let dangling_link = dir.path().join("dangling");
+        std::os::unix::fs::symlink(other_path, &dangling_link).unwrap();
+        assert!(!is_up_to_date(&dangling_link, other_path).await);
+    }
+}
@@ -0,0 +1,404 @@
+use std::{collections::HashMap, path::Path};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    cli::{cmd::list::FLAKEHUB_WEB_ROOT, error::FhError},
+    flakehub_url,
+};
+
+use super::handlers::{
+    Bun, Cpp, Deno, Elixir, Elm, Flake, Go, Haskell, Input, Java, JavaScript, Kotlin,
+    LanguageDefaults, Ocaml, Php, Python, Ruby, Rust, Scala, Swift, Terraform, Zig,
+};
+
+/// A non-interactive description of the choices `fh init` otherwise prompts for.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct InitConfig {
+    /// An optional description for the flake.
+    pub(crate) description: Option<String>,
+
+    /// The systems the flake supports, like `x86_64-linux` or `aarch64-darwin`.
+    pub(crate) systems: Vec<String>,
+
+    /// The Nixpkgs release to use: `latest`, `unstable`, a specific release like `24.11`, or the
+    /// URL of a Nixpkgs flake.
+    pub(crate) nixpkgs: Option<String>,
+
+    /// Which language toolchains to include in the development environment.
+    pub(crate) languages: Languages,
+
+    /// Extra flake inputs, in addition to Nixpkgs and the inputs that languages add.
+    pub(crate) inputs: HashMap<String, Input>,
+
+    /// Overlays to apply to Nixpkgs, like `rust-overlay.overlays.default`.
+    pub(crate) overlay_refs: Vec<String>,
+
+    /// Attributes to add to Nixpkgs with an overlay, mapped to their Nix expressions.
+    pub(crate) overlay_attrs: HashMap<String, String>,
+
+    /// Nixpkgs packages to add to the development environment, like `jq`.
+    pub(crate) dev_shell_packages: Vec<String>,
+
+    /// Nixpkgs packages that aren't free software but may be built anyway, like `terraform`.
+    pub(crate) unfree_packages: Vec<String>,
+
+    /// Environment variables to set in the development environment.
+    pub(crate) env_vars: HashMap<String, String>,
+
+    /// A shell hook that runs every time you enter the development environment.
+    pub(crate) shell_hook: Option<String>,
+
+    /// Whether to add doc comments explaining the different parts of the flake.
+    pub(crate) doc_comments: bool,
+
+    /// Whether to support legacy Nix commands like `nix-build` and `nix-shell`.
+    pub(crate) flake_compat: bool,
+}
+
+impl InitConfig {
+    /// The config that recreates `flake`, for replaying an interactive session later. Languages
+    /// aren't listed, because the packages and inputs they added are recorded directly.
+    pub(crate) fn from_flake(flake: &Flake, flake_compat: bool) -> Self {
+        let mut inputs = flake.inputs.clone();
+        let nixpkgs = inputs.remove("nixpkgs").map(|input| input.reference);
+
+        Self {
+            description: flake.description.clone(),
+            systems: flake.systems.clone(),
+            nixpkgs,
+            languages: Languages::default(),
+            inputs,
+            overlay_refs: flake.overlay_refs.clone(),
+            overlay_attrs: flake.overlay_attrs.clone(),
+            dev_shell_packages: flake.dev_shell_packages.clone(),
+            unfree_packages: flake.unfree_packages.clone(),
+            env_vars: flake.env_vars.clone(),
+            shell_hook: flake.shell_hook.clone(),
+            doc_comments: flake.doc_comments,
+            flake_compat,
+        }
+    }
+
+    /// Fill in a flake from the config, the same way the interactive prompts would.
+    pub(crate) fn into_flake(self) -> Result<Flake, FhError> {
+        if self.systems.is_empty() {
+            return Err(FhError::InvalidInitConfig(String::from(
+                "`systems` needs at least one system",
+            )));
+        }
+
+        let mut flake = Flake {
+            description: self.description,
+            systems: self.systems,
+            overlay_refs: self.overlay_refs,
+            overlay_attrs: self.overlay_attrs,
+            env_vars: self.env_vars,
+            shell_hook: self.shell_hook,
+            doc_comments: self.doc_comments,
+            ..Default::default()
+        };
+
+        flake.inputs.insert(
+            String::from("nixpkgs"),
+            Input::new(nixpkgs_url(self.nixpkgs.as_deref())?.as_str(), None),
+        );
+
+        self.languages.add_to(&mut flake);
+
+        flake.inputs.extend(self.inputs);
+        flake.dev_shell_packages.extend(self.dev_shell_packages);
+        flake.unfree_packages.extend(self.unfree_packages);
+
+        Ok(flake)
+    }
+}
+
+// The Nixpkgs flake for the `nixpkgs` setting, which defaults to the latest stable release.
+fn nixpkgs_url(nixpkgs: Option<&str>) -> Result<Url, FhError> {
+    let version = match nixpkgs.unwrap_or("latest") {
+        "latest" => String::from("*"),
+        "unstable" => String::from("0.1.*"),
+        release => match release.split_once('.') {
+            // `24.11` is `0.2411.*` on FlakeHub
+            Some((year, month))
+                if [year, month]
+                    .iter()
+                    .all(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_digit())) =>
+            {
+                format!("0.{year}{month}.*")
+            }
+            _ => {
+                return release.parse().map_err(|_| {
+                    FhError::InvalidInitConfig(format!(
+                        "`nixpkgs` must be `latest`, `unstable`, a release like `24.11`, or a URL, \
+                        not `{release}`"
+                    ))
+                })
+            }
+        },
+    };
+
+    Ok(flakehub_url!(
+        FLAKEHUB_WEB_ROOT,
+        "f",
+        "NixOS",
+        "nixpkgs",
+        &version
+    ))
+}
+
+/// Per-language toggles. Languages that are omitted are not included.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Languages {
+    pub(crate) bun: bool,
+    pub(crate) cpp: bool,
+    pub(crate) deno: bool,
+    pub(crate) elixir: bool,
+    pub(crate) elm: bool,
+    pub(crate) go: bool,
+    pub(crate) haskell: bool,
+    pub(crate) java: bool,
+    pub(crate) javascript: bool,
+    pub(crate) kotlin: bool,
+    pub(crate) ocaml: bool,
+    pub(crate) php: bool,
+    pub(crate) python: bool,
+    pub(crate) ruby: bool,
+    pub(crate) rust: bool,
+    pub(crate) scala: bool,
+    pub(crate) swift: bool,
+    /// Adds OpenTofu. For Terraform itself, list `terraform` in both `dev_shell_packages` and
+    /// `unfree_packages` instead.
+    pub(crate) terraform: bool,
+    pub(crate) zig: bool,
+}
+
+impl Languages {
+    fn add_to(&self, flake: &mut Flake) {
+        if self.bun {
+            Bun::add_defaults(flake);
+        }
+        if self.cpp {
+            Cpp::add_defaults(flake);
+        }
+        if self.deno {
+            Deno::add_defaults(flake);
+        }
+        if self.elixir {
+            Elixir::add_defaults(flake);
+        }
+        if self.elm {
+            Elm::add_defaults(flake);
+        }
+        if self.go {
+            Go::add_defaults(flake);
+        }
+        if self.haskell {
+            Haskell::add_defaults(flake);
+        }
+        if self.java {
+            Java::add_defaults(flake);
+        }
+        if self.javascript {
+            JavaScript::add_defaults(flake);
+        }
+        if self.kotlin {
+            Kotlin::add_defaults(flake);
+        }
+        if self.ocaml {
+            Ocaml::add_defaults(flake);
+        }
+        if self.php {
+            Php::add_defaults(flake);
+        }
+        if self.python {
+            Python::add_defaults(flake);
+        }
+        if self.ruby {
+            Ruby::add_defaults(flake);
+        }
+        if self.rust {
+            Rust::add_defaults(flake);
+        }
+        if self.scala {
+            Scala::add_defaults(flake);
+        }
+        if self.swift {
+            Swift::add_defaults(flake);
+        }
+        if self.terraform {
+            Terraform::add_defaults(flake);
+        }
+        if self.zig {
+            Zig::add_defaults(flake);
+        }
+    }
+}
+
+/// Read an `fh init` config from the file at `path`, as TOML if it has a `.toml` extension and as
+/// JSON otherwise.
+pub(crate) fn from_spec(path: &Path, contents: &str) -> Result<InitConfig, FhError> {
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        from_toml(contents)
+    } else {
+        from_json(contents)
+    }
+}
+
+/// Read an `fh init` config from JSON.
+pub(crate) fn from_json(contents: &str) -> Result<InitConfig, FhError> {
+    serde_json::from_str(contents).map_err(|e| FhError::InvalidInitConfig(e.to_string()))
+}
+
+/// Read an `fh init` config from TOML.
+pub(crate) fn from_toml(contents: &str) -> Result<InitConfig, FhError> {
+    toml::from_str(contents).map_err(|e| FhError::InvalidInitConfig(e.to_string()))
+}
+
+pub(crate) fn schema() -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&schemars::schema_for!(InitConfig))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cli::error::FhError;
+
+    #[test]
+    fn config_into_flake() {
+        let config = super::from_json(
+            r#"{
+                "systems": ["x86_64-linux"],
+                "nixpkgs": "24.11",
+                "languages": { "go": true, "rust": true },
+                "dev-shell-packages": ["jq"],
+                "env-vars": { "FOO": "bar" }
+            }"#,
+        )
+        .unwrap();
+        let flake = config.into_flake().unwrap();
+
+        assert_eq!(flake.systems, ["x86_64-linux"]);
+        assert_eq!(
+            flake.inputs["nixpkgs"].reference,
+            "https://flakehub.com/f/NixOS/nixpkgs/0.2411.*"
+        );
+        assert!(flake.inputs.contains_key("rust-overlay"));
+        assert_eq!(flake.dev_shell_packages, ["go_1_23", "rustToolchain", "jq"]);
+        assert_eq!(flake.env_vars["FOO"], "bar");
+    }
+
+    #[test]
+    fn config_from_toml() {
+        let toml = r#"
+            systems = ["x86_64-linux"]
+            nixpkgs = "24.11"
+            dev-shell-packages = ["jq"]
+
+            [languages]
+            go = true
+        "#;
+
+        let config = super::from_spec(std::path::Path::new("spec.toml"), toml).unwrap();
+        let flake = config.into_flake().unwrap();
+        assert_eq!(flake.systems, ["x86_64-linux"]);
+        assert_eq!(flake.dev_shell_packages, ["go_1_23", "jq"]);
+
+        // Anything else is read as JSON
+        assert!(matches!(
+            super::from_spec(std::path::Path::new("spec.json"), toml),
+            Err(FhError::InvalidInitConfig(_))
+        ));
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let config = super::from_json(
+            r#"{
+                "description": "A flake",
+                "systems": ["x86_64-linux"],
+                "nixpkgs": "unstable",
+                "languages": { "php": true },
+                "shell-hook": "echo hi",
+                "flake-compat": true
+            }"#,
+        )
+        .unwrap();
+        let flake = config.into_flake().unwrap();
+
+        let dumped = serde_json::to_string(&super::InitConfig::from_flake(&flake, true)).unwrap();
+        let replayed = super::from_json(&dumped).unwrap();
+        assert_eq!(
+            replayed.nixpkgs.as_deref(),
+            Some("https://flakehub.com/f/NixOS/nixpkgs/0.1.*")
+        );
+        assert!(replayed.flake_compat);
+
+        let replayed = replayed.into_flake().unwrap();
+        assert_eq!(replayed.description, flake.description);
+        assert_eq!(replayed.systems, flake.systems);
+        assert_eq!(replayed.dev_shell_packages, flake.dev_shell_packages);
+        assert_eq!(replayed.overlay_refs, flake.overlay_refs);
+        assert_eq!(replayed.shell_hook, flake.shell_hook);
+        assert_eq!(
+            replayed
+                .inputs
+                .keys()
+                .collect::<std::collections::BTreeSet<_>>(),
+            flake.inputs.keys().collect()
+        );
+        assert_eq!(
+            replayed.inputs["nixpkgs"].reference,
+            flake.inputs["nixpkgs"].reference
+        );
+    }
+
+    #[test]
+    fn invalid_configs() {
+        for (config, error) in [
+            (
+                r#"{ "systems": ["x86_64-linux"], "languages": { "cobol": true } }"#,
+                "unknown field `cobol`",
+            ),
+            (r#"{ "systems": [] }"#, "needs at least one system"),
+            (
+                r#"{ "systems": ["x86_64-linux"], "nixpkgs": "oldest" }"#,
+                "not `oldest`",
+            ),
+        ] {
+            let result = super::from_json(config).and_then(super::InitConfig::into_flake);
+            assert!(
+                matches!(result, Err(FhError::InvalidInitConfig(ref e)) if e.contains(error)),
+                "{config} should fail with {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn schema_is_valid_json() {
+        let schema = super::schema().unwrap();
+        let schema: serde_json::Value = serde_json::from_str(&schema).unwrap();
+
+        let properties = schema["properties"].as_object().unwrap();
+        for property in [
+            "description",
+            "systems",
+            "nixpkgs",
+            "languages",
+            "env-vars",
+            "shell-hook",
+            "doc-comments",
+            "dev-shell-packages",
+            "flake-compat",
+        ] {
+            assert!(
+                properties.contains_key(property),
+                "schema is missing `{property}`"
+            );
+        }
+    }
+}
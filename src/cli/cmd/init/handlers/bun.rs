@@ -0,0 +1,22 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+// Files that mark a project as using Bun rather than Node.js
+pub(super) const BUN_FILES: &[&str] = &["bunfig.toml", "bun.lockb", "bun.lock"];
+
+pub(crate) struct Bun;
+
+impl Handler for Bun {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if project.has_one_of(BUN_FILES) && Prompt::for_tool("Bun") {
+            Self::add_defaults(flake);
+        }
+    }
+}
+
+impl LanguageDefaults for Bun {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("bun"));
+    }
+}
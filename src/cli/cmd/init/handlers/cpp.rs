@@ -0,0 +1,51 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+const CPP_TOOLCHAINS: &[&str] = &["GCC", "Clang"];
+
+pub(crate) struct Cpp;
+
+impl Handler for Cpp {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if project.has_one_of(&["CMakeLists.txt", "meson.build", "Makefile", "configure.ac"])
+            && Prompt::for_language("C/C++")
+        {
+            let toolchain = Prompt::select("Which toolchain would you prefer?", CPP_TOOLCHAINS);
+            add_toolchain(flake, &toolchain);
+
+            if project.has_file("CMakeLists.txt") {
+                flake.dev_shell_packages.push(String::from("cmake"));
+            }
+
+            if project.has_file("meson.build") {
+                flake.dev_shell_packages.push(String::from("meson"));
+                flake.dev_shell_packages.push(String::from("ninja"));
+            }
+
+            add_common_tools(flake);
+        }
+    }
+}
+
+impl LanguageDefaults for Cpp {
+    fn add_defaults(flake: &mut Flake) {
+        add_toolchain(
+            flake,
+            CPP_TOOLCHAINS
+                .first()
+                .expect("there's at least one C/C++ toolchain"),
+        );
+        flake.dev_shell_packages.push(String::from("cmake"));
+        add_common_tools(flake);
+    }
+}
+
+fn add_toolchain(flake: &mut Flake, toolchain: &str) {
+    flake.dev_shell_packages.push(toolchain.to_lowercase());
+}
+
+fn add_common_tools(flake: &mut Flake) {
+    flake.dev_shell_packages.push(String::from("pkg-config"));
+    flake.dev_shell_packages.push(String::from("gdb"));
+}
@@ -0,0 +1,22 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+// Files that mark a project as using Deno rather than Node.js
+pub(super) const DENO_FILES: &[&str] = &["deno.json", "deno.jsonc"];
+
+pub(crate) struct Deno;
+
+impl Handler for Deno {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if project.has_one_of(DENO_FILES) && Prompt::for_tool("Deno") {
+            Self::add_defaults(flake);
+        }
+    }
+}
+
+impl LanguageDefaults for Deno {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("deno"));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::cli::cmd::init::{project::Project, prompt::Prompt};
 
-use super::{Flake, Handler};
+use super::{Flake, Handler, LanguageDefaults};
 
 const ELIXIR_LATEST: &str = "elixir_1_15";
 const ERLANG_LATEST: &str = "erlang_26";
@@ -10,9 +10,7 @@ pub(crate) struct Elixir;
 impl Handler for Elixir {
     fn handle(project: &Project, flake: &mut Flake) {
         if project.has_file("mix.exs") && Prompt::for_language("Elixir") {
-            flake.dev_shell_packages.push(String::from(ELIXIR_LATEST));
-            flake.dev_shell_packages.push(String::from("elixir_ls"));
-            flake.dev_shell_packages.push(String::from(ERLANG_LATEST));
+            Self::add_defaults(flake);
 
             if Prompt::bool("Would you like to add Livebook to the environment?") {
                 flake.dev_shell_packages.push(String::from("livebook"));
@@ -20,3 +18,11 @@ impl Handler for Elixir {
         }
     }
 }
+
+impl LanguageDefaults for Elixir {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from(ELIXIR_LATEST));
+        flake.dev_shell_packages.push(String::from("elixir_ls"));
+        flake.dev_shell_packages.push(String::from(ERLANG_LATEST));
+    }
+}
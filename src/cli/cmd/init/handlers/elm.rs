@@ -1,15 +1,21 @@
 use crate::cli::cmd::init::{project::Project, prompt::Prompt};
 
-use super::{Flake, Handler};
+use super::{Flake, Handler, LanguageDefaults};
 
 pub(crate) struct Elm;
 
 impl Handler for Elm {
     fn handle(project: &Project, flake: &mut Flake) {
         if project.has_file("elm.json") && Prompt::for_language("Elm") {
-            flake
-                .dev_shell_packages
-                .push(String::from("elmPackages.elm"));
+            Self::add_defaults(flake);
         }
     }
 }
+
+impl LanguageDefaults for Elm {
+    fn add_defaults(flake: &mut Flake) {
+        flake
+            .dev_shell_packages
+            .push(String::from("elmPackages.elm"));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::cli::cmd::init::prompt::Prompt;
 
-use super::{Flake, Handler, Project};
+use super::{Flake, Handler, LanguageDefaults, Project};
 
 const GO_VERSIONS: &[&str] = &["1.22", "1.23"];
 
@@ -10,8 +10,21 @@ impl Handler for Go {
     fn handle(project: &Project, flake: &mut Flake) {
         if project.has_file("go.mod") && Prompt::for_language("Go") {
             let go_version = Prompt::select("Select a version of Go", GO_VERSIONS);
-            let go_version_attr = format!("go_{}", go_version.replace(".", "_"));
-            flake.dev_shell_packages.push(go_version_attr);
+            add_go(flake, &go_version);
         }
     }
 }
+
+impl LanguageDefaults for Go {
+    fn add_defaults(flake: &mut Flake) {
+        add_go(
+            flake,
+            GO_VERSIONS.last().expect("there's at least one Go version"),
+        );
+    }
+}
+
+fn add_go(flake: &mut Flake, go_version: &str) {
+    let go_version_attr = format!("go_{}", go_version.replace(".", "_"));
+    flake.dev_shell_packages.push(go_version_attr);
+}
@@ -0,0 +1,36 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+pub(crate) struct Haskell;
+
+impl Handler for Haskell {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if (project.has_file_with_extension("cabal")
+            || project.has_one_of(&["stack.yaml", "package.yaml"]))
+            && Prompt::for_language("Haskell")
+        {
+            Self::add_defaults(flake);
+
+            // The language server pulls in a lot, so it's opt-in
+            if Prompt::bool(
+                "Would you like to add the Haskell Language Server to your environment? It's a large download.",
+            ) {
+                flake
+                    .dev_shell_packages
+                    .push(String::from("haskell-language-server"));
+            }
+
+            if project.has_file("stack.yaml") && Prompt::for_tool("Stack") {
+                flake.dev_shell_packages.push(String::from("stack"));
+            }
+        }
+    }
+}
+
+impl LanguageDefaults for Haskell {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("ghc"));
+        flake.dev_shell_packages.push(String::from("cabal-install"));
+    }
+}
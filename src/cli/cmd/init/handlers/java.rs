@@ -1,6 +1,6 @@
 use crate::cli::cmd::init::{project::Project, prompt::Prompt};
 
-use super::{Flake, Handler};
+use super::{Flake, Handler, LanguageDefaults};
 
 const JAVA_VERSIONS: &[&str] = &["19", "18", "17", "16", "15"];
 
@@ -22,3 +22,11 @@ impl Handler for Java {
         }
     }
 }
+
+impl LanguageDefaults for Java {
+    fn add_defaults(flake: &mut Flake) {
+        flake
+            .dev_shell_packages
+            .push(format!("jdk{}", JAVA_VERSIONS[0]));
+    }
+}
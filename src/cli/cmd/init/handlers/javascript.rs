@@ -1,6 +1,6 @@
 use crate::cli::cmd::init::{project::Project, prompt::Prompt};
 
-use super::{Flake, Handler};
+use super::{bun::BUN_FILES, deno::DENO_FILES, Flake, Handler, LanguageDefaults};
 
 const NODE_VERSIONS: &[&str] = &["22", "20", "18"];
 
@@ -8,21 +8,20 @@ pub(crate) struct JavaScript;
 
 impl Handler for JavaScript {
     fn handle(project: &Project, flake: &mut Flake) {
-        if project.has_one_of(&["deno.json", "deno.jsonc"]) && Prompt::for_tool("Deno") {
-            flake.dev_shell_packages.push(String::from("deno"));
+        // Deno and Bun projects get their own runtimes instead of Node.js
+        if project.has_one_of(DENO_FILES) || project.has_one_of(BUN_FILES) {
+            return;
         }
 
         if project.has_file("package.json") && Prompt::for_language("JavaScript/TypeScript") {
-            if project.has_one_of(&["bunfig.toml", "bun.lockb"])
-                && Prompt::bool(
-                    "This seems to be a Bun project. Would you like to add it to your environment?",
-                )
-            {
-                flake.dev_shell_packages.push(String::from("bun"));
-            }
-
             if Prompt::bool("Is this a Node.js project?") {
-                let version = Prompt::select("Select a version of Node.js", NODE_VERSIONS);
+                // The version the project pins, if any, is offered first
+                let mut versions = NODE_VERSIONS.to_vec();
+                if let Some(pinned) = pinned_node_version(project) {
+                    versions.sort_by_key(|version| *version != pinned);
+                }
+
+                let version = Prompt::select("Select a version of Node.js", &versions);
                 flake.dev_shell_packages.push(format!("nodejs_{version}"));
             }
 
@@ -40,3 +39,60 @@ impl Handler for JavaScript {
         }
     }
 }
+
+// The major version of Node.js that the project asks for in `.nvmrc`, `.node-version`, or the
+// `engines.node` field of its package.json.
+fn pinned_node_version(project: &Project) -> Option<String> {
+    let engines_node = || {
+        let package: serde_json::Value =
+            serde_json::from_str(&project.read_file("package.json")?).ok()?;
+        package["engines"]["node"].as_str().map(String::from)
+    };
+
+    [".nvmrc", ".node-version"]
+        .iter()
+        .filter_map(|file| project.read_file(file))
+        .chain(engines_node())
+        .find_map(|spec| node_major_version(&spec))
+}
+
+// The first major version in a version or range, like `20` in `v20.11.0` or `>=18 <21`. Aliases like
+// `lts/iron` don't name one.
+fn node_major_version(spec: &str) -> Option<String> {
+    let start = spec.find(|c: char| c.is_ascii_digit())?;
+    let major: String = spec[start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+
+    Some(major)
+}
+
+impl LanguageDefaults for JavaScript {
+    fn add_defaults(flake: &mut Flake) {
+        flake
+            .dev_shell_packages
+            .push(format!("nodejs_{}", NODE_VERSIONS[0]));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn node_major_version() {
+        for (spec, major) in [
+            ("20\n", Some("20")),
+            ("v18.19.0", Some("18")),
+            (">=22.0.0", Some("22")),
+            ("^20 || ^22", Some("20")),
+            ("lts/iron", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                super::node_major_version(spec).as_deref(),
+                major,
+                "{spec:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+pub(crate) struct Kotlin;
+
+impl Handler for Kotlin {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if project.has_one_of(&["build.gradle.kts", "settings.gradle.kts"])
+            && Prompt::for_language("Kotlin")
+        {
+            Self::add_defaults(flake);
+
+            if Prompt::for_tool("Gradle") {
+                flake.dev_shell_packages.push(String::from("gradle"));
+            }
+        }
+    }
+}
+
+impl LanguageDefaults for Kotlin {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("kotlin"));
+    }
+}
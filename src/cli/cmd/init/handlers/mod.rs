@@ -1,35 +1,55 @@
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub(crate) mod bun;
+pub(crate) mod cpp;
+pub(crate) mod deno;
 pub(crate) mod elixir;
 pub(crate) mod elm;
 pub(crate) mod go;
+pub(crate) mod haskell;
 pub(crate) mod java;
 pub(crate) mod javascript;
+pub(crate) mod kotlin;
+pub(crate) mod ocaml;
 pub(crate) mod php;
 pub(crate) mod python;
 pub(crate) mod ruby;
 pub(crate) mod rust;
+pub(crate) mod scala;
+pub(crate) mod swift;
 pub(crate) mod system;
+pub(crate) mod terraform;
 pub(crate) mod tools;
 pub(crate) mod zig;
 
+pub(crate) use bun::Bun;
+pub(crate) use cpp::Cpp;
+pub(crate) use deno::Deno;
 pub(crate) use elixir::Elixir;
 pub(crate) use elm::Elm;
 pub(crate) use go::Go;
+pub(crate) use haskell::Haskell;
 pub(crate) use java::Java;
 pub(crate) use javascript::JavaScript;
+pub(crate) use kotlin::Kotlin;
+pub(crate) use ocaml::Ocaml;
 pub(crate) use php::Php;
 pub(crate) use python::Python;
 pub(crate) use ruby::Ruby;
 pub(crate) use rust::Rust;
+pub(crate) use scala::Scala;
+pub(crate) use swift::Swift;
 pub(crate) use system::System;
+pub(crate) use terraform::Terraform;
 pub(crate) use tools::Tools;
 pub(crate) use zig::Zig;
 
 use super::{dev_shell::DevShell, project::Project};
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct Input {
     pub(crate) reference: String,
     pub(crate) follows: Option<String>,
@@ -53,6 +73,7 @@ pub(crate) struct Flake {
     pub(crate) overlay_refs: Vec<String>,
     pub(crate) overlay_attrs: HashMap<String, String>,
     pub(crate) dev_shell_packages: Vec<String>,
+    pub(crate) unfree_packages: Vec<String>,
     pub(crate) env_vars: HashMap<String, String>,
     pub(crate) shell_hook: Option<String>,
     pub(crate) doc_comments: bool,
@@ -62,6 +83,12 @@ pub(crate) trait Handler {
     fn handle(project: &Project, flake: &mut Flake);
 }
 
+/// What a language adds to the flake when it's chosen without any prompts, like from an `fh init`
+/// spec file: its most recent toolchain, and none of the optional extras.
+pub(crate) trait LanguageDefaults {
+    fn add_defaults(flake: &mut Flake);
+}
+
 // Helper functions
 fn version_as_attr(v: &str, substring: &str) -> String {
     v.replace('.', substring)
@@ -0,0 +1,32 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+pub(crate) struct Ocaml;
+
+impl Handler for Ocaml {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if (project.has_one_of(&["dune-project", "dune"])
+            || project.has_file_with_extension("opam"))
+            && Prompt::for_language("OCaml")
+        {
+            Self::add_defaults(flake);
+
+            if Prompt::bool(
+                "Would you like to add the OCaml language server to your environment for editor support?",
+            ) {
+                flake
+                    .dev_shell_packages
+                    .push(String::from("ocamlPackages.ocaml-lsp"));
+            }
+        }
+    }
+}
+
+impl LanguageDefaults for Ocaml {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("ocaml"));
+        flake.dev_shell_packages.push(String::from("dune_3"));
+        flake.dev_shell_packages.push(String::from("opam"));
+    }
+}
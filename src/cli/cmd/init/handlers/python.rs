@@ -1,6 +1,6 @@
 use crate::cli::cmd::init::{project::Project, prompt::Prompt};
 
-use super::{version_as_attr_default, Flake, Handler};
+use super::{version_as_attr_default, Flake, Handler, LanguageDefaults};
 
 const PYTHON_VERSIONS: &[&str] = &["3.11", "3.10", "3.9"];
 const PYTHON_TOOLS: &[&str] = &["pip", "virtualenv", "pipenv"];
@@ -29,3 +29,12 @@ impl Handler for Python {
         }
     }
 }
+
+impl LanguageDefaults for Python {
+    fn add_defaults(flake: &mut Flake) {
+        let python_version_attr = version_as_attr_default(PYTHON_VERSIONS[0]);
+        flake
+            .dev_shell_packages
+            .push(format!("python{python_version_attr}"));
+    }
+}
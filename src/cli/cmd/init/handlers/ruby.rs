@@ -1,6 +1,6 @@
 use crate::cli::cmd::init::{project::Project, prompt::Prompt};
 
-use super::{version_as_attr, Flake, Handler};
+use super::{version_as_attr, Flake, Handler, LanguageDefaults};
 
 const RUBY_VERSIONS: &[&str] = &["3.2", "3.1"];
 
@@ -18,3 +18,12 @@ impl Handler for Ruby {
         }
     }
 }
+
+impl LanguageDefaults for Ruby {
+    fn add_defaults(flake: &mut Flake) {
+        let ruby_version_attr = version_as_attr(RUBY_VERSIONS[0], "_");
+        flake
+            .dev_shell_packages
+            .push(format!("ruby_{ruby_version_attr}"));
+    }
+}
@@ -3,7 +3,7 @@ use crate::{
     flakehub_url,
 };
 
-use super::{Flake, Handler, Input, Project};
+use super::{Flake, Handler, Input, LanguageDefaults, Project};
 
 const CARGO_TOOLS: &[&str] = &["bloat", "edit", "outdated", "udeps", "watch"];
 
@@ -12,18 +12,7 @@ pub(crate) struct Rust;
 impl Handler for Rust {
     fn handle(project: &Project, flake: &mut Flake) {
         if project.has_file("Cargo.toml") && Prompt::for_language("Rust") {
-            flake.inputs.insert(
-                String::from("rust-overlay"),
-                Input::new(
-                    flakehub_url!(FLAKEHUB_WEB_ROOT, "f", "oxalica", "rust-overlay", "0.1.*")
-                        .as_str(),
-                    Some("nixpkgs"),
-                ),
-            );
-
-            flake
-                .overlay_refs
-                .push(String::from("rust-overlay.overlays.default"));
+            add_rust_overlay(flake);
 
             let rust_toolchain_func = String::from(if project.has_file("rust-toolchain") {
                 "(final.rust-bin.fromRustupToolchainFile ./rust-toolchain)"
@@ -89,3 +78,29 @@ impl Handler for Rust {
         }
     }
 }
+
+impl LanguageDefaults for Rust {
+    fn add_defaults(flake: &mut Flake) {
+        add_rust_overlay(flake);
+
+        flake.overlay_attrs.insert(
+            String::from("rustToolchain"),
+            String::from("final.rust-bin.stable.latest.default"),
+        );
+        flake.dev_shell_packages.push(String::from("rustToolchain"));
+    }
+}
+
+fn add_rust_overlay(flake: &mut Flake) {
+    flake.inputs.insert(
+        String::from("rust-overlay"),
+        Input::new(
+            flakehub_url!(FLAKEHUB_WEB_ROOT, "f", "oxalica", "rust-overlay", "0.1.*").as_str(),
+            Some("nixpkgs"),
+        ),
+    );
+
+    flake
+        .overlay_refs
+        .push(String::from("rust-overlay.overlays.default"));
+}
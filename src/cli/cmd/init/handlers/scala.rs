@@ -0,0 +1,40 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+pub(crate) struct Scala;
+
+impl Handler for Scala {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if (project.has_one_of(&["build.sbt", "build.sc"])
+            || project.has_file_with_extension("scala"))
+            && Prompt::for_language("Scala")
+        {
+            Self::add_defaults(flake);
+
+            if project.has_file("build.sbt") && Prompt::for_tool("sbt") {
+                flake.dev_shell_packages.push(String::from("sbt"));
+            }
+
+            if project.has_file("build.sc") && Prompt::for_tool("Mill") {
+                flake.dev_shell_packages.push(String::from("mill"));
+            }
+
+            if Prompt::bool("Would you like to add Scala CLI to your environment?") {
+                flake.dev_shell_packages.push(String::from("scala-cli"));
+            }
+
+            if Prompt::bool(
+                "Would you like to add the Metals language server to your environment for editor support?",
+            ) {
+                flake.dev_shell_packages.push(String::from("metals"));
+            }
+        }
+    }
+}
+
+impl LanguageDefaults for Scala {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("scala"));
+    }
+}
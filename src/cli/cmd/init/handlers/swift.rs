@@ -0,0 +1,26 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+pub(crate) struct Swift;
+
+impl Handler for Swift {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if project.has_file("Package.swift") && Prompt::for_language("Swift") {
+            Self::add_defaults(flake);
+
+            if Prompt::bool(
+                "Would you like to add the SourceKit language server to your environment for editor support?",
+            ) {
+                flake.dev_shell_packages.push(String::from("sourcekit-lsp"));
+            }
+        }
+    }
+}
+
+impl LanguageDefaults for Swift {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("swift"));
+        flake.dev_shell_packages.push(String::from("swiftpm"));
+    }
+}
@@ -5,64 +5,66 @@ use crate::cli::cmd::init::{
 
 use super::{Flake, Handler};
 
-const SYSTEMS: &[MultiSelectOption] = &[
-    MultiSelectOption(
+// The systems to choose from, with descriptions
+const SYSTEMS: &[(&str, &str)] = &[
+    (
         "x86_64-linux",
         "Linux on a 64-bit x86 processor, like Intel or AMD",
-        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-        {
-            true
-        },
-        #[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
-        {
-            false
-        },
     ),
-    MultiSelectOption(
+    (
         "aarch64-darwin",
         "macOS on Apple Silicon, like the M1 or M2 chips",
-        #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-        {
-            true
-        },
-        #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
-        {
-            false
-        },
-    ),
-    MultiSelectOption(
-        "x86_64-darwin",
-        "macOS on Intel CPUs",
-        #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-        {
-            true
-        },
-        #[cfg(not(all(target_os = "macos", target_arch = "x86_64")))]
-        {
-            false
-        },
-    ),
-    MultiSelectOption(
-        "aarch64-linux",
-        "Linux on a 64-bit Arm processor",
-        #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
-        {
-            true
-        },
-        #[cfg(not(all(target_os = "linux", target_arch = "aarch64")))]
-        {
-            false
-        },
     ),
+    ("x86_64-darwin", "macOS on Intel CPUs"),
+    ("aarch64-linux", "Linux on a 64-bit Arm processor"),
 ];
 
 pub(crate) struct System;
 
+// The Nix system for a Rust target's architecture and OS, as in `std::env::consts`, if it's one of
+// the supported systems.
+fn nix_system(arch: &str, os: &str) -> Option<&'static str> {
+    let os = match os {
+        "macos" => "darwin",
+        os => os,
+    };
+
+    SYSTEMS
+        .iter()
+        .map(|(system, _)| *system)
+        .find(|system| *system == format!("{arch}-{os}"))
+}
+
+/// Parse one of the supported systems, for `fh init --systems`.
+pub(crate) fn parse_system(system: &str) -> Result<String, String> {
+    if SYSTEMS.iter().any(|(supported, _)| *supported == system) {
+        Ok(system.to_string())
+    } else {
+        Err(format!(
+            "unknown system `{system}`; expected one of {}",
+            SYSTEMS
+                .iter()
+                .map(|(supported, _)| format!("`{supported}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
+
 fn get_systems() -> Vec<String> {
+    // The system fh is running on is selected to begin with
+    let current = nix_system(std::env::consts::ARCH, std::env::consts::OS);
+    let options = SYSTEMS
+        .iter()
+        .map(|&(system, description)| {
+            MultiSelectOption(system, description, Some(system) == current)
+        })
+        .collect();
+
     let selected = Prompt::guided_multi_select(
         "Which systems would you like to support?",
         "system",
-        SYSTEMS.to_vec(),
+        options,
     );
 
     if selected.is_empty() {
@@ -76,7 +78,37 @@ fn get_systems() -> Vec<String> {
 
 impl Handler for System {
     fn handle(_: &Project, flake: &mut Flake) {
-        let systems = get_systems();
-        flake.systems = systems;
+        // Systems given with `--systems` are used as they are
+        if flake.systems.is_empty() {
+            flake.systems = get_systems();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn nix_system() {
+        for (arch, os, system) in [
+            ("x86_64", "linux", Some("x86_64-linux")),
+            ("aarch64", "linux", Some("aarch64-linux")),
+            ("x86_64", "macos", Some("x86_64-darwin")),
+            ("aarch64", "macos", Some("aarch64-darwin")),
+            ("riscv64", "linux", None),
+            ("x86_64", "windows", None),
+        ] {
+            assert_eq!(super::nix_system(arch, os), system, "{arch}-{os}");
+        }
+    }
+
+    #[test]
+    fn parse_system() {
+        assert_eq!(
+            super::parse_system("aarch64-darwin").as_deref(),
+            Ok("aarch64-darwin")
+        );
+
+        let err = super::parse_system("x86_64-windows").unwrap_err();
+        assert!(err.starts_with("unknown system `x86_64-windows`; expected one of `x86_64-linux`"));
     }
 }
@@ -0,0 +1,45 @@
+use crate::cli::cmd::init::prompt::Prompt;
+
+use super::{Flake, Handler, LanguageDefaults, Project};
+
+// Terraform's license isn't free, so Nixpkgs only builds it with `allowUnfree`; OpenTofu is the
+// open source fork and comes first
+const TERRAFORM_DISTRIBUTIONS: &[&str] = &["OpenTofu", "Terraform"];
+
+pub(crate) struct Terraform;
+
+impl Handler for Terraform {
+    fn handle(project: &Project, flake: &mut Flake) {
+        if (project.has_file_with_extension("tf")
+            || project.has_one_of(&[".terraform.lock.hcl", "terragrunt.hcl"]))
+            && Prompt::for_tool("Terraform")
+        {
+            let distribution = Prompt::select(
+                "Which distribution would you like? Terraform requires allowing unfree packages in Nixpkgs.",
+                TERRAFORM_DISTRIBUTIONS,
+            );
+            let package = distribution.to_lowercase();
+            if distribution == "Terraform" {
+                flake.unfree_packages.push(package.clone());
+            }
+            flake.dev_shell_packages.push(package);
+
+            if Prompt::bool(
+                "Would you like to add the Terraform language server to your environment for editor support?",
+            ) {
+                flake.dev_shell_packages.push(String::from("terraform-ls"));
+            }
+
+            if Prompt::for_tool("TFLint") {
+                flake.dev_shell_packages.push(String::from("tflint"));
+            }
+        }
+    }
+}
+
+impl LanguageDefaults for Terraform {
+    // OpenTofu, which doesn't need any unfree packages to be allowed
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("opentofu"));
+    }
+}
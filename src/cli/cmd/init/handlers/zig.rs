@@ -1,13 +1,19 @@
 use crate::cli::cmd::init::{project::Project, prompt::Prompt};
 
-use super::{Flake, Handler};
+use super::{Flake, Handler, LanguageDefaults};
 
 pub(crate) struct Zig;
 
 impl Handler for Zig {
     fn handle(project: &Project, flake: &mut Flake) {
         if project.has_file("build.zig") && Prompt::for_language("Zig") {
-            flake.dev_shell_packages.push(String::from("zig"));
+            Self::add_defaults(flake);
         }
     }
 }
+
+impl LanguageDefaults for Zig {
+    fn add_defaults(flake: &mut Flake) {
+        flake.dev_shell_packages.push(String::from("zig"));
+    }
+}
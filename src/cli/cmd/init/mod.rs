@@ -1,3 +1,4 @@
+pub(crate) mod config;
 pub(crate) mod dev_shell;
 pub(crate) mod handlers;
 pub(crate) mod project;
@@ -5,12 +6,12 @@ pub(crate) mod prompt;
 pub(crate) mod template;
 
 use clap::Parser;
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Result, WrapErr};
 use prompt::Prompt;
 use std::{
     fs::write,
     io::IsTerminal,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{exit, Command, ExitCode},
 };
 use url::Url;
@@ -20,19 +21,21 @@ use crate::{
         cmd::{init::handlers::Elm, list::FLAKEHUB_WEB_ROOT},
         error::FhError,
     },
-    flakehub_url,
+    flakehub_url, info_println,
 };
 
-use super::FlakeHubClient;
+use super::{
+    local_flake_dir, nix_command, nix_process, FlakeHubClient, FlakeOutputRef, SudoChoice,
+};
 
 use self::{
     dev_shell::DevShell,
     handlers::{
-        Elixir, Flake, Go, Handler, Input, Java, JavaScript, Php, Python, Ruby, Rust, System,
-        Tools, Zig,
+        Bun, Cpp, Deno, Elixir, Flake, Go, Handler, Haskell, Input, Java, JavaScript, Kotlin,
+        Ocaml, Php, Python, Ruby, Rust, Scala, Swift, System, Terraform, Tools, Zig,
     },
     project::Project,
-    template::TemplateData,
+    template::{FlakeStyle, TemplateData},
 };
 
 use super::CommandExecute;
@@ -52,28 +55,98 @@ pub(crate) struct InitSubcommand {
     #[clap(long, short, default_value = "./flake.nix")]
     output: PathBuf,
 
+    /// The structure of the generated flake.
+    #[clap(long, value_enum, default_value_t = FlakeStyle::Default)]
+    style: FlakeStyle,
+
+    /// The systems the flake supports, separated by commas, like
+    /// `x86_64-linux,aarch64-darwin`. Skips the prompt for them.
+    #[clap(long, value_delimiter = ',', value_parser = handlers::system::parse_system, conflicts_with = "from_spec")]
+    systems: Vec<String>,
+
+    /// Print a JSON Schema describing the `fh init` configuration and exit.
+    #[clap(long)]
+    print_schema: bool,
+
+    /// Create the flake from a JSON or TOML file (by its `.toml` extension) with the choices that
+    /// would otherwise be prompted for, without any prompts. See `--print-schema` for the file's
+    /// format.
+    #[clap(long, value_name = "PATH")]
+    from_spec: Option<PathBuf>,
+
+    /// Also write the choices made at the prompts to this path as JSON, so that they can be
+    /// replayed with `--from-spec`.
+    #[clap(long, value_name = "PATH")]
+    dump_spec: Option<PathBuf>,
+
+    /// Scaffold the project from a template on FlakeHub instead, like `omnicorp/templates#rust`
+    /// or `omnicorp/templates/0.1#rust`, without any prompts. The name after `#` is looked up
+    /// under the flake's `templates`.
+    #[clap(
+        long,
+        value_name = "TEMPLATE_REF",
+        conflicts_with_all = ["from_spec", "dump_spec", "systems", "lock"]
+    )]
+    template: Option<String>,
+
+    /// Run `nix flake lock` once the flake is written, without asking first.
+    #[clap(long)]
+    lock: bool,
+
     #[clap(from_global)]
     api_addr: url::Url,
+
+    #[clap(from_global)]
+    frontend_addr: url::Url,
+
+    #[clap(from_global)]
+    print_commands: bool,
 }
 
 #[async_trait::async_trait]
 impl CommandExecute for InitSubcommand {
     async fn execute(self) -> Result<ExitCode> {
+        if self.print_schema {
+            println!("{}", config::schema()?);
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        if let Some(template) = &self.template {
+            return self.init_from_template(template).await;
+        }
+
+        if let Some(spec_path) = &self.from_spec {
+            return self.init_from_spec(spec_path).await;
+        }
+
         if !std::io::stdout().is_terminal() {
             println!("fh init can only be used in a terminal; exiting");
             exit(1);
         } else {
-            let mut flake = Flake::default();
+            let mut flake = Flake {
+                systems: self.systems.clone(),
+                ..Default::default()
+            };
 
             if self.output.exists() && !Prompt::bool("A flake.nix already exists in the current directory. Would you like to overwrite it?") {
                 println!("Exiting. Let's a build a new flake soon, though :)");
                 return Ok(ExitCode::SUCCESS);
             }
 
+            // The flake being overwritten's description is offered again
+            let existing_description = if self.output.exists() {
+                existing_description(&self.output).await
+            } else {
+                None
+            };
+
             println!("Let's build a Nix flake!");
 
-            let project = Project::new(self.root);
-            flake.description = Prompt::maybe_string("An optional description for your flake:");
+            let project = Project::new(self.root.clone());
+            flake.description = Prompt::maybe_string_with_initial(
+                "An optional description for your flake:",
+                existing_description.as_deref(),
+            );
 
             // Supported systems
             System::handle(&project, &mut flake);
@@ -110,34 +183,30 @@ impl CommandExecute for InitSubcommand {
                 Input::new(nixpkgs_version.as_ref(), None),
             );
 
-            flake.inputs.insert(
-                String::from("flake-schemas"),
-                Input::new(
-                    flakehub_url!(
-                        FLAKEHUB_WEB_ROOT,
-                        "f",
-                        "DeterminateSystems",
-                        "flake-schemas",
-                        "*"
-                    )
-                    .as_str(),
-                    None,
-                ),
-            );
+            add_standard_inputs(&mut flake, self.style);
 
             // Languages
+            Bun::handle(&project, &mut flake);
+            Cpp::handle(&project, &mut flake);
+            Deno::handle(&project, &mut flake);
             Elixir::handle(&project, &mut flake);
             Elm::handle(&project, &mut flake);
             Go::handle(&project, &mut flake);
+            Haskell::handle(&project, &mut flake);
             Java::handle(&project, &mut flake);
             JavaScript::handle(&project, &mut flake);
+            Kotlin::handle(&project, &mut flake);
+            Ocaml::handle(&project, &mut flake);
             Php::handle(&project, &mut flake);
             Python::handle(&project, &mut flake);
             Ruby::handle(&project, &mut flake);
             Rust::handle(&project, &mut flake);
+            Scala::handle(&project, &mut flake);
+            Swift::handle(&project, &mut flake);
             Zig::handle(&project, &mut flake);
 
             // Other tools
+            Terraform::handle(&project, &mut flake);
             Tools::handle(&project, &mut flake);
 
             // Nix formatter
@@ -147,13 +216,51 @@ impl CommandExecute for InitSubcommand {
                 flake.dev_shell_packages.push(String::from("nixpkgs-fmt"));
             }
 
+            if Prompt::bool(
+                "Would you like to add any other packages from Nixpkgs to your environment?",
+            ) {
+                // Checking packages means evaluating Nixpkgs, which we can only do with Nix around
+                let validate = command_exists("nix");
+
+                while let Some(package) = Prompt::maybe_string(
+                    "Package attribute name (like `jq` or `python3Packages.black`), or nothing to finish:",
+                ) {
+                    if !is_valid_attr_path(&package) {
+                        println!("`{package}` isn't a valid Nixpkgs attribute name");
+                        continue;
+                    }
+
+                    if validate
+                        && nixpkgs_has_package(&nixpkgs_version, &package).await == Some(false)
+                        && !Prompt::bool(&format!(
+                            "`{package}` wasn't found in the Nixpkgs you selected. Add it anyway?"
+                        ))
+                    {
+                        continue;
+                    }
+
+                    flake.dev_shell_packages.push(package);
+                }
+            }
+
             flake.doc_comments = Prompt::bool("Would you like to add doc comments to your flake that explain the meaning of different aspects of the flake?");
 
             if Prompt::bool("Would you like to add any environment variables?") {
                 loop {
                     let name = Prompt::maybe_string("Variable name:");
                     if let Some(name) = name {
-                        let value = Prompt::maybe_string("Variable value:");
+                        // An unclosed `${` breaks the whole flake, so give a chance to fix it
+                        let value = loop {
+                            let value = Prompt::maybe_string("Variable value:");
+                            if let Some(v) =
+                                value.as_deref().filter(|v| has_unclosed_interpolation(v))
+                            {
+                                if !Prompt::bool(&format!("`{v}` has a `${{` without a closing `}}`, so the flake won't evaluate. Would you like to use it anyway?")) {
+                                    continue;
+                                }
+                            }
+                            break value;
+                        };
                         if let Some(value) = value {
                             flake.env_vars.insert(name, value);
                             if !Prompt::bool("Enter another variable?") {
@@ -191,53 +298,20 @@ impl CommandExecute for InitSubcommand {
                 return Ok(ExitCode::SUCCESS);
             }
 
-            flake.dev_shells.insert(
-                String::from("default"),
-                DevShell {
-                    packages: flake.dev_shell_packages,
-                    env_vars: flake.env_vars,
-                },
-            );
-
             let use_flake_compat = Prompt::bool(
                 "Would you like to support legacy Nix commands like `nix-build` and `nix-shell`?",
             );
 
-            if use_flake_compat {
-                flake.inputs.insert(
-                    String::from("flake-compat"),
-                    Input::new(
-                        flakehub_url!(FLAKEHUB_WEB_ROOT, "f", "edolstra", "flake-compat", "*")
-                            .as_str(),
-                        None,
-                    ),
-                );
-                write(
-                    PathBuf::from("default.nix"),
-                    String::from(include_str!("../../../../assets/default.nix")),
-                )?;
+            if let Some(dump_spec) = &self.dump_spec {
+                let spec = config::InitConfig::from_flake(&flake, use_flake_compat);
                 write(
-                    PathBuf::from("shell.nix"),
-                    String::from(include_str!("../../../../assets/shell.nix")),
-                )?;
+                    dump_spec,
+                    format!("{}\n", serde_json::to_string_pretty(&spec)?),
+                )
+                .wrap_err_with(|| format!("Writing {}", dump_spec.display()))?;
             }
 
-            let data = TemplateData {
-                description: flake.description,
-                inputs: flake.inputs,
-                systems: flake.systems,
-                dev_shells: flake.dev_shells,
-                overlay_refs: flake.overlay_refs.clone(),
-                overlay_attrs: flake.overlay_attrs.clone(),
-                has_overlays: flake.overlay_refs.len() + flake.overlay_attrs.keys().len() > 0,
-                fh_version: env!("CARGO_PKG_VERSION").to_string(),
-                doc_comments: flake.doc_comments,
-                shell_hook: flake.shell_hook,
-            };
-
-            let flake_string = data.render()?;
-
-            write(self.output, flake_string)?;
+            write_flake(flake, self.style, use_flake_compat, &self.output)?;
 
             if project.has_directory(".git")
                 && command_exists("git")
@@ -257,6 +331,14 @@ impl CommandExecute for InitSubcommand {
                 }
             }
 
+            if self.lock
+                || Prompt::bool(
+                    "Would you like to run `nix flake lock` now, so that the flake is ready to build?",
+                )
+            {
+                self.lock_flake().await;
+            }
+
             if !project.has_file(".envrc")
                 && Prompt::bool("Would you like to add a .envrc file so that you can use direnv in this project?")
             {
@@ -271,7 +353,7 @@ impl CommandExecute for InitSubcommand {
                 }
             }
 
-            println!(
+            info_println!(
                 "Your flake is ready to go! Run `nix flake show` to see which outputs it provides."
             );
 
@@ -280,12 +362,340 @@ impl CommandExecute for InitSubcommand {
     }
 }
 
+impl InitSubcommand {
+    async fn init_from_template(&self, template: &str) -> Result<ExitCode> {
+        let output_ref = template_output_ref(&self.frontend_addr, template)?;
+
+        // Make sure that the template exists before handing it to Nix
+        FlakeHubClient::resolve(self.api_addr.as_ref(), &output_ref, false)
+            .await
+            .map_err(|e| match e {
+                FhError::NotFound(..) => FhError::NotATemplate(output_ref.to_string()),
+                e => e,
+            })?;
+
+        let supports_bare_tarball_urls = crate::shared::nix_info::nix_info()
+            .await
+            .is_some_and(|info| info.supports_bare_tarball_urls());
+        let template_url =
+            template_url(&self.frontend_addr, &output_ref, supports_bare_tarball_urls);
+
+        let mut cmd = nix_process("nix")?;
+        cmd.args(["--extra-experimental-features", "nix-command flakes"])
+            .args(["flake", "init", "--template", template_url.as_str()])
+            .current_dir(&self.root);
+        if self.print_commands {
+            eprintln!("{:?}", cmd.as_std());
+        }
+
+        let status = cmd.status().await.wrap_err("failed to spawn Nix command")?;
+        if !status.success() {
+            return Err(FhError::FailedNixCommand(
+                format!("`nix flake init` exited with {status}"),
+                None,
+            )
+            .into());
+        }
+
+        info_println!(
+            "Your project is ready to go! Run `nix flake show` to see which outputs it provides."
+        );
+
+        Ok(ExitCode::SUCCESS)
+    }
+
+    // Locks the new flake's inputs. The flake is already written by then, so failing to lock it is
+    // reported rather than treated as an error.
+    async fn lock_flake(&self) {
+        if !command_exists("nix") {
+            info_println!("It looks like Nix isn't installed. Skipping `nix flake lock`.");
+            return;
+        }
+
+        let result = nix_command(
+            &[
+                String::from("flake"),
+                String::from("lock"),
+                local_flake_dir(&self.output),
+            ],
+            SudoChoice::Never,
+            self.print_commands,
+        )
+        .await;
+
+        match result {
+            Ok(()) => info_println!("Locked the flake's inputs in flake.lock."),
+            Err(e) => info_println!(
+                "Couldn't lock the flake's inputs ({e}). Run `nix flake lock` to try again."
+            ),
+        }
+    }
+
+    async fn init_from_spec(&self, spec_path: &Path) -> Result<ExitCode> {
+        let contents = std::fs::read_to_string(spec_path)
+            .wrap_err_with(|| format!("Reading {}", spec_path.display()))?;
+        let config = config::from_spec(spec_path, &contents)?;
+        let use_flake_compat = config.flake_compat;
+
+        // There's nobody to ask whether it's okay to overwrite the existing flake
+        if self.output.exists() {
+            return Err(color_eyre::eyre::eyre!(
+                "{} already exists; remove it or choose another path with `--output`",
+                self.output.display()
+            ));
+        }
+
+        let mut flake = config.into_flake()?;
+        add_standard_inputs(&mut flake, self.style);
+
+        if flake.dev_shell_packages.is_empty() {
+            return Err(FhError::InvalidInitConfig(String::from(
+                "the development environment doesn't have any packages in it",
+            ))
+            .into());
+        }
+
+        write_flake(flake, self.style, use_flake_compat, &self.output)?;
+
+        if self.lock {
+            self.lock_flake().await;
+        }
+
+        info_println!(
+            "Your flake is ready to go! Run `nix flake show` to see which outputs it provides."
+        );
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+// The output for `--template`, which may leave out the version (for the latest release) and the
+// `templates.` at the start of the attribute path.
+fn template_output_ref(frontend_addr: &Url, template: &str) -> Result<FlakeOutputRef, FhError> {
+    let template = template
+        .strip_prefix(frontend_addr.join("f/")?.as_str())
+        .unwrap_or(template);
+    let (release, attr_path) = template.split_once('#').ok_or_else(|| {
+        FhError::MissingFromOutputRef(String::from("the template's name after `#`"))
+    })?;
+
+    let release = match release.split('/').count() {
+        2 => format!("{release}/*"),
+        _ => release.to_string(),
+    };
+    let attr_path = if attr_path.starts_with("templates.") {
+        attr_path.to_string()
+    } else {
+        format!("templates.{attr_path}")
+    };
+
+    FlakeOutputRef::try_from(format!("{release}#{attr_path}"))
+}
+
+// The URL that `nix flake init` gets the template from. Like the URLs of FlakeHub inputs, it needs a
+// `.tar.gz` suffix unless Nix recognizes FlakeHub tarballs without one.
+fn template_url(
+    frontend_addr: &Url,
+    output_ref: &FlakeOutputRef,
+    supports_bare_tarball_urls: bool,
+) -> Url {
+    let version = if supports_bare_tarball_urls {
+        output_ref.version_constraint.clone()
+    } else {
+        format!("{}.tar.gz", output_ref.version_constraint)
+    };
+
+    let mut template_url = flakehub_url!(
+        frontend_addr.as_str(),
+        "f",
+        &output_ref.org,
+        &output_ref.project,
+        &version
+    );
+    template_url.set_fragment(Some(&output_ref.attr_path));
+
+    template_url
+}
+
+// The inputs that every new flake gets, apart from Nixpkgs. Inputs the flake already has are kept.
+fn add_standard_inputs(flake: &mut Flake, style: FlakeStyle) {
+    flake
+        .inputs
+        .entry(String::from("flake-schemas"))
+        .or_insert_with(|| {
+            Input::new(
+                flakehub_url!(
+                    FLAKEHUB_WEB_ROOT,
+                    "f",
+                    "DeterminateSystems",
+                    "flake-schemas",
+                    "*"
+                )
+                .as_str(),
+                None,
+            )
+        });
+
+    if style == FlakeStyle::FlakeParts {
+        flake
+            .inputs
+            .entry(String::from("flake-parts"))
+            .or_insert_with(|| {
+                Input::new(
+                    flakehub_url!(FLAKEHUB_WEB_ROOT, "f", "hercules-ci", "flake-parts", "*")
+                        .as_str(),
+                    None,
+                )
+            });
+    }
+}
+
+// Renders the flake to `output`, along with the files for legacy Nix commands if they're wanted.
+fn write_flake(
+    mut flake: Flake,
+    style: FlakeStyle,
+    use_flake_compat: bool,
+    output: &Path,
+) -> Result<()> {
+    flake.dev_shells.insert(
+        String::from("default"),
+        DevShell {
+            packages: flake.dev_shell_packages,
+            env_vars: flake.env_vars,
+        },
+    );
+
+    if use_flake_compat {
+        flake.inputs.insert(
+            String::from("flake-compat"),
+            Input::new(
+                flakehub_url!(FLAKEHUB_WEB_ROOT, "f", "edolstra", "flake-compat", "*").as_str(),
+                None,
+            ),
+        );
+        write(
+            PathBuf::from("default.nix"),
+            String::from(include_str!("../../../../assets/default.nix")),
+        )?;
+        write(
+            PathBuf::from("shell.nix"),
+            String::from(include_str!("../../../../assets/shell.nix")),
+        )?;
+    }
+
+    let data = TemplateData {
+        description: flake.description,
+        inputs: flake.inputs,
+        systems: flake.systems,
+        dev_shells: flake.dev_shells,
+        overlay_refs: flake.overlay_refs.clone(),
+        overlay_attrs: flake.overlay_attrs.clone(),
+        has_overlays: flake.overlay_refs.len() + flake.overlay_attrs.keys().len() > 0,
+        imports_nixpkgs: flake.overlay_refs.len() + flake.overlay_attrs.keys().len() > 0
+            || !flake.unfree_packages.is_empty(),
+        unfree_packages: flake.unfree_packages,
+        fh_version: env!("CARGO_PKG_VERSION").to_string(),
+        doc_comments: flake.doc_comments,
+        shell_hook: flake.shell_hook,
+        style,
+    };
+
+    let flake_string = data.render()?;
+
+    write(output, flake_string)?;
+
+    Ok(())
+}
+
+// The top-level `description` of an existing flake.nix, if it has one that's a plain string.
+async fn existing_description(flake_path: &PathBuf) -> Option<String> {
+    let (_, parsed) = crate::cli::cmd::add::load_flake(flake_path, true)
+        .await
+        .ok()?;
+    flake_description(&parsed.expression)
+}
+
+fn flake_description(expr: &nixel::Expression) -> Option<String> {
+    let kv = crate::cli::cmd::add::flake::find_first_attrset_by_path(
+        expr,
+        Some(["description".into()].into()),
+    )
+    .ok()??;
+
+    match &*kv.to {
+        nixel::Expression::String(value) => match &value.parts[..] {
+            [nixel::Part::Raw(raw)] => Some(raw.content.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 pub(super) fn command_exists(cmd: &str) -> bool {
     Command::new(cmd).output().is_ok()
 }
 
+// Whether `package` is an attribute path like `jq` or `python3Packages.black`
+fn is_valid_attr_path(package: &str) -> bool {
+    package.split('.').all(|segment| {
+        let mut chars = segment.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || ['_', '-', '\''].contains(&c))
+    })
+}
+
+// Whether `value` has a Nix interpolation (`${...}`) that's never closed. Escaped interpolations,
+// like `\${HOME}`, are left alone.
+fn has_unclosed_interpolation(value: &str) -> bool {
+    let mut depth = 0usize;
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                depth += 1;
+            }
+            '{' if depth > 0 => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth > 0
+}
+
+// Whether `nixpkgs` provides `package` for the current system, or `None` if we couldn't tell (for
+// example, because Nixpkgs couldn't be fetched).
+async fn nixpkgs_has_package(nixpkgs: &Url, package: &str) -> Option<bool> {
+    let output = crate::cli::cmd::nix_process("nix")
+        .ok()?
+        .args(["eval", "--raw", &format!("{nixpkgs}#{package}.name")])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .ok()?;
+
+    if output.status.success() {
+        return Some(true);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("does not provide attribute") {
+        Some(false)
+    } else {
+        tracing::debug!("Couldn't check whether Nixpkgs has {package}: {stderr}");
+        None
+    }
+}
+
 async fn select_nixpkgs(api_addr: &str) -> Result<Url, FhError> {
-    let releases = FlakeHubClient::releases(api_addr, "NixOS", "nixpkgs").await?;
+    let releases = FlakeHubClient::releases(api_addr, "NixOS", "nixpkgs", None, None).await?;
     let releases: Vec<&str> = releases.iter().map(|r| r.version.as_str()).collect();
     let release = Prompt::select("Choose one of the following Nixpkgs releases:", &releases);
     let version = format!("{release}.tar.gz");
@@ -297,3 +707,108 @@ async fn select_nixpkgs(api_addr: &str) -> Result<Url, FhError> {
         &version
     ))
 }
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn template_output_ref() {
+        let frontend_addr = url::Url::parse("https://flakehub.com").unwrap();
+
+        for (template, output_ref) in [
+            (
+                "omnicorp/templates#rust",
+                "omnicorp/templates/*#templates.rust",
+            ),
+            (
+                "omnicorp/templates/0.1#templates.rust",
+                "omnicorp/templates/0.1#templates.rust",
+            ),
+            (
+                "https://flakehub.com/f/omnicorp/templates/0.1#go",
+                "omnicorp/templates/0.1#templates.go",
+            ),
+        ] {
+            assert_eq!(
+                super::template_output_ref(&frontend_addr, template)
+                    .unwrap()
+                    .to_string(),
+                output_ref
+            );
+        }
+
+        assert!(super::template_output_ref(&frontend_addr, "omnicorp/templates").is_err());
+    }
+
+    #[test]
+    fn template_url() {
+        let frontend_addr = url::Url::parse("https://flakehub.com").unwrap();
+        let output_ref =
+            super::template_output_ref(&frontend_addr, "omnicorp/templates/0.1#rust").unwrap();
+
+        assert_eq!(
+            super::template_url(&frontend_addr, &output_ref, false).as_str(),
+            "https://flakehub.com/f/omnicorp/templates/0.1.tar.gz#templates.rust"
+        );
+        assert_eq!(
+            super::template_url(&frontend_addr, &output_ref, true).as_str(),
+            "https://flakehub.com/f/omnicorp/templates/0.1#templates.rust"
+        );
+    }
+
+    #[test]
+    fn flake_description() {
+        let parsed = nixel::parse(String::from(
+            r#"{ description = "My flake"; inputs.nixpkgs.url = "github:NixOS/nixpkgs"; outputs = _: { }; }"#,
+        ));
+        assert_eq!(
+            super::flake_description(&parsed.expression).as_deref(),
+            Some("My flake")
+        );
+
+        // Descriptions that are computed can't be carried over
+        let parsed = nixel::parse(String::from(
+            r#"{ description = "${name} flake"; outputs = _: { }; }"#,
+        ));
+        assert_eq!(super::flake_description(&parsed.expression), None);
+
+        let parsed = nixel::parse(String::from("{ outputs = _: { }; }"));
+        assert_eq!(super::flake_description(&parsed.expression), None);
+    }
+
+    #[test]
+    fn unclosed_interpolations() {
+        for closed in [
+            "plain",
+            "${pkgs.foo}/bin",
+            "${pkgs.foo}/bin:${pkgs.bar}/bin",
+            "${builtins.toString { a = 1; }.a}",
+            "\\${HOME",
+            "}",
+        ] {
+            assert!(!super::has_unclosed_interpolation(closed), "{closed}");
+        }
+
+        for unclosed in ["${pkgs.foo/bin", "${pkgs.foo}/bin:${pkgs.bar", "${{}"] {
+            assert!(super::has_unclosed_interpolation(unclosed), "{unclosed}");
+        }
+    }
+
+    #[test]
+    fn valid_attr_paths() {
+        for valid in [
+            "jq",
+            "python3Packages.black",
+            "nodePackages_latest.npm",
+            "gtk3-x11",
+        ] {
+            assert!(super::is_valid_attr_path(valid), "{valid} should be valid");
+        }
+
+        for invalid in ["", "jq ", "pkgs..jq", "3d", "python3Packages.", "foo;bar"] {
+            assert!(
+                !super::is_valid_attr_path(invalid),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+}
@@ -21,4 +21,21 @@ impl Project {
     pub(crate) fn has_one_of(&self, files: &[&str]) -> bool {
         files.iter().any(|f| self.has_file(f))
     }
+
+    // The contents of a file in the root, if it exists and can be read
+    pub(crate) fn read_file(&self, file: &str) -> Option<String> {
+        std::fs::read_to_string(self.root.join(file)).ok()
+    }
+
+    // Whether any file directly in the root ends in `.{extension}`, like `.cabal`
+    pub(crate) fn has_file_with_extension(&self, extension: &str) -> bool {
+        let Ok(entries) = std::fs::read_dir(&self.root) else {
+            return false;
+        };
+
+        entries.flatten().any(|entry| {
+            let path = entry.path();
+            path.is_file() && path.extension().is_some_and(|ext| ext == extension)
+        })
+    }
 }
@@ -8,6 +8,17 @@ use crate::cli::error::FhError;
 
 use super::{dev_shell::DevShell, handlers::Input};
 
+/// The overall structure of the generated flake.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum FlakeStyle {
+    /// Produce system-specific outputs with a small `forEachSupportedSystem` helper
+    #[default]
+    Default,
+    /// Use flake-parts and its `perSystem` module
+    FlakeParts,
+}
+
 #[derive(Debug, Serialize)]
 pub(crate) struct TemplateData {
     pub(crate) description: Option<String>,
@@ -22,7 +33,11 @@ pub(crate) struct TemplateData {
     // either overlay_refs or overlay_attrs is non-empty, so we calculate that in Rust
     // and set a Boolean here instead
     pub(crate) has_overlays: bool,
+    pub(crate) unfree_packages: Vec<String>,
+    // Nixpkgs is imported by hand, rather than used as is, for overlays and unfree packages alike
+    pub(crate) imports_nixpkgs: bool,
     pub(crate) doc_comments: bool,
+    pub(crate) style: FlakeStyle,
 }
 
 impl TemplateData {
@@ -43,8 +58,13 @@ impl TemplateData {
 
         let mut handlebars = Handlebars::new();
 
+        let template = match self.style {
+            FlakeStyle::Default => include_str!("../../../../assets/flake.hbs"),
+            FlakeStyle::FlakeParts => include_str!("../../../../assets/flake-parts.hbs"),
+        };
+
         handlebars
-            .register_template_string("flake", include_str!("../../../../assets/flake.hbs"))
+            .register_template_string("flake", template)
             .map_err(|err| FhError::Template(Box::new(err)))?;
 
         handlebars
@@ -52,3 +72,81 @@ impl TemplateData {
             .map_err(FhError::Render)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{FlakeStyle, TemplateData};
+    use crate::cli::cmd::init::{dev_shell::DevShell, handlers::Input};
+
+    fn template_data(style: FlakeStyle) -> TemplateData {
+        TemplateData {
+            description: Some(String::from("A test flake")),
+            inputs: HashMap::from([
+                (
+                    String::from("nixpkgs"),
+                    Input::new("https://flakehub.com/f/NixOS/nixpkgs/*", None),
+                ),
+                (
+                    String::from("flake-parts"),
+                    Input::new("https://flakehub.com/f/hercules-ci/flake-parts/*", None),
+                ),
+            ]),
+            systems: vec![String::from("x86_64-linux")],
+            dev_shells: HashMap::from([(
+                String::from("default"),
+                DevShell {
+                    packages: vec![String::from("hello")],
+                    env_vars: HashMap::new(),
+                },
+            )]),
+            overlay_refs: vec![],
+            overlay_attrs: HashMap::new(),
+            shell_hook: None,
+            fh_version: String::from("0.0.0"),
+            has_overlays: false,
+            unfree_packages: vec![],
+            imports_nixpkgs: false,
+            doc_comments: false,
+            style,
+        }
+    }
+
+    #[test]
+    fn render_flake_parts_style() {
+        let flake = template_data(FlakeStyle::FlakeParts).render().unwrap();
+
+        assert!(flake.contains("flake-parts.lib.mkFlake { inherit inputs; }"));
+        assert!(flake.contains("perSystem = { pkgs, system, ... }:"));
+        assert!(flake.contains("systems = [ \"x86_64-linux\" ];"));
+        assert!(!flake.contains("forEachSupportedSystem"));
+    }
+
+    #[test]
+    fn render_default_style() {
+        let flake = template_data(FlakeStyle::Default).render().unwrap();
+
+        assert!(flake.contains("forEachSupportedSystem"));
+        assert!(!flake.contains("perSystem"));
+        assert!(!flake.contains("allowUnfreePredicate"));
+    }
+
+    #[test]
+    fn render_unfree_packages() {
+        for style in [FlakeStyle::Default, FlakeStyle::FlakeParts] {
+            let mut data = template_data(style);
+            data.unfree_packages = vec![String::from("terraform")];
+            data.imports_nixpkgs = true;
+            let flake = data.render().unwrap();
+
+            assert!(
+                flake.contains(
+                    "config.allowUnfreePredicate = pkg: builtins.elem (nixpkgs.lib.getName pkg) [ \"terraform\" ];"
+                ),
+                "{style:?}:\n{flake}"
+            );
+            assert!(!flake.contains("overlays"), "{style:?}:\n{flake}");
+        }
+    }
+}
@@ -3,14 +3,14 @@ use indicatif::{ProgressBar, ProgressStyle};
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::process::ExitCode;
-use tabled::{Table, Tabled};
+use tabled::Tabled;
 use url::Url;
 
-use super::print_json;
 use crate::{
     cli::{
-        cmd::{FlakeHubClient, DEFAULT_STYLE},
+        cmd::{FlakeHubClient, OutputFormat},
         error::FhError,
     },
     flakehub_url,
@@ -20,6 +20,12 @@ use super::CommandExecute;
 
 pub(crate) const FLAKEHUB_WEB_ROOT: &str = "https://flakehub.com";
 
+// How many flakes to request at a time when streaming `fh list flakes` as CSV
+const FLAKES_PAGE_SIZE: usize = 100;
+
+// How many of those pages to have in flight at once when the number of pages is known
+const FLAKES_PAGE_CONCURRENCY: usize = 4;
+
 /// Lists key FlakeHub resources.
 #[derive(Parser)]
 pub(crate) struct ListSubcommand {
@@ -30,6 +36,24 @@ pub(crate) struct ListSubcommand {
     #[arg(long, global = true, env = "FH_OUTPUT_JSON")]
     json: bool,
 
+    /// How to print the results. Defaults to a table in a terminal and CSV otherwise, or JSON
+    /// with `--json`.
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// The maximum number of results to list.
+    #[arg(long, global = true)]
+    limit: Option<usize>,
+
+    /// Skip this many results before listing any. Combine with `--limit` to page through
+    /// flakes, releases, and versions.
+    #[arg(long, global = true)]
+    offset: Option<usize>,
+
+    /// Re-run the query every this many seconds, redrawing the table until interrupted.
+    #[arg(long, global = true, value_name = "SECONDS")]
+    watch: Option<u64>,
+
     #[arg(from_global)]
     api_addr: url::Url,
 }
@@ -72,8 +96,8 @@ impl TryFrom<String> for Flake {
 
 #[derive(Deserialize, Serialize)]
 pub(crate) struct Version {
-    version: semver::Version,
-    simplified_version: semver::Version,
+    pub(crate) version: semver::Version,
+    pub(crate) simplified_version: semver::Version,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -90,6 +114,12 @@ pub(crate) struct Release {
 enum Subcommands {
     /// Lists all currently public flakes on FlakeHub.
     Flakes,
+    /// Lists the inputs declared in a local flake.nix.
+    Inputs {
+        /// The flake.nix to read, or `-` to read it from stdin.
+        #[arg(long, default_value = "./flake.nix")]
+        flake_path: PathBuf,
+    },
     /// Lists all public flakes with the provided label.
     Label { label: String },
     /// Lists all currently public organizations on FlakeHub.
@@ -98,6 +128,9 @@ enum Subcommands {
     Releases {
         /// The flake for which you want to list releases.
         flake: String,
+        /// Include the URL to use in a flake's `inputs` for each release.
+        #[arg(long)]
+        with_url: bool,
     },
     /// List all versions that match the provided version constraint.
     Versions {
@@ -111,176 +144,399 @@ enum Subcommands {
 #[async_trait::async_trait]
 impl CommandExecute for ListSubcommand {
     async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let Some(interval) = self.watch else {
+            self.list().await?;
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        if self.format() != OutputFormat::Table || !std::io::stdout().is_terminal() {
+            return Err(color_eyre::eyre::eyre!(
+                "--watch redraws a table, so it can only be used in a terminal and with table output"
+            ));
+        }
+        if interval == 0 {
+            return Err(color_eyre::eyre::eyre!(
+                "--watch needs an interval of at least one second"
+            ));
+        }
+
+        let command = std::env::args().collect::<Vec<_>>().join(" ");
+
+        loop {
+            // Clear the screen and move the cursor to the top left before redrawing
+            print!("\x1b[2J\x1b[H");
+            println!(
+                "Every {interval}s: {command}    {}\n",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+            );
+
+            // A failed refresh shouldn't end the watch; the next one may well succeed
+            if let Err(e) = self.list().await {
+                eprintln!("{e}");
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(std::time::Duration::from_secs(interval)) => {}
+                _ = tokio::signal::ctrl_c() => return Ok(ExitCode::SUCCESS),
+            }
+        }
+    }
+}
+
+impl ListSubcommand {
+    fn format(&self) -> OutputFormat {
+        OutputFormat::choose(self.output, self.json)
+    }
+
+    async fn list(&self) -> color_eyre::Result<()> {
         use Subcommands::*;
 
-        match self.cmd {
+        if self.offset.is_some() {
+            let unsupported = match &self.cmd {
+                Inputs { .. } => Some("inputs"),
+                Label { .. } => Some("label"),
+                Orgs => Some("orgs"),
+                Flakes | Releases { .. } | Versions { .. } => None,
+            };
+            if let Some(subcommand) = unsupported {
+                return Err(color_eyre::eyre::eyre!(
+                    "`fh list {subcommand}` can't be paged, so --offset can't be used with it; it \
+                    works with `fh list flakes`, `fh list releases`, and `fh list versions`"
+                ));
+            }
+        }
+
+        let format = self.format();
+
+        match &self.cmd {
+            // CSV rows don't need to be aligned with each other, so they can be written out page by
+            // page as they arrive instead of holding every flake in memory
+            Flakes if format == OutputFormat::Csv && self.offset.is_none() => {
+                let mut writer = csv::Writer::from_writer(std::io::stdout());
+                let count = stream_flakes(self.api_addr.as_ref(), &mut writer, self.limit).await?;
+
+                if count == 0 {
+                    eprintln!("No results");
+                }
+            }
             Flakes => {
                 let pb = ProgressBar::new_spinner();
                 pb.set_style(ProgressStyle::default_spinner());
 
-                match FlakeHubClient::flakes(self.api_addr.as_ref()).await {
-                    Ok(flakes) => {
-                        if flakes.is_empty() {
-                            eprintln!("No results");
-                        } else if self.json {
-                            print_json(&flakes)?;
-                        } else {
-                            let rows = flakes
-                                .into_iter()
-                                .map(Into::into)
-                                .collect::<Vec<FlakeRow>>();
-                            if std::io::stdout().is_terminal() {
-                                let mut table = Table::new(rows);
-                                table.with(DEFAULT_STYLE.clone());
-                                println!("{table}");
-                            } else {
-                                let mut writer = csv::Writer::from_writer(std::io::stdout());
-                                for row in rows {
-                                    writer.serialize(row)?;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => return Err(e.into()),
+                let flakes =
+                    FlakeHubClient::flakes(self.api_addr.as_ref(), self.limit, self.offset).await?;
+                ensure_offset_applied(
+                    self.offset,
+                    &flakes,
+                    Flake::name,
+                    FlakeHubClient::flakes(self.api_addr.as_ref(), Some(1), None),
+                )
+                .await?;
+                let flakes = apply_limit(flakes, self.limit);
+                if flakes.is_empty() {
+                    eprintln!("No results");
                 }
+                format.print(flakes, FlakeRow::from)?;
+            }
+            Inputs { flake_path } => {
+                let (_, parsed) = crate::cli::cmd::add::load_flake(flake_path, false).await?;
+                let rows = apply_limit(flake_inputs(&parsed.expression)?, self.limit);
+
+                if rows.is_empty() {
+                    eprintln!("No results");
+                }
+                format.print_rows(rows)?;
             }
             Label { label } => {
-                if string_has_whitespace(&label) {
+                if string_has_whitespace(label) {
                     return Err(FhError::LabelParse(String::from("whitespace not allowed")).into());
                 }
 
                 let label = label.to_lowercase();
 
-                match FlakeHubClient::flakes_by_label(self.api_addr.as_ref(), &label).await {
-                    Ok(flakes) => {
-                        if flakes.is_empty() {
-                            eprintln!("No results");
-                        } else if self.json {
-                            print_json(&flakes)?;
-                        } else {
-                            let rows = flakes
-                                .into_iter()
-                                .map(Into::into)
-                                .collect::<Vec<FlakeRow>>();
-                            if std::io::stdout().is_terminal() {
-                                let mut table = Table::new(rows);
-                                table.with(DEFAULT_STYLE.clone());
-                                println!("{table}");
-                            } else {
-                                let mut writer = csv::Writer::from_writer(std::io::stdout());
-                                for row in rows {
-                                    writer.serialize(row)?;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => return Err(e.into()),
+                let flakes =
+                    FlakeHubClient::flakes_by_label(self.api_addr.as_ref(), &label, self.limit)
+                        .await?;
+                let flakes = apply_limit(flakes, self.limit);
+                if flakes.is_empty() {
+                    eprintln!("No results");
                 }
+                format.print(flakes, FlakeRow::from)?;
             }
             Orgs => {
                 let pb = ProgressBar::new_spinner();
                 pb.set_style(ProgressStyle::default_spinner());
 
-                match FlakeHubClient::orgs(self.api_addr.as_ref()).await {
-                    Ok(orgs) => {
-                        if orgs.is_empty() {
-                            eprintln!("No results");
-                        } else if self.json {
-                            print_json(&orgs)?;
-                        } else {
-                            let rows = orgs.into_iter().map(Into::into).collect::<Vec<OrgRow>>();
-
-                            if std::io::stdout().is_terminal() {
-                                let mut table = Table::new(rows);
-                                table.with(DEFAULT_STYLE.clone());
-                                println!("{table}");
-                            } else {
-                                let mut writer = csv::Writer::from_writer(std::io::stdout());
-                                for row in rows {
-                                    writer.serialize(row)?;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => return Err(e.into()),
+                let orgs = FlakeHubClient::orgs(self.api_addr.as_ref(), self.limit).await?;
+                let orgs = apply_limit(orgs, self.limit);
+                if orgs.is_empty() {
+                    eprintln!("No results");
                 }
+                format.print(orgs, OrgRow::from)?;
             }
-            Releases { flake } => {
+            Releases { flake, with_url } => {
                 let pb = ProgressBar::new_spinner();
                 pb.set_style(ProgressStyle::default_spinner());
 
-                let flake = Flake::try_from(flake)?;
+                let flake = Flake::try_from(flake.clone())?;
+
+                let releases = FlakeHubClient::releases(
+                    self.api_addr.as_ref(),
+                    &flake.org,
+                    &flake.project,
+                    self.limit,
+                    self.offset,
+                )
+                .await?;
+                ensure_offset_applied(
+                    self.offset,
+                    &releases,
+                    |r| r.version.clone(),
+                    FlakeHubClient::releases(
+                        self.api_addr.as_ref(),
+                        &flake.org,
+                        &flake.project,
+                        Some(1),
+                        None,
+                    ),
+                )
+                .await?;
+                let releases = apply_limit(releases, self.limit);
 
-                match FlakeHubClient::releases(self.api_addr.as_ref(), &flake.org, &flake.project)
-                    .await
-                {
-                    Ok(releases) => {
-                        let rows = releases
+                if releases.is_empty() {
+                    eprintln!("No results");
+                }
+                if *with_url {
+                    format.print_rows(
+                        releases
+                            .into_iter()
+                            .map(|r| (flake.clone(), r).into())
+                            .collect::<Vec<ReleaseWithUrlRow>>(),
+                    )?;
+                } else {
+                    format.print_rows(
+                        releases
                             .into_iter()
                             .map(Into::into)
-                            .collect::<Vec<ReleaseRow>>();
-
-                        if rows.is_empty() {
-                            eprintln!("No results");
-                        } else if self.json {
-                            print_json(&rows)?;
-                        } else if std::io::stdout().is_terminal() {
-                            let mut table = Table::new(rows);
-                            table.with(DEFAULT_STYLE.clone());
-                            println!("{table}");
-                        } else {
-                            let mut writer = csv::Writer::from_writer(std::io::stdout());
-                            for row in rows {
-                                writer.serialize(row)?;
-                            }
-                        }
-                    }
-                    Err(e) => return Err(e.into()),
+                            .collect::<Vec<ReleaseRow>>(),
+                    )?;
                 }
             }
             Versions { flake, constraint } => {
                 let pb = ProgressBar::new_spinner();
                 pb.set_style(ProgressStyle::default_spinner());
 
-                let flake = Flake::try_from(flake)?.clone();
+                let flake = Flake::try_from(flake.clone())?;
 
-                match FlakeHubClient::versions(
+                let versions = FlakeHubClient::versions(
                     self.api_addr.as_ref(),
                     &flake.org,
                     &flake.project,
-                    &constraint,
+                    constraint,
+                    self.limit,
+                    self.offset,
+                )
+                .await?;
+                ensure_offset_applied(
+                    self.offset,
+                    &versions,
+                    |v| v.version.clone(),
+                    FlakeHubClient::versions(
+                        self.api_addr.as_ref(),
+                        &flake.org,
+                        &flake.project,
+                        constraint,
+                        Some(1),
+                        None,
+                    ),
                 )
-                .await
-                {
-                    Ok(versions) => {
-                        if versions.is_empty() {
-                            eprintln!("No versions match the provided constraint");
-                        } else if self.json {
-                            print_json(&versions)?;
-                        } else {
-                            let rows = versions
-                                .into_iter()
-                                .map(|v| (flake.clone(), v).into())
-                                .collect::<Vec<VersionRow>>();
-                            if std::io::stdout().is_terminal() {
-                                let mut table = Table::new(rows);
-                                table.with(DEFAULT_STYLE.clone());
-                                println!("{table}");
-                            } else {
-                                let mut writer = csv::Writer::from_writer(std::io::stdout());
-                                for row in rows {
-                                    writer.serialize(row)?;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => return Err(e.into()),
+                .await?;
+                let versions = apply_limit(versions, self.limit);
+                if versions.is_empty() {
+                    eprintln!("No versions match the provided constraint");
                 }
+                format.print(versions, |v| VersionRow::from((flake.clone(), v)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Writes all flakes (or the first `limit` of them) to `writer` one page at a time, returning how
+// many were written.
+async fn stream_flakes<W: std::io::Write>(
+    api_addr: &str,
+    writer: &mut csv::Writer<W>,
+    limit: Option<usize>,
+) -> color_eyre::Result<usize> {
+    use futures::{StreamExt as _, TryStreamExt as _};
+
+    let mut count = 0;
+    let mut page = 1;
+    let mut previous_first: Option<String> = None;
+
+    // With a limit, it's known up front how many pages are needed, so a few of them can be fetched
+    // at a time; without one, the pages are fetched one by one until a short one comes back
+    let (last_page, concurrency) = match limit {
+        Some(limit) => (
+            limit.div_ceil(FLAKES_PAGE_SIZE).max(1),
+            FLAKES_PAGE_CONCURRENCY,
+        ),
+        None => (usize::MAX, 1),
+    };
+    let mut pages = futures::stream::iter(1..=last_page)
+        .map(|page| FlakeHubClient::flakes_page(api_addr, page, FLAKES_PAGE_SIZE))
+        .buffered(concurrency);
+
+    while let Some(flakes) = pages.try_next().await? {
+        let page_len = flakes.len();
+
+        // A server that doesn't paginate returns the same flakes for every page
+        let first = flakes.first().map(Flake::name);
+        if page > 1 && first.is_some() && first == previous_first {
+            break;
+        }
+        previous_first = first;
+
+        let remaining = limit.map_or(page_len, |limit| limit.saturating_sub(count));
+        for flake in flakes.into_iter().take(remaining) {
+            writer.serialize(FlakeRow::from(flake))?;
+        }
+        writer.flush()?;
+        count += page_len.min(remaining);
+
+        if limit.is_some_and(|limit| count >= limit) {
+            break;
+        }
+
+        // A short page is the last one, and a page that's longer than requested means the server
+        // sent everything at once
+        if page_len != FLAKES_PAGE_SIZE {
+            break;
+        }
+        page += 1;
+    }
+
+    Ok(count)
+}
+
+// A FlakeHub that doesn't support `offset` ignores it and starts from the beginning, which would
+// quietly list the wrong results. If the first result is also the first one without an offset,
+// the offset wasn't applied. `without_offset` is only awaited when there's an offset to check.
+async fn ensure_offset_applied<T, K: PartialEq>(
+    offset: Option<usize>,
+    items: &[T],
+    key: impl Fn(&T) -> K,
+    without_offset: impl std::future::Future<Output = Result<Vec<T>, FhError>>,
+) -> color_eyre::Result<()> {
+    let (Some(1..), Some(first)) = (offset, items.first()) else {
+        return Ok(());
+    };
+
+    if without_offset.await?.first().map(&key) == Some(key(first)) {
+        return Err(color_eyre::eyre::eyre!(
+            "FlakeHub didn't apply --offset, so these results would start from the beginning; \
+            leave out --offset and use --limit instead"
+        ));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn apply_limit<T>(mut items: Vec<T>, limit: Option<usize>) -> Vec<T> {
+    if let Some(limit) = limit {
+        items.truncate(limit);
+    }
+
+    items
+}
+
+// Every input the flake declares, in the order they're declared.
+fn flake_inputs(expr: &nixel::Expression) -> color_eyre::Result<Vec<InputRow>> {
+    let all_toplevel_inputs = crate::cli::cmd::add::flake::find_all_attrsets_by_path(
+        expr,
+        Some(["inputs".into()].into()),
+    )?;
+    let mut rows: Vec<InputRow> = Vec::new();
+
+    for input in crate::cli::cmd::add::flake::collect_all_inputs(all_toplevel_inputs.clone())? {
+        let Some(input_name) = input.from.iter().find_map(|part| match part {
+            nixel::Part::Raw(raw) => {
+                let content = raw.content.trim();
+
+                (!["inputs", "url"].contains(&content)).then(|| content.to_string())
             }
+            _ => None,
+        }) else {
+            continue;
+        };
+
+        let last_attr = match input.from.last() {
+            Some(nixel::Part::Raw(raw)) => raw.content.trim(),
+            _ => "",
+        };
+        // Either `nixpkgs.url = "...";` or `nixpkgs = { url = "..."; };`, but not something like
+        // `nixpkgs.flake = false;`
+        let url = if last_attr == "url" || matches!(&*input.to, nixel::Expression::Map(_)) {
+            crate::cli::cmd::convert::find_input_value_by_path(&input.to, ["url".into()].into())?
+        } else {
+            None
+        };
+        let follows = last_attr == "follows" || has_follows(&input.to);
+
+        let row = input_row(&mut rows, &input_name);
+        if url.is_some() {
+            row.url = url;
         }
+        row.follows |= follows;
+    }
 
-        Ok(ExitCode::SUCCESS)
+    for kv in &all_toplevel_inputs {
+        // inputs.nixpkgs.inputs.flake-utils.follows = "flake-utils";
+        // `collect_all_inputs` skips these, since they don't say anything about the input's URL
+        if let [nixel::Part::Raw(inputs), nixel::Part::Raw(name), .., nixel::Part::Raw(last)] =
+            &kv.from[..]
+        {
+            if &*inputs.content == "inputs" && kv.from.len() > 3 && &*last.content == "follows" {
+                input_row(&mut rows, name.content.trim()).follows = true;
+            }
+        }
     }
+
+    Ok(rows)
+}
+
+fn input_row<'a>(rows: &'a mut Vec<InputRow>, name: &str) -> &'a mut InputRow {
+    let idx = match rows.iter().position(|row| row.input == name) {
+        Some(idx) => idx,
+        None => {
+            rows.push(InputRow {
+                input: name.to_string(),
+                url: None,
+                follows: false,
+            });
+            rows.len() - 1
+        }
+    };
+
+    &mut rows[idx]
+}
+
+// Whether an input's attrset, like `{ url = "..."; inputs.nixpkgs.follows = "nixpkgs"; }`, has a
+// `follows` anywhere in it.
+fn has_follows(expr: &nixel::Expression) -> bool {
+    let nixel::Expression::Map(map) = expr else {
+        return false;
+    };
+
+    map.bindings.iter().any(|binding| match binding {
+        nixel::Binding::KeyValue(kv) => {
+            matches!(kv.from.last(), Some(nixel::Part::Raw(raw)) if &*raw.content == "follows")
+                || has_follows(&kv.to)
+        }
+        nixel::Binding::Inherit(_) => false,
+    })
 }
 
 fn string_has_whitespace(s: &str) -> bool {
@@ -339,6 +595,23 @@ impl From<(Flake, Version)> for VersionRow {
     }
 }
 
+#[derive(Tabled, serde::Serialize)]
+struct InputRow {
+    #[tabled(rename = "Input", display_with = "bold")]
+    #[serde(rename = "Input")]
+    input: String,
+    #[tabled(rename = "URL", display_with = "display_url")]
+    #[serde(rename = "URL")]
+    url: Option<String>,
+    #[tabled(rename = "Follows")]
+    #[serde(rename = "Follows")]
+    follows: bool,
+}
+
+fn display_url(url: &Option<String>) -> String {
+    dimmed(url.as_deref().unwrap_or("-"))
+}
+
 #[derive(Tabled, serde::Serialize)]
 struct FlakeRow {
     #[tabled(rename = "Flake", display_with = "bold")]
@@ -372,10 +645,320 @@ impl From<Release> for ReleaseRow {
     }
 }
 
-fn dimmed(v: impl ToString) -> String {
-    v.to_string().dimmed().to_string()
+#[derive(Tabled, serde::Serialize)]
+pub(crate) struct ReleaseWithUrlRow {
+    #[tabled(rename = "Version", display_with = "bold")]
+    #[serde(rename = "Version")]
+    pub(crate) version: String,
+    #[tabled(rename = "Input URL", display_with = "dimmed")]
+    #[serde(rename = "Input URL")]
+    pub(crate) input_url: Url,
+}
+
+impl From<(Flake, Release)> for ReleaseWithUrlRow {
+    fn from((flake, release): (Flake, Release)) -> Self {
+        let input_url = flakehub_url!(
+            FLAKEHUB_WEB_ROOT,
+            "f",
+            &flake.org,
+            &flake.project,
+            &format!("{}.tar.gz", release.version)
+        );
+
+        Self {
+            version: release.version,
+            input_url,
+        }
+    }
+}
+
+pub(super) fn dimmed(v: impl ToString) -> String {
+    if crate::cli::color::stdout() {
+        v.to_string().dimmed().to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+pub(super) fn bold(v: impl ToString) -> String {
+    if crate::cli::color::stdout() {
+        v.to_string().bold().to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::{extract::Query, response::IntoResponse};
+
+    use super::{Flake, Release, ReleaseWithUrlRow, FLAKES_PAGE_SIZE};
+    use crate::cli::cmd::test_server;
+
+    const TOTAL_FLAKES: usize = 250;
+
+    #[derive(serde::Deserialize)]
+    struct Pagination {
+        page: usize,
+        per_page: usize,
+    }
+
+    async fn flakes(Query(pagination): Query<Pagination>) -> axum::response::Response {
+        let start = (pagination.page - 1) * pagination.per_page;
+        let end = (start + pagination.per_page).min(TOTAL_FLAKES);
+        let flakes: Vec<_> = (start..end)
+            .map(|i| serde_json::json!({ "org": "someorg", "project": format!("project{i}") }))
+            .collect();
+
+        axum::Json(flakes).into_response()
+    }
+
+    async fn unpaginated_flakes() -> axum::response::Response {
+        let flakes: Vec<_> = (0..FLAKES_PAGE_SIZE)
+            .map(|i| serde_json::json!({ "org": "someorg", "project": format!("project{i}") }))
+            .collect();
+
+        axum::Json(flakes).into_response()
+    }
+
+    #[tokio::test]
+    async fn stream_flakes_in_pages() {
+        let test_server =
+            test_server(axum::Router::new().route("/flakes", axum::routing::get(flakes)));
+        let server_url = test_server.server_address().unwrap();
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        let count = super::stream_flakes(server_url.as_str(), &mut writer, None)
+            .await
+            .unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        assert_eq!(count, TOTAL_FLAKES);
+        // One header line plus one line per flake
+        assert_eq!(csv.lines().count(), TOTAL_FLAKES + 1);
+        assert!(csv.contains("someorg/project249"));
+    }
+
+    #[tokio::test]
+    async fn stream_flakes_without_server_pagination() {
+        let test_server = test_server(
+            axum::Router::new().route("/flakes", axum::routing::get(unpaginated_flakes)),
+        );
+        let server_url = test_server.server_address().unwrap();
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        let count = super::stream_flakes(server_url.as_str(), &mut writer, None)
+            .await
+            .unwrap();
+
+        assert_eq!(count, FLAKES_PAGE_SIZE);
+    }
+
+    #[tokio::test]
+    async fn stream_flakes_with_limit() {
+        let test_server =
+            test_server(axum::Router::new().route("/flakes", axum::routing::get(flakes)));
+        let server_url = test_server.server_address().unwrap();
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        let count = super::stream_flakes(server_url.as_str(), &mut writer, Some(150))
+            .await
+            .unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        assert_eq!(count, 150);
+        assert_eq!(csv.lines().count(), 150 + 1);
+        assert!(csv.contains("someorg/project149"));
+        assert!(!csv.contains("someorg/project150"));
+    }
+
+    #[tokio::test]
+    async fn limit_is_sent_to_server() {
+        #[derive(serde::Deserialize)]
+        struct Limit {
+            limit: Option<usize>,
+        }
+
+        async fn orgs(Query(params): Query<Limit>) -> axum::response::Response {
+            let orgs: Vec<_> = (0..params.limit.unwrap_or(TOTAL_FLAKES))
+                .map(|i| serde_json::json!({ "name": format!("org{i}") }))
+                .collect();
+
+            axum::Json(orgs).into_response()
+        }
+
+        let test_server = test_server(axum::Router::new().route("/orgs", axum::routing::get(orgs)));
+        let server_url = test_server.server_address().unwrap();
+
+        let orgs = crate::cli::cmd::FlakeHubClient::orgs(server_url.as_str(), Some(5))
+            .await
+            .unwrap();
+        assert_eq!(orgs.len(), 5);
+
+        let orgs = crate::cli::cmd::FlakeHubClient::orgs(server_url.as_str(), None)
+            .await
+            .unwrap();
+        assert_eq!(orgs.len(), TOTAL_FLAKES);
+    }
+
+    #[tokio::test]
+    async fn offset_is_sent_to_server() {
+        #[derive(serde::Deserialize)]
+        struct Window {
+            limit: Option<usize>,
+            offset: Option<usize>,
+        }
+
+        async fn releases(Query(window): Query<Window>) -> axum::response::Response {
+            let releases: Vec<_> = (0..TOTAL_FLAKES)
+                .skip(window.offset.unwrap_or(0))
+                .take(window.limit.unwrap_or(TOTAL_FLAKES))
+                .map(|i| serde_json::json!({ "version": format!("0.1.{i}") }))
+                .collect();
+
+            axum::Json(releases).into_response()
+        }
+
+        let test_server = test_server(
+            axum::Router::new().route("/f/:org/:project/releases", axum::routing::get(releases)),
+        );
+        let server_url = test_server.server_address().unwrap();
+
+        let releases = crate::cli::cmd::FlakeHubClient::releases(
+            server_url.as_str(),
+            "someorg",
+            "someflake",
+            Some(3),
+            Some(10),
+        )
+        .await
+        .unwrap();
+        super::ensure_offset_applied(
+            Some(10),
+            &releases,
+            |r| r.version.clone(),
+            crate::cli::cmd::FlakeHubClient::releases(
+                server_url.as_str(),
+                "someorg",
+                "someflake",
+                Some(1),
+                None,
+            ),
+        )
+        .await
+        .unwrap();
+        let versions: Vec<_> = releases.into_iter().map(|r| r.version).collect();
+        assert_eq!(versions, ["0.1.10", "0.1.11", "0.1.12"]);
+    }
+
+    #[tokio::test]
+    async fn ignored_offset_is_an_error() {
+        async fn releases() -> axum::response::Response {
+            let releases: Vec<_> = (0..TOTAL_FLAKES)
+                .map(|i| serde_json::json!({ "version": format!("0.1.{i}") }))
+                .collect();
+
+            axum::Json(releases).into_response()
+        }
+
+        let test_server = test_server(
+            axum::Router::new().route("/f/:org/:project/releases", axum::routing::get(releases)),
+        );
+        let server_url = test_server.server_address().unwrap();
+        let releases = |limit, offset| {
+            crate::cli::cmd::FlakeHubClient::releases(
+                server_url.as_str(),
+                "someorg",
+                "someflake",
+                limit,
+                offset,
+            )
+        };
+
+        let ignored = releases(Some(3), Some(10)).await.unwrap();
+        let err = super::ensure_offset_applied(
+            Some(10),
+            &ignored,
+            |r| r.version.clone(),
+            releases(Some(1), None),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("didn't apply --offset"));
+
+        // Without an offset, there's nothing to check
+        super::ensure_offset_applied(None, &ignored, |r| r.version.clone(), async {
+            panic!("shouldn't be fetched without an offset")
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn flake_inputs() {
+        let flake_contents = r#"{
+  inputs.nixpkgs.url = "https://flakehub.com/f/NixOS/nixpkgs/0.1.*";
+  inputs.flake-utils.url = "github:numtide/flake-utils";
+  inputs.flake-utils.inputs.nixpkgs.follows = "nixpkgs";
+  inputs = {
+    home-manager = {
+      url = "github:nix-community/home-manager";
+      inputs.nixpkgs.follows = "nixpkgs";
+    };
+    systems.follows = "flake-utils/systems";
+    nix-darwin.url = "github:LnL7/nix-darwin";
+    nix-darwin.flake = true;
+  };
+
+  outputs = { ... }: { };
 }
+"#;
+        let parsed = nixel::parse(flake_contents.to_string());
+
+        let rows = super::flake_inputs(&parsed.expression).unwrap();
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|row| (row.input.as_str(), row.url.as_deref(), row.follows))
+            .collect();
+
+        assert_eq!(
+            rows,
+            [
+                (
+                    "nixpkgs",
+                    Some("https://flakehub.com/f/NixOS/nixpkgs/0.1.*"),
+                    false
+                ),
+                ("flake-utils", Some("github:numtide/flake-utils"), true),
+                (
+                    "home-manager",
+                    Some("github:nix-community/home-manager"),
+                    true
+                ),
+                ("systems", None, true),
+                ("nix-darwin", Some("github:LnL7/nix-darwin"), false),
+            ]
+        );
+
+        let empty = nixel::parse(String::from("{ outputs = { ... }: { }; }"));
+        assert!(super::flake_inputs(&empty.expression).unwrap().is_empty());
+    }
+
+    #[test]
+    fn release_input_url() {
+        let flake = Flake {
+            org: String::from("NixOS"),
+            project: String::from("nixpkgs"),
+        };
+        let release = Release {
+            version: String::from("0.2411.715026"),
+        };
+
+        let row: ReleaseWithUrlRow = (flake, release).into();
 
-fn bold(v: impl ToString) -> String {
-    v.to_string().bold().to_string()
+        assert_eq!(
+            row.input_url.as_str(),
+            "https://flakehub.com/f/NixOS/nixpkgs/0.2411.715026.tar.gz"
+        );
+    }
 }
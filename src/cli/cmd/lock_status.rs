@@ -0,0 +1,289 @@
+use std::cmp::Ordering;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use serde::Serialize;
+use tabled::{Table, Tabled};
+
+use super::{print_json, CommandExecute, FlakeHubClient, DEFAULT_STYLE};
+use crate::info_eprintln;
+use crate::shared::flake_lock::FlakeLock;
+
+/// Reports which FlakeHub inputs in a flake.lock have newer releases that match their version
+/// constraints.
+#[derive(Debug, Parser)]
+pub(crate) struct LockStatusSubcommand {
+    /// The flake.lock to check.
+    #[clap(long, default_value = "./flake.lock")]
+    lock_path: PathBuf,
+
+    /// Output results as JSON.
+    #[clap(long, env = "FH_OUTPUT_JSON")]
+    json: bool,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+}
+
+/// A root input that's locked to a FlakeHub release.
+#[derive(Debug, PartialEq)]
+pub(super) struct FlakeHubInput {
+    pub(super) name: String,
+    pub(super) org: String,
+    pub(super) project: String,
+    pub(super) constraint: String,
+    pub(super) locked_version: semver::Version,
+}
+
+impl FlakeHubInput {
+    /// The newest release that matches the input's constraint, or `None` if FlakeHub couldn't
+    /// say.
+    pub(super) async fn latest_version(&self, api_addr: &url::Url) -> Option<semver::Version> {
+        match FlakeHubClient::versions(
+            api_addr.as_ref(),
+            &self.org,
+            &self.project,
+            &self.constraint,
+            None,
+            None,
+        )
+        .await
+        {
+            Ok(versions) => latest_version(versions.into_iter().map(|v| v.version)),
+            Err(e) => {
+                tracing::warn!(
+                    "Couldn't look up the releases of {}/{} for input `{}`: {e}",
+                    self.org,
+                    self.project,
+                    self.name
+                );
+                None
+            }
+        }
+    }
+
+    /// Whether `version` is newer than the one the input is locked to.
+    pub(super) fn is_outdated_by(&self, version: &semver::Version) -> bool {
+        version.cmp_precedence(&self.locked_version) == Ordering::Greater
+    }
+}
+
+#[derive(Tabled, Serialize)]
+struct LockStatusRow {
+    #[tabled(rename = "Input")]
+    #[serde(rename = "Input")]
+    input: String,
+    #[tabled(rename = "Constraint")]
+    #[serde(rename = "Constraint")]
+    constraint: String,
+    #[tabled(rename = "Locked")]
+    #[serde(rename = "Locked")]
+    locked: semver::Version,
+    #[tabled(rename = "Latest", display_with = "display_latest")]
+    #[serde(rename = "Latest")]
+    latest: Option<semver::Version>,
+    #[tabled(rename = "Update available", display_with = "display_yes_no")]
+    #[serde(rename = "Update available")]
+    update_available: bool,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for LockStatusSubcommand {
+    #[tracing::instrument(skip_all)]
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let lock = FlakeLock::read(&self.lock_path).await?;
+        let inputs = flakehub_inputs(&lock);
+
+        if inputs.is_empty() {
+            info_eprintln!(
+                "{} has no inputs locked to FlakeHub releases",
+                self.lock_path.display()
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut rows = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let latest = input.latest_version(&self.api_addr).await;
+
+            rows.push(LockStatusRow {
+                update_available: latest
+                    .as_ref()
+                    .is_some_and(|latest| input.is_outdated_by(latest)),
+                input: input.name,
+                constraint: input.constraint,
+                locked: without_build(input.locked_version),
+                latest: latest.map(without_build),
+            });
+        }
+
+        if self.json {
+            print_json(&rows)?;
+        } else if std::io::stdout().is_terminal() {
+            let mut table = Table::new(rows);
+            table.with(DEFAULT_STYLE.clone());
+            println!("{table}");
+        } else {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for row in rows {
+                writer.serialize(row)?;
+            }
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+// The root inputs of `lock` that were locked from a FlakeHub URL like
+// `https://flakehub.com/f/NixOS/nixpkgs/0.2405.*`. Inputs that `follows` another have no lock entry
+// of their own, so they're left out.
+pub(super) fn flakehub_inputs(lock: &FlakeLock) -> Vec<FlakeHubInput> {
+    lock.root_input_names()
+        .into_iter()
+        .filter_map(|name| {
+            let original = lock.root_input_original(name)?.get("url")?.as_str()?;
+            let locked = lock.root_input_locked(name)?.get("url")?.as_str()?;
+
+            let original = flakehub_path(original)?;
+            let (org, project, constraint) =
+                match &original.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                    ["f", org, project, constraint] => (*org, *project, *constraint),
+                    _ => return None,
+                };
+            // Locked URLs look like `https://api.flakehub.com/f/pinned/{org}/{project}/{version}/...`
+            let locked = flakehub_path(locked)?;
+            let locked_version = match &locked.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                ["f", "pinned", _, _, version, ..] => semver::Version::parse(version).ok()?,
+                _ => return None,
+            };
+
+            Some(FlakeHubInput {
+                name: name.to_string(),
+                org: org.to_string(),
+                project: project.to_string(),
+                constraint: constraint.trim_end_matches(".tar.gz").to_string(),
+                locked_version,
+            })
+        })
+        .collect()
+}
+
+// The decoded path segments of a FlakeHub URL.
+fn flakehub_path(url: &str) -> Option<Vec<String>> {
+    let url = url::Url::parse(url).ok()?;
+    if !matches!(url.host_str(), Some("flakehub.com" | "api.flakehub.com")) {
+        return None;
+    }
+
+    url.path_segments()?
+        .map(|segment| urlencoding::decode(segment).ok().map(|s| s.into_owned()))
+        .collect()
+}
+
+// The newest of `versions`, ignoring build metadata like `+rev-...`.
+fn latest_version(versions: impl Iterator<Item = semver::Version>) -> Option<semver::Version> {
+    versions.max_by(|a, b| a.cmp_precedence(b))
+}
+
+pub(super) fn without_build(mut version: semver::Version) -> semver::Version {
+    version.build = semver::BuildMetadata::EMPTY;
+    version
+}
+
+fn display_latest(latest: &Option<semver::Version>) -> String {
+    latest
+        .as_ref()
+        .map_or_else(|| String::from("unknown"), ToString::to_string)
+}
+
+fn display_yes_no(update_available: &bool) -> String {
+    String::from(if *update_available { "yes" } else { "no" })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shared::flake_lock::FlakeLock;
+
+    #[test]
+    fn flakehub_inputs() {
+        let lock = FlakeLock::parse(
+            r#"{
+              "nodes": {
+                "fh": {
+                  "locked": {
+                    "type": "tarball",
+                    "url": "https://api.flakehub.com/f/pinned/DeterminateSystems/fh/0.1.10/018c3a0b-0000-0000-0000-000000000000/source.tar.gz"
+                  },
+                  "original": {
+                    "type": "tarball",
+                    "url": "https://flakehub.com/f/DeterminateSystems/fh/0.1.%2A.tar.gz"
+                  }
+                },
+                "nixpkgs": {
+                  "locked": {
+                    "type": "tarball",
+                    "url": "https://api.flakehub.com/f/pinned/NixOS/nixpkgs/0.2405.630265%2Brev-0000000000000000000000000000000000000000/0190b1f2-0000-0000-0000-000000000000/source.tar.gz"
+                  },
+                  "original": {
+                    "type": "tarball",
+                    "url": "https://flakehub.com/f/NixOS/nixpkgs/0.2405"
+                  }
+                },
+                "flake-utils": {
+                  "locked": { "owner": "numtide", "repo": "flake-utils", "type": "github" },
+                  "original": { "owner": "numtide", "repo": "flake-utils", "type": "github" }
+                },
+                "root": {
+                  "inputs": {
+                    "fh": "fh",
+                    "flake-utils": "flake-utils",
+                    "nixpkgs": "nixpkgs",
+                    "nixpkgs-follower": ["nixpkgs"]
+                  }
+                }
+              },
+              "root": "root",
+              "version": 7
+            }"#,
+        )
+        .unwrap();
+
+        let inputs = super::flakehub_inputs(&lock);
+        assert_eq!(
+            inputs,
+            [
+                super::FlakeHubInput {
+                    name: String::from("fh"),
+                    org: String::from("DeterminateSystems"),
+                    project: String::from("fh"),
+                    constraint: String::from("0.1.*"),
+                    locked_version: semver::Version::parse("0.1.10").unwrap(),
+                },
+                super::FlakeHubInput {
+                    name: String::from("nixpkgs"),
+                    org: String::from("NixOS"),
+                    project: String::from("nixpkgs"),
+                    constraint: String::from("0.2405"),
+                    locked_version: semver::Version::parse(
+                        "0.2405.630265+rev-0000000000000000000000000000000000000000"
+                    )
+                    .unwrap(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn latest_version_ignores_build_metadata() {
+        let versions = ["0.1.9", "0.1.10+rev-aaa", "0.1.10+rev-bbb", "0.1.2"]
+            .map(|v| semver::Version::parse(v).unwrap());
+
+        let latest = super::latest_version(versions.into_iter()).unwrap();
+        assert_eq!(
+            super::without_build(latest),
+            semver::Version::parse("0.1.10").unwrap()
+        );
+    }
+}
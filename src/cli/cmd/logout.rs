@@ -0,0 +1,284 @@
+use std::path::Path;
+use std::process::ExitCode;
+
+use axum::body::Body;
+use clap::Parser;
+use color_eyre::eyre::WrapErr;
+use hyper::{Method, StatusCode};
+
+use super::login::{dnixd_uds, write_nix_config, DnixdUnavailable, CACHE_PUBLIC_KEYS};
+use super::CommandExecute;
+use crate::info_println;
+
+/// Log out of FlakeHub, removing the credentials and configuration that `fh login` set up.
+#[derive(Debug, Parser)]
+pub(crate) struct LogoutSubcommand {
+    /// Keep the FlakeHub Cache substituter and its public keys in your nix.conf.
+    #[clap(long)]
+    keep_nix_conf: bool,
+
+    #[clap(from_global)]
+    cache_addr: url::Url,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for LogoutSubcommand {
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let mut removed = Vec::new();
+
+        match dnixd_uds().await {
+            Ok(mut uds) => {
+                let request = http::request::Builder::new()
+                    .uri("http://localhost/remove-netrc-token")
+                    .method(Method::POST)
+                    .body(Body::empty())?;
+                let response = uds
+                    .send_request(request)
+                    .await
+                    .wrap_err("Performing the logout request with determinate-nixd")?;
+
+                if response.status() == StatusCode::OK {
+                    removed.push(String::from("the token enrolled with determinate-nixd"));
+                } else {
+                    tracing::warn!(
+                        "determinate-nixd didn't remove its FlakeHub token ({}); run `determinate-nixd logout` to remove it",
+                        response.status()
+                    );
+                }
+            }
+            Err(err @ DnixdUnavailable::Absent(_)) => {
+                tracing::debug!("{err}, will not attempt to use it");
+            }
+            Err(err @ DnixdUnavailable::Unhealthy(_)) => {
+                tracing::warn!(
+                    "{err}; it may still have a FlakeHub token, which `determinate-nixd logout` removes"
+                );
+            }
+        }
+
+        let token_path = crate::cli::token::user_token_read_path()?;
+        if remove_file_if_exists(&token_path).await? {
+            removed.push(format!("the token in {}", token_path.display()));
+        }
+
+        let xdg = xdg::BaseDirectories::new()?;
+        let netrc_path = xdg.get_config_file("nix/netrc");
+        if remove_flakehub_netrc_lines(&netrc_path).await? {
+            removed.push(format!("the FlakeHub logins in {}", netrc_path.display()));
+        }
+
+        if !self.keep_nix_conf {
+            let nix_config_path = xdg.get_config_file("nix/nix.conf");
+            match tokio::fs::read_to_string(&nix_config_path).await {
+                Ok(contents) => {
+                    let (new_contents, settings) =
+                        remove_fh_nix_config(&contents, &netrc_path, &self.cache_addr);
+
+                    if !settings.is_empty() {
+                        write_nix_config(&nix_config_path, &new_contents).await?;
+                        removed.push(format!(
+                            "{} from {}",
+                            settings
+                                .iter()
+                                .map(|setting| format!("`{setting}`"))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            nix_config_path.display()
+                        ));
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).wrap_err_with(|| {
+                        format!("Reading the Nix configuration file {:?}", &nix_config_path)
+                    })
+                }
+            }
+        }
+
+        if removed.is_empty() {
+            info_println!("You weren't logged in to FlakeHub; there was nothing to remove.");
+        } else {
+            info_println!("Logged out of FlakeHub. Removed:");
+            for item in removed {
+                info_println!("* {item}");
+            }
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+// Whether there was a file to remove.
+async fn remove_file_if_exists(path: &Path) -> color_eyre::Result<bool> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).wrap_err_with(|| format!("Removing {}", path.display())),
+    }
+}
+
+// Removes the lines that `fh login` wrote to the netrc, and the netrc itself if nothing else is left
+// in it. Returns whether there were any.
+async fn remove_flakehub_netrc_lines(netrc_path: &Path) -> color_eyre::Result<bool> {
+    let contents = match tokio::fs::read_to_string(netrc_path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e).wrap_err_with(|| format!("Reading {}", netrc_path.display())),
+    };
+
+    let remaining = contents
+        .lines()
+        .filter(|line| !line.contains(" login flakehub password "))
+        .collect::<Vec<_>>();
+    if remaining.len() == contents.lines().count() {
+        return Ok(false);
+    }
+
+    if remaining.iter().all(|line| line.trim().is_empty()) {
+        remove_file_if_exists(netrc_path).await?;
+    } else {
+        tokio::fs::write(netrc_path, remaining.join("\n") + "\n")
+            .await
+            .wrap_err_with(|| format!("Writing {}", netrc_path.display()))?;
+    }
+
+    Ok(true)
+}
+
+// Takes the settings that `fh login` added back out of a nix.conf, leaving everything else (including
+// comments) as it was. Returns the new contents and the names of the settings that changed.
+fn remove_fh_nix_config(
+    contents: &str,
+    netrc_path: &Path,
+    cache_addr: &url::Url,
+) -> (String, Vec<String>) {
+    let netrc_path = netrc_path.display().to_string();
+    let mut changed_settings = Vec::new();
+    let mut new_contents = String::new();
+
+    for line in contents.lines() {
+        let (setting, comment) = match line.find('#') {
+            Some(idx) => line.split_at(idx),
+            None => (line, ""),
+        };
+        let Some((name, value)) = setting.split_once('=') else {
+            new_contents.push_str(line);
+            new_contents.push('\n');
+            continue;
+        };
+        let name = name.trim();
+
+        let is_ours = |item: &str| match name {
+            "netrc-file" => item == netrc_path,
+            "extra-substituters" => item == cache_addr.as_str(),
+            "extra-trusted-public-keys" => CACHE_PUBLIC_KEYS.contains(&item),
+            _ => false,
+        };
+        let items = value.split_whitespace().collect::<Vec<_>>();
+        let remaining = items
+            .iter()
+            .copied()
+            .filter(|item| !is_ours(item))
+            .collect::<Vec<_>>();
+
+        if remaining.len() == items.len() {
+            new_contents.push_str(line);
+            new_contents.push('\n');
+            continue;
+        }
+
+        changed_settings.push(name.to_string());
+        if !remaining.is_empty() {
+            new_contents.push_str(&format!("{name} = {}", remaining.join(" ")));
+            if !comment.is_empty() {
+                new_contents.push(' ');
+                new_contents.push_str(comment);
+            }
+            new_contents.push('\n');
+        }
+    }
+
+    (new_contents, changed_settings)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    #[test]
+    fn remove_fh_nix_config() {
+        let cache_addr = url::Url::parse("https://cache.flakehub.com").unwrap();
+        let keys = super::CACHE_PUBLIC_KEYS.join(" ");
+        let contents = format!(
+            "# My settings\n\
+            experimental-features = nix-command flakes\n\
+            netrc-file = /home/me/.config/nix/netrc\n\
+            extra-substituters = {cache_addr} https://example.cachix.org # keep cachix\n\
+            extra-trusted-public-keys = {keys}\n"
+        );
+
+        let (new_contents, changed) = super::remove_fh_nix_config(
+            &contents,
+            Path::new("/home/me/.config/nix/netrc"),
+            &cache_addr,
+        );
+
+        assert_eq!(
+            new_contents,
+            "# My settings\n\
+            experimental-features = nix-command flakes\n\
+            extra-substituters = https://example.cachix.org # keep cachix\n"
+        );
+        assert_eq!(
+            changed,
+            [
+                "netrc-file",
+                "extra-substituters",
+                "extra-trusted-public-keys"
+            ]
+        );
+
+        // Someone else's netrc is left alone
+        let (_, changed) = super::remove_fh_nix_config(
+            "netrc-file = /etc/nix/netrc\n",
+            Path::new("/home/me/.config/nix/netrc"),
+            &cache_addr,
+        );
+        assert!(changed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_flakehub_netrc_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let netrc_path = dir.path().join("netrc");
+
+        std::fs::write(
+            &netrc_path,
+            "machine flakehub.com login flakehub password abc\n\
+            machine example.com login me password def\n",
+        )
+        .unwrap();
+        assert!(super::remove_flakehub_netrc_lines(&netrc_path)
+            .await
+            .unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&netrc_path).unwrap(),
+            "machine example.com login me password def\n"
+        );
+
+        std::fs::write(
+            &netrc_path,
+            "machine flakehub.com login flakehub password abc\n",
+        )
+        .unwrap();
+        assert!(super::remove_flakehub_netrc_lines(&netrc_path)
+            .await
+            .unwrap());
+        assert!(!netrc_path.exists());
+
+        assert!(!super::remove_flakehub_netrc_lines(&netrc_path)
+            .await
+            .unwrap());
+    }
+}
@@ -1,16 +1,25 @@
 pub(crate) mod add;
 pub(crate) mod apply;
+pub(crate) mod cache;
 pub(crate) mod completion;
 pub(crate) mod convert;
+pub(crate) mod doctor;
 pub(crate) mod eject;
+pub(crate) mod fetch;
 pub(crate) mod init;
 pub(crate) mod list;
+pub(crate) mod lock_status;
 pub(crate) mod login;
+pub(crate) mod logout;
+pub(crate) mod open;
+pub(crate) mod ping;
+pub(crate) mod remove;
 pub(crate) mod resolve;
 pub(crate) mod search;
 pub(crate) mod status;
+pub(crate) mod update;
 
-use std::{fmt::Display, process::Stdio};
+use std::{collections::VecDeque, fmt::Display, io::IsTerminal, process::Stdio};
 
 use color_eyre::eyre::WrapErr;
 use once_cell::sync::Lazy;
@@ -19,23 +28,29 @@ use reqwest::{
     Client, StatusCode,
 };
 use serde::{Deserialize, Serialize};
-use tabled::settings::{
-    style::{HorizontalLine, On, VerticalLineIter},
-    Style,
+use tabled::{
+    settings::{
+        style::{HorizontalLine, On, VerticalLineIter},
+        Style,
+    },
+    Table, Tabled,
 };
 use url::Url;
 
 use self::{
-    init::command_exists,
     list::{Flake, Org, Release, Version},
     resolve::ResolvedPath,
     search::SearchResult,
     status::TokenStatus,
 };
+use crate::cli::retry::send;
 use crate::{flakehub_url, APP_USER_AGENT};
 
 use super::error::FhError;
 
+// How many of the last lines of a failed Nix command's stderr to include in its error
+const NIX_STDERR_TAIL_LINES: usize = 10;
+
 #[allow(clippy::type_complexity)]
 static DEFAULT_STYLE: Lazy<
     Style<
@@ -66,15 +81,29 @@ pub trait CommandExecute {
 pub(crate) enum FhSubcommands {
     Add(add::AddSubcommand),
     Apply(apply::ApplySubcommand),
+    Cache(cache::CacheSubcommand),
     Completion(completion::CompletionSubcommand),
+    #[command(name = "__complete-flakes", hide = true)]
+    CompleteFlakes(completion::CompleteFlakesSubcommand),
+    #[command(name = "__complete-versions", hide = true)]
+    CompleteVersions(completion::CompleteVersionsSubcommand),
     Convert(convert::ConvertSubcommand),
+    Doctor(doctor::DoctorSubcommand),
     Eject(eject::EjectSubcommand),
+    Fetch(fetch::FetchSubcommand),
     Init(init::InitSubcommand),
     List(list::ListSubcommand),
+    LockStatus(lock_status::LockStatusSubcommand),
     Login(login::LoginSubcommand),
+    Logout(logout::LogoutSubcommand),
+    Open(open::OpenSubcommand),
+    Ping(ping::PingSubcommand),
+    Remove(remove::RemoveSubcommand),
     Resolve(resolve::ResolveSubcommand),
     Search(search::SearchSubcommand),
     Status(status::StatusSubcommand),
+    Update(update::UpdateSubcommand),
+    Whoami(status::WhoamiSubcommand),
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,13 +111,32 @@ struct ProjectMetadata {
     source_github_owner_repo_pair: String,
     source_subdirectory: Option<String>,
     version: String,
+    /// Where the project's source lives. Older FlakeHub responses don't include this, and
+    /// everything was on GitHub then.
+    #[serde(default)]
+    source_host: Option<SourceHost>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum SourceHost {
+    Github,
+    Gitlab,
+    Sourcehut,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize)]
 struct ProjectCanonicalNames {
     project: String,
-    // FIXME: detect Nix version and strip .tar.gz if it supports it
     pretty_download_url: url::Url,
+    /// Where the project's source lives, like [`ProjectMetadata::source_host`].
+    #[serde(default)]
+    source_host: Option<SourceHost>,
+    /// The subdirectory of the repository that the project was published from, if any.
+    #[serde(default)]
+    source_subdirectory: Option<String>,
 }
 
 pub(crate) struct FlakeHubClient;
@@ -97,30 +145,64 @@ impl FlakeHubClient {
     pub(crate) async fn search(
         api_addr: &str,
         query: String,
+        limit: Option<usize>,
     ) -> Result<Vec<SearchResult>, FhError> {
         let url = flakehub_url!(api_addr, "search");
-        let params = vec![("q", query)];
-        get_with_params(url, params, false).await
+        let mut params = vec![("q", query)];
+        if let Some(limit) = limit {
+            params.push(("limit", limit.to_string()));
+        }
+        // Without a token this is the same as an anonymous search, so only logged-in users see
+        // the private flakes they have access to
+        get_with_params(url, params, true).await
     }
 
-    async fn flakes(api_addr: &str) -> Result<Vec<Flake>, FhError> {
+    async fn flakes(
+        api_addr: &str,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<Flake>, FhError> {
         let url = flakehub_url!(api_addr, "flakes");
-        get(url, true).await
+        get_with_params(url, window_params(limit, offset), true).await
     }
 
-    async fn flakes_by_label(api_addr: &str, label: &str) -> Result<Vec<Flake>, FhError> {
+    async fn flakes_page(
+        api_addr: &str,
+        page: usize,
+        per_page: usize,
+    ) -> Result<Vec<Flake>, FhError> {
+        let url = flakehub_url!(api_addr, "flakes");
+        let params = vec![
+            ("page", page.to_string()),
+            ("per_page", per_page.to_string()),
+        ];
+        get_with_params(url, params, true).await
+    }
+
+    async fn flakes_by_label(
+        api_addr: &str,
+        label: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Flake>, FhError> {
         let url = flakehub_url!(api_addr, "label", label);
-        get(url, true).await
+        get_with_params(url, window_params(limit, None), true).await
     }
 
-    async fn releases(api_addr: &str, org: &str, project: &str) -> Result<Vec<Release>, FhError> {
+    async fn releases(
+        api_addr: &str,
+        org: &str,
+        project: &str,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<Release>, FhError> {
         let url = flakehub_url!(api_addr, "f", org, project, "releases");
-        get(url, true).await
+        get_with_params(url, window_params(limit, offset), true).await
     }
 
-    async fn orgs(api_addr: &str) -> Result<Vec<Org>, FhError> {
+    async fn orgs(api_addr: &str, limit: Option<usize>) -> Result<Vec<Org>, FhError> {
         let url = flakehub_url!(api_addr, "orgs");
-        let params = vec![("include_public", String::from("true"))];
+        let mut params = vec![("include_public", String::from("true"))];
+        params.extend(window_params(limit, None));
         get_with_params(url, params, true).await
     }
 
@@ -129,10 +211,12 @@ impl FlakeHubClient {
         org: &str,
         project: &str,
         constraint: &str,
+        limit: Option<usize>,
+        offset: Option<usize>,
     ) -> Result<Vec<Version>, FhError> {
         let version = urlencoding::encode(constraint);
         let url = flakehub_url!(api_addr, "version", "resolve", org, project, &version);
-        get(url, true).await
+        get_with_params(url, window_params(limit, offset), true).await
     }
 
     async fn metadata(
@@ -144,19 +228,33 @@ impl FlakeHubClient {
         let url = flakehub_url!(api_addr, "version", org, project, version);
         let client = make_base_client(true).await?;
 
-        let res = client.get(url.to_string()).send().await?;
+        let res = send(client.get(url.to_string())).await?;
+
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(FhError::NotAuthorized(String::from("flake")).into());
+        }
 
         // Enrich the CLI error text with the error returned by FlakeHub
         if let Err(e) = res.error_for_status_ref() {
-            let err_text = res.text().await?;
+            let err_text = res.text().await.map_err(FhError::from)?;
             return Err(e).wrap_err(err_text)?;
         };
 
-        let res = res.json::<ProjectMetadata>().await?;
+        let res = res.json::<ProjectMetadata>().await.map_err(FhError::from)?;
 
         Ok(res)
     }
 
+    async fn outputs(
+        api_addr: &str,
+        org: &str,
+        project: &str,
+        version_constraint: &str,
+    ) -> Result<Vec<resolve::FlakeOutput>, FhError> {
+        let url = flakehub_url!(api_addr, "f", org, project, version_constraint, "outputs");
+        get(url, true).await
+    }
+
     async fn resolve(
         api_addr: &str,
         output_ref: &FlakeOutputRef,
@@ -169,15 +267,15 @@ impl FlakeHubClient {
             ref attr_path,
         } = output_ref;
 
-        let mut url = flakehub_url!(
-            api_addr,
-            "f",
-            org,
-            flake,
-            version_constraint,
-            "output",
-            attr_path
-        );
+        // FlakeHub reads a bare version like `0.1.5` as "compatible with 0.1.5", so pin exact
+        // versions explicitly to make sure we don't get a newer release
+        let exact_version = output_ref.exact_version();
+        let version_req = match &exact_version {
+            Some(version) => format!("={version}"),
+            None => version_constraint.to_string(),
+        };
+
+        let mut url = flakehub_url!(api_addr, "f", org, flake, &version_req, "output", attr_path);
 
         if include_token {
             url.set_query(Some("include_token=true"));
@@ -185,19 +283,23 @@ impl FlakeHubClient {
 
         let client = make_base_client(true).await?;
 
-        match client.get(url).send().await {
+        match send(client.get(url)).await {
             Ok(res) => match res.status() {
                 StatusCode::OK => Ok(res.json().await?),
-                StatusCode::NOT_FOUND => Err(FhError::NotFound(
-                    "output reference".to_string(),
-                    output_ref.to_string(),
-                )),
+                StatusCode::NOT_FOUND => Err(match exact_version {
+                    Some(version) => {
+                        FhError::NotFound("version".to_string(), format!("{org}/{flake}/{version}"))
+                    }
+                    None => {
+                        FhError::NotFound("output reference".to_string(), output_ref.to_string())
+                    }
+                }),
                 StatusCode::UNAUTHORIZED => {
                     Err(FhError::NotAuthorized("output reference".to_string()))
                 }
                 status => Err(FhError::MiscHttp(status)),
             },
-            Err(e) => Err(e.into()),
+            Err(e) => Err(e),
         }
     }
 
@@ -206,35 +308,68 @@ impl FlakeHubClient {
         org: &str,
         project: &str,
         version: Option<&str>,
-    ) -> color_eyre::Result<(String, url::Url)> {
+    ) -> color_eyre::Result<ProjectCanonicalNames> {
         let url = match version {
             Some(version) => flakehub_url!(api_addr, "version", org, project, version),
             None => flakehub_url!(api_addr, "f", org, project),
         };
         let client = make_base_client(true).await?;
-        let res = client.get(url.to_string()).send().await?;
+        let res = send(client.get(url.to_string())).await?;
+
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(FhError::NotAuthorized(String::from("flake")).into());
+        }
 
         // Enrich the CLI error text with the error returned by FlakeHub
         if let Err(e) = res.error_for_status_ref() {
-            let err_text = res.text().await?;
+            let err_text = res.text().await.map_err(FhError::from)?;
             return Err(e).wrap_err(err_text)?;
         };
 
-        let res = res.json::<ProjectCanonicalNames>().await?;
-        Ok((res.project, res.pretty_download_url))
+        let mut res = res
+            .json::<ProjectCanonicalNames>()
+            .await
+            .map_err(FhError::from)?;
+
+        let supports_bare_tarball_urls = crate::shared::nix_info::nix_info()
+            .await
+            .is_some_and(|info| info.supports_bare_tarball_urls());
+        if supports_bare_tarball_urls {
+            if let Some(path) = res.pretty_download_url.path().strip_suffix(".tar.gz") {
+                let path = path.to_string();
+                res.pretty_download_url.set_path(&path);
+            }
+        }
+
+        Ok(res)
+    }
+
+    // Time an anonymous request to the API. Any response at all, even an unauthorized one, means
+    // the API is reachable.
+    async fn ping(api_addr: &str) -> Result<(StatusCode, std::time::Duration), FhError> {
+        let url = flakehub_url!(api_addr, "cli", "status");
+        let client = make_base_client(false).await?;
+
+        let start = std::time::Instant::now();
+        let res = client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await?;
+
+        Ok((res.status(), start.elapsed()))
     }
 
     async fn auth_status(api_addr: &str, token: &str) -> color_eyre::Result<TokenStatus> {
         let url = flakehub_url!(api_addr, "cli", "status");
 
-        let res = reqwest::Client::builder()
+        let request = reqwest::Client::builder()
             .user_agent(APP_USER_AGENT)
+            .timeout(crate::cli::timeout::timeout())
             .build()?
             .get(url)
-            .header(AUTHORIZATION, &format!("Bearer {token}"))
-            .send()
-            .await
-            .wrap_err("Failed to send request")?;
+            .header(AUTHORIZATION, &format!("Bearer {token}"));
+        let res = send(request).await.wrap_err("Failed to send request")?;
 
         if res.status() == 401 {
             return Err(color_eyre::eyre::eyre!(
@@ -245,7 +380,7 @@ impl FlakeHubClient {
         let res = res
             .error_for_status()
             .wrap_err("Request was unsuccessful")?;
-        let token_status: TokenStatus = res.json().await.wrap_err(
+        let token_status: TokenStatus = res.json().await.map_err(FhError::from).wrap_err(
             "Failed to get TokenStatus from response (wasn't JSON, or was invalid JSON?)",
         )?;
 
@@ -256,7 +391,25 @@ impl FlakeHubClient {
 async fn get<T: for<'de> Deserialize<'de>>(url: Url, authenticated: bool) -> Result<T, FhError> {
     let client = make_base_client(authenticated).await?;
 
-    Ok(client.get(url).send().await?.json::<T>().await?)
+    let res = send(client.get(url)).await?;
+    if res.status() == StatusCode::UNAUTHORIZED {
+        return Err(FhError::NotAuthorized(String::from("resource")));
+    }
+
+    Ok(res.json::<T>().await?)
+}
+
+// Asks FlakeHub for at most `limit` results, skipping the first `offset`. Servers that don't support
+// `limit` send everything, so callers still truncate what they get back.
+fn window_params(limit: Option<usize>, offset: Option<usize>) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    if let Some(limit) = limit {
+        params.push(("limit", limit.to_string()));
+    }
+    if let Some(offset) = offset {
+        params.push(("offset", offset.to_string()));
+    }
+    params
 }
 
 async fn get_with_params<T: for<'de> Deserialize<'de>>(
@@ -266,13 +419,12 @@ async fn get_with_params<T: for<'de> Deserialize<'de>>(
 ) -> Result<T, FhError> {
     let client = make_base_client(authenticated).await?;
 
-    Ok(client
-        .get(url)
-        .query(&params)
-        .send()
-        .await?
-        .json::<T>()
-        .await?)
+    let res = send(client.get(url).query(&params)).await?;
+    if res.status() == StatusCode::UNAUTHORIZED {
+        return Err(FhError::NotAuthorized(String::from("resource")));
+    }
+
+    Ok(res.json::<T>().await?)
 }
 
 pub(crate) fn print_json<T: Serialize>(value: T) -> Result<(), FhError> {
@@ -281,8 +433,82 @@ pub(crate) fn print_json<T: Serialize>(value: T) -> Result<(), FhError> {
     Ok(())
 }
 
+pub(crate) fn print_yaml<T: Serialize>(value: T) -> Result<(), FhError> {
+    print!("{}", serde_yaml_ng::to_string(&value)?);
+    Ok(())
+}
+
+/// How a subcommand prints its results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// A table for reading, the default in a terminal.
+    Table,
+    /// JSON, the same as `--json`.
+    Json,
+    /// CSV, the default when the output isn't a terminal.
+    Csv,
+    /// YAML, serialized the same way as JSON.
+    Yaml,
+}
+
+impl OutputFormat {
+    /// The format given with `--output`, or JSON if `--json` was, or else whatever suits stdout.
+    pub(crate) fn choose(output: Option<Self>, json: bool) -> Self {
+        match output {
+            Some(output) => output,
+            None if json => Self::Json,
+            None if std::io::stdout().is_terminal() => Self::Table,
+            None => Self::Csv,
+        }
+    }
+
+    /// Prints `rows`, which are serialized as they are for JSON too.
+    pub(crate) fn print_rows<R: Tabled + Serialize>(self, rows: Vec<R>) -> color_eyre::Result<()> {
+        self.print(rows, std::convert::identity)
+    }
+
+    /// Prints `items` as JSON or YAML, or turns each into a row with `into_row` for a table or CSV.
+    pub(crate) fn print<T: Serialize, R: Tabled + Serialize>(
+        self,
+        items: Vec<T>,
+        into_row: impl FnMut(T) -> R,
+    ) -> color_eyre::Result<()> {
+        self.write(std::io::stdout().lock(), items, into_row)
+    }
+
+    /// Writes `items` to `out` the same way that [`OutputFormat::print`] prints them. JSON and YAML
+    /// always get a list, even an empty one, but there's no table without any rows.
+    pub(crate) fn write<T: Serialize, R: Tabled + Serialize>(
+        self,
+        mut out: impl std::io::Write,
+        items: Vec<T>,
+        into_row: impl FnMut(T) -> R,
+    ) -> color_eyre::Result<()> {
+        match self {
+            Self::Json => writeln!(out, "{}", serde_json::to_string(&items)?)?,
+            Self::Yaml => write!(out, "{}", serde_yaml_ng::to_string(&items)?)?,
+            Self::Table if items.is_empty() => {}
+            Self::Table => {
+                let mut table = Table::new(items.into_iter().map(into_row));
+                table.with(DEFAULT_STYLE.clone());
+                writeln!(out, "{table}")?;
+            }
+            Self::Csv => {
+                let mut writer = csv::Writer::from_writer(out);
+                for row in items.into_iter().map(into_row) {
+                    writer.serialize(row)?;
+                }
+                writer.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 // Parses a flake reference as a string to construct paths of the form:
 // https://api.flakehub.com/f/{org}/{flake}/{version_constraint}/output/{attr_path}
+#[derive(Clone)]
 struct FlakeOutputRef {
     org: String,
     project: String,
@@ -300,6 +526,14 @@ impl Display for FlakeOutputRef {
     }
 }
 
+impl FlakeOutputRef {
+    /// The version this ref is pinned to, if it names an exact version (like `0.1.5`) rather than a
+    /// wildcard or a range.
+    fn exact_version(&self) -> Option<semver::Version> {
+        semver::Version::parse(&self.version_constraint).ok()
+    }
+}
+
 impl TryFrom<String> for FlakeOutputRef {
     type Error = FhError;
 
@@ -358,30 +592,37 @@ async fn make_base_client(_authenticated: bool) -> Result<Client, FhError> {
 
     Ok(reqwest::Client::builder()
         .user_agent(APP_USER_AGENT)
+        .timeout(crate::cli::timeout::timeout())
         .default_headers(headers)
         .build()?)
 }
 
+/// Serves `router` over real HTTP, so that tests can point fh's FlakeHub client at its address.
+#[cfg(test)]
+pub(crate) fn test_server(router: axum::Router) -> axum_test::TestServer {
+    let test_server_config = axum_test::TestServerConfig::builder()
+        .http_transport()
+        .build();
+    axum_test::TestServer::new_with_config(router.into_make_service(), test_server_config).unwrap()
+}
+
 #[cfg(not(test))]
 async fn make_base_client(authenticated: bool) -> Result<Client, FhError> {
-    use self::login::user_auth_token_read_path;
-
     let mut headers = HeaderMap::new();
     headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
 
     if authenticated {
-        if let Ok(token) = tokio::fs::read_to_string(user_auth_token_read_path().await?).await {
-            if !token.is_empty() {
-                headers.insert(
-                    AUTHORIZATION,
-                    HeaderValue::from_str(&format!("Bearer {}", token.trim()))?,
-                );
-            }
+        if let Some(token) = crate::cli::token::read_token().await? {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", token.token))?,
+            );
         }
     }
 
     Ok(reqwest::Client::builder()
         .user_agent(APP_USER_AGENT)
+        .timeout(crate::cli::timeout::timeout())
         .default_headers(headers)
         .build()?)
 }
@@ -405,16 +646,104 @@ macro_rules! flakehub_url {
     }};
 }
 
+/// Whether to run a Nix command with `sudo`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SudoChoice {
+    /// Use `sudo` unless fh is already running as root
+    Auto,
+    /// Always use `sudo`
+    Always,
+    /// Never use `sudo`, like in containers or with rootless Nix
+    Never,
+}
+
+impl SudoChoice {
+    fn escalates(self, is_root: bool) -> bool {
+        match self {
+            SudoChoice::Auto => !is_root,
+            SudoChoice::Always => true,
+            SudoChoice::Never => false,
+        }
+    }
+}
+
+/// The directory of `flake_path`, in the form `nix flake lock` and friends take a local flake in. A
+/// relative path like `my-flake` would be taken for a flake in the registry, so it's made absolute.
+pub(crate) fn local_flake_dir(flake_path: &std::path::Path) -> String {
+    let flake_dir = match flake_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+
+    flake_dir
+        .canonicalize()
+        .unwrap_or_else(|_| flake_dir.to_path_buf())
+        .display()
+        .to_string()
+}
+
 fn is_root_user() -> bool {
     nix::unistd::getuid().is_root()
 }
 
-async fn nix_command(args: &[String], sudo_if_necessary: bool) -> Result<(), FhError> {
-    if !command_exists("nix") {
-        return Err(FhError::MissingExecutable("nix".to_string()));
+/// A command for running one of Nix's programs (like `nix` or `nix-store`). Every Nix subprocess
+/// should start here, so that a missing Nix is always reported the same way.
+pub(crate) fn nix_process(program: &str) -> Result<tokio::process::Command, FhError> {
+    nix_process_with_path(program, std::env::var_os("PATH").as_deref())
+}
+
+fn nix_process_with_path(
+    program: &str,
+    path: Option<&std::ffi::OsStr>,
+) -> Result<tokio::process::Command, FhError> {
+    if !executable_on_path(program, path) {
+        return Err(FhError::MissingNix(program.to_string()));
     }
 
-    let use_sudo = sudo_if_necessary && !is_root_user();
+    Ok(tokio::process::Command::new(program))
+}
+
+fn executable_on_path(program: &str, path: Option<&std::ffi::OsStr>) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let Some(path) = path else {
+        return false;
+    };
+
+    std::env::split_paths(path).any(|dir| {
+        std::fs::metadata(dir.join(program))
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    })
+}
+
+async fn nix_command(
+    args: &[String],
+    sudo: SudoChoice,
+    print_command: bool,
+) -> Result<(), FhError> {
+    run_nix_command(args, sudo, print_command, false).await
+}
+
+/// Like [`nix_command`], but Nix's stderr is also kept so that the end of it can be included in the
+/// error if the command fails. It's still shown as it arrives (above a [`Spinner`], if there is
+/// one), though Nix draws no progress bar when its stderr isn't a terminal.
+///
+/// [`Spinner`]: crate::shared::progress::Spinner
+async fn nix_command_captured(
+    args: &[String],
+    sudo: SudoChoice,
+    print_command: bool,
+) -> Result<(), FhError> {
+    run_nix_command(args, sudo, print_command, true).await
+}
+
+async fn run_nix_command(
+    args: &[String],
+    sudo: SudoChoice,
+    print_command: bool,
+    capture_stderr: bool,
+) -> Result<(), FhError> {
+    let use_sudo = sudo.escalates(is_root_user());
 
     let mut cmd = if use_sudo {
         tracing::warn!(
@@ -422,32 +751,81 @@ async fn nix_command(args: &[String], sudo_if_necessary: bool) -> Result<(), FhE
             whoami::username()
         );
 
+        // Check for Nix ourselves so that a missing Nix isn't reported as a sudo failure
+        nix_process("nix")?;
+
         let mut cmd = tokio::process::Command::new("sudo");
         cmd.arg("nix");
         cmd
     } else {
-        tokio::process::Command::new("nix")
+        nix_process("nix")?
     };
 
     cmd.args(["--extra-experimental-features", "nix-command flakes"]);
     cmd.args(args);
     cmd.stdout(Stdio::inherit());
-    cmd.stderr(Stdio::inherit());
+    cmd.stderr(if capture_stderr {
+        Stdio::piped()
+    } else {
+        Stdio::inherit()
+    });
     let cmd_str = format!("{:?}", cmd.as_std());
     tracing::debug!("Running: {:?}", cmd_str);
+    if print_command {
+        crate::shared::progress::eprintln_above_spinner(&cmd_str);
+    }
 
-    let output = cmd
-        .spawn()
-        .wrap_err("failed to spawn Nix command")?
-        .wait_with_output()
+    let mut child = cmd.spawn().wrap_err("failed to spawn Nix command")?;
+    // Read stderr while waiting, so that Nix never blocks on a full pipe
+    let stderr_tail = child
+        .stderr
+        .take()
+        .map(|stderr| tokio::spawn(tee_stderr(stderr)));
+
+    let status = child
+        .wait()
         .await
         .wrap_err("failed to wait for Nix command output")?;
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        Err(FhError::FailedNixCommand(cmd_str))
+    // Let the rest of stderr through before anything else is printed
+    let stderr_tail = match stderr_tail {
+        Some(task) => Some(Vec::from(task.await.unwrap_or_default()).join("\n")),
+        None => None,
+    };
+
+    if status.success() {
+        return Ok(());
     }
+
+    Err(FhError::FailedNixCommand(cmd_str, stderr_tail))
+}
+
+// Copies a Nix command's stderr through to fh's own as it arrives, and returns its last
+// `NIX_STDERR_TAIL_LINES` lines.
+async fn tee_stderr(stderr: impl tokio::io::AsyncRead + Unpin) -> VecDeque<String> {
+    use tokio::io::AsyncBufReadExt as _;
+
+    let mut reader = tokio::io::BufReader::new(stderr);
+    let mut tail = VecDeque::with_capacity(NIX_STDERR_TAIL_LINES);
+    let mut line = Vec::new();
+
+    // Nix's output isn't guaranteed to be UTF-8, so it's read as bytes
+    while reader
+        .read_until(b'\n', &mut line)
+        .await
+        .is_ok_and(|read| read > 0)
+    {
+        let text = String::from_utf8_lossy(&line).trim_end().to_string();
+        crate::shared::progress::eprintln_above_spinner(&text);
+
+        if tail.len() == NIX_STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(text);
+        line.clear();
+    }
+
+    tail
 }
 
 fn parse_flake_output_ref(
@@ -496,11 +874,143 @@ fn validate_segment(s: &str) -> Result<(), FhError> {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn local_flake_dir() {
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+
+        for flake_path in ["flake.nix", "./flake.nix"] {
+            assert_eq!(
+                super::local_flake_dir(std::path::Path::new(flake_path)),
+                cwd.display().to_string()
+            );
+        }
+
+        // Paths that don't exist are left as they are
+        assert_eq!(
+            super::local_flake_dir(std::path::Path::new("/nonexistent/my-flake/flake.nix")),
+            "/nonexistent/my-flake"
+        );
+    }
+
+    #[test]
+    fn missing_nix() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = tempfile::tempdir().unwrap();
+        let nix = dir.path().join("nix");
+        std::fs::write(&nix, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&nix, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let nix_store = dir.path().join("nix-store");
+        std::fs::write(&nix_store, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&nix_store, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let path = std::env::join_paths(["/does/not/exist", dir.path().to_str().unwrap()]).unwrap();
+        assert!(super::nix_process_with_path("nix", Some(&path)).is_ok());
+
+        // Not executable
+        assert!(matches!(
+            super::nix_process_with_path("nix-store", Some(&path)),
+            Err(super::FhError::MissingNix(program)) if program == "nix-store"
+        ));
+
+        let Err(err) = super::nix_process_with_path("nix", None) else {
+            panic!("found nix without a PATH");
+        };
+        assert!(err
+            .to_string()
+            .contains("https://determinate.systems/nix-installer"));
+    }
+
+    #[tokio::test]
+    async fn unauthorized_explains_token_precedence() {
+        let router = axum::Router::new().route(
+            "/orgs",
+            axum::routing::get(|| async { axum::http::StatusCode::UNAUTHORIZED }),
+        );
+        let test_server = super::test_server(router);
+        let server_url = test_server.server_address().unwrap();
+
+        let Err(err) = super::FlakeHubClient::orgs(server_url.as_str(), None).await else {
+            panic!("an unauthorized request succeeded");
+        };
+        assert!(matches!(err, super::FhError::NotAuthorized(_)));
+        assert!(err.to_string().contains("FH_TOKEN"));
+    }
+
+    #[tokio::test]
+    async fn tee_stderr_keeps_the_tail() {
+        let stderr: String = (1..=15).map(|i| format!("line {i}\n")).collect();
+        let tail = super::tee_stderr(stderr.as_bytes()).await;
+        assert_eq!(tail.len(), super::NIX_STDERR_TAIL_LINES);
+        assert_eq!(tail.front().unwrap(), "line 6");
+        assert_eq!(tail.back().unwrap(), "line 15");
+
+        // A last line without a newline, and bytes that aren't UTF-8, are still kept
+        let tail = super::tee_stderr(&b"error: \xff\nno newline"[..]).await;
+        assert_eq!(tail, ["error: \u{fffd}", "no newline"]);
+
+        let err = super::FhError::FailedNixCommand(
+            String::from("nix build"),
+            Some(Vec::from(tail).join("\n")),
+        );
+        assert!(err
+            .to_string()
+            .ends_with("it ended with:\nerror: \u{fffd}\nno newline"));
+        let err = super::FhError::FailedNixCommand(String::from("nix build"), None);
+        assert!(err
+            .to_string()
+            .ends_with("check prior Nix output for details"));
+    }
+
+    #[test]
+    fn sudo_choice() {
+        use super::SudoChoice;
+
+        assert!(SudoChoice::Auto.escalates(false));
+        assert!(!SudoChoice::Auto.escalates(true));
+        assert!(SudoChoice::Always.escalates(true));
+        assert!(!SudoChoice::Never.escalates(false));
+    }
+
+    #[test]
+    fn output_format() {
+        use super::OutputFormat;
+
+        assert_eq!(
+            OutputFormat::choose(Some(OutputFormat::Csv), true),
+            OutputFormat::Csv
+        );
+        assert_eq!(OutputFormat::choose(None, true), OutputFormat::Json);
+    }
+
+    #[test]
+    fn output_format_without_results() {
+        use super::OutputFormat;
+
+        #[derive(serde::Serialize, tabled::Tabled)]
+        struct Row {
+            name: String,
+        }
+
+        let write = |format: OutputFormat| {
+            let mut out = Vec::new();
+            format
+                .write(&mut out, Vec::<Row>::new(), std::convert::identity)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(write(OutputFormat::Json), "[]\n");
+        assert_eq!(write(OutputFormat::Yaml), "[]\n");
+        assert_eq!(write(OutputFormat::Table), "");
+        assert_eq!(write(OutputFormat::Csv), "");
+    }
+
     #[test]
     fn flakehub_url_macro() {
         let root = "https://flakehub.com";
 
-        for (provided, expected) in vec![
+        for (provided, expected) in [
             (
                 flakehub_url!(root, "flake", "DeterminateSystems", "fh"),
                 "https://flakehub.com/flake/DeterminateSystems/fh",
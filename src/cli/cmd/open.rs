@@ -0,0 +1,80 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+use color_eyre::eyre::WrapErr;
+use url::Url;
+
+use super::{list::FLAKEHUB_WEB_ROOT, validate_segment, CommandExecute};
+use crate::{cli::error::FhError, flakehub_url};
+
+/// Opens a flake's FlakeHub page in your web browser.
+#[derive(Debug, Parser)]
+pub(crate) struct OpenSubcommand {
+    /// The flake to open, like `NixOS/nixpkgs`, or `NixOS/nixpkgs/0.2405.0` for a specific release.
+    flake_ref: String,
+
+    /// Print the URL instead of opening it.
+    #[clap(long)]
+    print: bool,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for OpenSubcommand {
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let url = flake_page_url(&self.flake_ref)?;
+
+        if self.print {
+            println!("{url}");
+        } else {
+            ::open::that(url.as_str())
+                .wrap_err_with(|| format!("Opening {url} in your browser"))?;
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+// `{org}/{project}` -> https://flakehub.com/flake/{org}/{project}
+// `{org}/{project}/{version}` -> https://flakehub.com/flake/{org}/{project}/{version}
+fn flake_page_url(flake_ref: &str) -> Result<Url, FhError> {
+    let segments = flake_ref.split('/').collect::<Vec<_>>();
+    if !(2..=3).contains(&segments.len()) || segments.iter().any(|segment| segment.is_empty()) {
+        return Err(FhError::FlakeParse(format!(
+            "flake ref {flake_ref} invalid; must be of the form {{org}}/{{project}} or \
+            {{org}}/{{project}}/{{version}}"
+        )));
+    }
+    for segment in &segments {
+        validate_segment(segment)?;
+    }
+
+    let mut url = flakehub_url!(FLAKEHUB_WEB_ROOT, "flake");
+    url.path_segments_mut()
+        .expect("URL cannot be a base (this should never happen)")
+        .extend(segments);
+
+    Ok(url)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn flake_page_url() {
+        for (flake_ref, expected) in [
+            ("NixOS/nixpkgs", "https://flakehub.com/flake/NixOS/nixpkgs"),
+            (
+                "DeterminateSystems/fh/0.1.21",
+                "https://flakehub.com/flake/DeterminateSystems/fh/0.1.21",
+            ),
+        ] {
+            assert_eq!(super::flake_page_url(flake_ref).unwrap().as_str(), expected);
+        }
+
+        for flake_ref in ["NixOS", "NixOS/", "NixOS/nix pkgs", "a/b/c/d"] {
+            assert!(
+                super::flake_page_url(flake_ref).is_err(),
+                "{flake_ref} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+use serde::Serialize;
+
+use super::{print_json, CommandExecute, FlakeHubClient};
+
+/// Check whether the FlakeHub API is reachable, and how quickly it responds.
+#[derive(Debug, Parser)]
+pub(crate) struct PingSubcommand {
+    /// Output the result as JSON.
+    #[arg(long, env = "FH_OUTPUT_JSON")]
+    json: bool,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PingResult {
+    api_addr: url::Url,
+    reachable: bool,
+    latency_ms: Option<u128>,
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl std::fmt::Display for PingResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "API address: {}", self.api_addr)?;
+        writeln!(f, "Reachable: {}", self.reachable)?;
+        if let Some(status) = self.status {
+            writeln!(f, "HTTP status: {status}")?;
+        }
+        if let Some(latency_ms) = self.latency_ms {
+            writeln!(f, "Latency: {latency_ms} ms")?;
+        }
+        if let Some(error) = &self.error {
+            writeln!(f, "Error: {error}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for PingSubcommand {
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let result = ping(self.api_addr).await;
+        let reachable = result.reachable;
+
+        if self.json {
+            print_json(result)?;
+        } else {
+            print!("{result}");
+        }
+
+        Ok(if reachable {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+}
+
+async fn ping(api_addr: url::Url) -> PingResult {
+    match FlakeHubClient::ping(api_addr.as_ref()).await {
+        Ok((status, latency)) => PingResult {
+            api_addr,
+            // The endpoint wants a token, so a 401 still means the API is up
+            reachable: !status.is_server_error(),
+            latency_ms: Some(latency.as_millis()),
+            status: Some(status.as_u16()),
+            error: None,
+        },
+        Err(e) => PingResult {
+            api_addr,
+            reachable: false,
+            latency_ms: None,
+            status: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    fn test_server(status: StatusCode) -> axum_test::TestServer {
+        let router = axum::Router::new().route(
+            "/cli/status",
+            axum::routing::get(move || async move { status.into_response() }),
+        );
+        crate::cli::cmd::test_server(router)
+    }
+
+    #[tokio::test]
+    async fn ping_reachable_api() {
+        let test_server = test_server(StatusCode::UNAUTHORIZED);
+        let server_url = test_server.server_address().unwrap();
+
+        let result = super::ping(server_url.clone()).await;
+        assert!(result.reachable);
+        assert_eq!(result.status, Some(401));
+        assert!(result.latency_ms.is_some());
+        assert_eq!(result.api_addr, server_url);
+    }
+
+    #[tokio::test]
+    async fn ping_unhealthy_api() {
+        let test_server = test_server(StatusCode::SERVICE_UNAVAILABLE);
+        let server_url = test_server.server_address().unwrap();
+
+        let result = super::ping(server_url).await;
+        assert!(!result.reachable);
+        assert_eq!(result.status, Some(503));
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["reachable"], false);
+        assert_eq!(json["status"], 503);
+    }
+
+    #[tokio::test]
+    async fn ping_unreachable_api() {
+        // Nothing listens on the discard port
+        let result = super::ping(url::Url::parse("http://127.0.0.1:9").unwrap()).await;
+        assert!(!result.reachable);
+        assert_eq!(result.status, None);
+        assert!(result.error.is_some());
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use super::add::{
+    flake::remove_flake_input, is_stdin, load_flake, verify_edited_flake, write_edited_flake,
+};
+use super::CommandExecute;
+
+/// Removes a flake input from your flake.nix.
+#[derive(Parser, Debug)]
+pub(crate) struct RemoveSubcommand {
+    /// The flake.nix to modify, or `-` to read it from stdin (implies `--dry-run`).
+    #[clap(long, default_value = "./flake.nix")]
+    pub(crate) flake_path: PathBuf,
+    /// The name of the flake input to remove.
+    pub(crate) input_name: String,
+    /// Print to stdout the new flake.nix contents instead of writing it to disk.
+    #[clap(long)]
+    pub(crate) dry_run: bool,
+    /// Replace any invalid UTF-8 in the flake.nix instead of refusing to edit it.
+    #[clap(long)]
+    pub(crate) lossy: bool,
+    /// Write the new flake.nix even if it no longer parses.
+    #[clap(long)]
+    pub(crate) force: bool,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for RemoveSubcommand {
+    async fn execute(mut self) -> color_eyre::Result<ExitCode> {
+        let from_stdin = is_stdin(&self.flake_path);
+        // There's no file to write the result back to when the flake came from stdin
+        if from_stdin {
+            self.dry_run = true;
+        }
+
+        if !from_stdin && !self.flake_path.exists() {
+            return Err(color_eyre::eyre::eyre!(
+                "the flake at {} did not exist",
+                self.flake_path.display()
+            ));
+        }
+
+        let (flake_contents, parsed) = load_flake(&self.flake_path, self.lossy).await?;
+
+        let new_flake_contents =
+            remove_flake_input(&parsed.expression, &flake_contents, &self.input_name)?;
+
+        if self.dry_run {
+            println!("{new_flake_contents}");
+        } else {
+            if !self.force {
+                verify_edited_flake(&parsed.expression, &new_flake_contents)?;
+            }
+
+            write_edited_flake(&self.flake_path, &flake_contents, &new_flake_contents).await?;
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
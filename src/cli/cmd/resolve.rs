@@ -3,19 +3,28 @@ use std::process::ExitCode;
 use clap::Parser;
 use serde::{Deserialize, Serialize};
 
-use super::{parse_flake_output_ref, print_json, CommandExecute, FlakeHubClient};
+use super::{parse_flake_output_ref, print_json, CommandExecute, FlakeHubClient, FlakeOutputRef};
 
 /// Resolves a FlakeHub flake reference into a store path.
 #[derive(Debug, Parser)]
 pub(crate) struct ResolveSubcommand {
     /// The FlakeHub flake reference to resolve.
     /// References must be of this form: {org}/{flake}/{version_req}#{attr_path}
+    /// `github:{owner}/{repo}[/{ref}]#{attr_path}` and `gitlab:` references are also accepted if
+    /// the repository is published to FlakeHub.
+    /// A `*` in the attribute path matches any single attribute name, and every matching output
+    /// is resolved, e.g. `{org}/{flake}/{version_req}#packages.x86_64-linux.*`.
     flake_ref: String,
 
     /// Output the result as JSON displaying the store path plus the original attribute path.
     #[arg(long, env = "FH_OUTPUT_JSON")]
     json: bool,
 
+    /// Include a token that can only substitute the resolved store path's closure from FlakeHub
+    /// Cache in the JSON output. Treat it like a password.
+    #[arg(long, requires = "json")]
+    include_token: bool,
+
     #[clap(from_global)]
     api_addr: url::Url,
 
@@ -23,29 +32,70 @@ pub(crate) struct ResolveSubcommand {
     frontend_addr: url::Url,
 }
 
+#[derive(Deserialize, Serialize)]
+pub(crate) struct FlakeOutput {
+    pub(crate) attribute_path: String,
+}
+
 #[derive(Deserialize, Serialize)]
 pub(crate) struct ResolvedPath {
     // The original attribute path, i.e. attr_path in {org}/{flake}/{version}#{attr_path}
     attribute_path: String,
     // The resolved store path
     pub(crate) store_path: String,
-    // A JWT that can only substitute the closure of this store path
+    // A JWT that can only substitute the closure of this store path, which is `null` unless one
+    // was asked for
+    #[serde(default)]
     pub(crate) token: Option<String>,
 }
 
+// Written by hand so that the token never ends up in logs
+impl std::fmt::Debug for ResolvedPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResolvedPath")
+            .field("attribute_path", &self.attribute_path)
+            .field("store_path", &self.store_path)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
 #[async_trait::async_trait]
 impl CommandExecute for ResolveSubcommand {
     #[tracing::instrument(skip_all)]
     async fn execute(self) -> color_eyre::Result<ExitCode> {
-        let output_ref = parse_flake_output_ref(&self.frontend_addr, &self.flake_ref)?;
+        let flake_ref = match forge_ref_to_flakehub_ref(&self.api_addr, &self.flake_ref).await? {
+            Some(flakehub_ref) => {
+                tracing::debug!("Resolving {} as {}", self.flake_ref, flakehub_ref);
+                flakehub_ref
+            }
+            None => self.flake_ref.clone(),
+        };
+        let output_ref = parse_flake_output_ref(&self.frontend_addr, &flake_ref)?;
+
+        if output_ref.attr_path.contains('*') {
+            let resolved_paths =
+                resolve_glob(self.api_addr.as_ref(), &output_ref, self.include_token).await?;
+
+            if self.json {
+                print_json(resolved_paths)?;
+            } else {
+                for resolved_path in resolved_paths {
+                    println!("{}", resolved_path.store_path);
+                }
+            }
+
+            return Ok(ExitCode::SUCCESS);
+        }
 
         let resolved_path =
-            FlakeHubClient::resolve(self.api_addr.as_ref(), &output_ref, false).await?;
+            FlakeHubClient::resolve(self.api_addr.as_ref(), &output_ref, self.include_token)
+                .await?;
 
         tracing::debug!(
-            "Successfully resolved reference {} to path {}",
+            "Successfully resolved reference {} to {:?}",
             &output_ref,
-            &resolved_path.store_path
+            &resolved_path
         );
 
         if self.json {
@@ -57,3 +107,301 @@ impl CommandExecute for ResolveSubcommand {
         Ok(ExitCode::SUCCESS)
     }
 }
+
+// Resolves every output whose attribute path matches the glob in `output_ref`'s attribute path.
+#[tracing::instrument(skip_all)]
+async fn resolve_glob(
+    api_addr: &str,
+    output_ref: &FlakeOutputRef,
+    include_token: bool,
+) -> color_eyre::Result<Vec<ResolvedPath>> {
+    let outputs = FlakeHubClient::outputs(
+        api_addr,
+        &output_ref.org,
+        &output_ref.project,
+        &output_ref.version_constraint,
+    )
+    .await?;
+    let matches = matching_outputs(&output_ref.attr_path, &outputs);
+
+    if matches.is_empty() {
+        return Err(color_eyre::eyre::eyre!(
+            "no outputs of {}/{}/{} match `{}`",
+            output_ref.org,
+            output_ref.project,
+            output_ref.version_constraint,
+            output_ref.attr_path
+        ));
+    }
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for attr_path in matches {
+        let api_addr = api_addr.to_string();
+        let output_ref = FlakeOutputRef {
+            attr_path,
+            ..output_ref.clone()
+        };
+
+        tasks.spawn(
+            async move { FlakeHubClient::resolve(&api_addr, &output_ref, include_token).await },
+        );
+    }
+
+    let mut resolved_paths = Vec::new();
+    while let Some(resolved_path) = tasks.join_next().await {
+        resolved_paths.push(resolved_path??);
+    }
+    resolved_paths.sort_by(|a, b| a.attribute_path.cmp(&b.attribute_path));
+
+    Ok(resolved_paths)
+}
+
+// The attribute paths of `outputs` that match `glob`, where `*` matches any single attribute name
+// (or part of one), but never crosses a `.`.
+fn matching_outputs(glob: &str, outputs: &[FlakeOutput]) -> Vec<String> {
+    let glob: Vec<&str> = glob.split('.').collect();
+
+    outputs
+        .iter()
+        .filter(|output| {
+            let attr_path: Vec<&str> = output.attribute_path.split('.').collect();
+
+            attr_path.len() == glob.len()
+                && glob
+                    .iter()
+                    .zip(attr_path.iter())
+                    .all(|(pattern, name)| segment_matches(pattern, name))
+        })
+        .map(|output| output.attribute_path.clone())
+        .collect()
+}
+
+fn segment_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one item
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` at all, so it has to be an exact match
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+// Turns a `github:` or `gitlab:` reference into the FlakeHub reference for the same repository,
+// or returns `None` if the reference isn't one of those. FlakeHub projects are looked up by
+// owner and repository name, so GitLab references only match projects published from GitLab.
+#[tracing::instrument(skip_all)]
+async fn forge_ref_to_flakehub_ref(
+    api_addr: &url::Url,
+    flake_ref: &str,
+) -> color_eyre::Result<Option<String>> {
+    let (forge_ref, attr_path) = match flake_ref.split_once('#') {
+        Some((forge_ref, attr_path)) => (forge_ref, Some(attr_path)),
+        None => (flake_ref, None),
+    };
+
+    let flakehub_url = if forge_ref.starts_with("github:") {
+        crate::cli::cmd::convert::convert_github_input_to_flakehub(forge_ref.parse()?, api_addr)
+            .await?
+    } else if forge_ref.starts_with("gitlab:") {
+        crate::cli::cmd::convert::convert_gitlab_input_to_flakehub(forge_ref.parse()?, api_addr)
+            .await?
+    } else {
+        return Ok(None);
+    };
+
+    let flakehub_url = flakehub_url.map_err(|reason| {
+        color_eyre::eyre::eyre!("{forge_ref} can't be resolved on FlakeHub: {reason}")
+    })?;
+
+    let release_ref = flakehub_url_to_release_ref(&flakehub_url)?;
+
+    Ok(Some(match attr_path {
+        Some(attr_path) => format!("{release_ref}#{attr_path}"),
+        None => release_ref,
+    }))
+}
+
+// `https://flakehub.com/f/{org}/{project}/{version}.tar.gz` -> `{org}/{project}/{version}`
+fn flakehub_url_to_release_ref(flakehub_url: &url::Url) -> color_eyre::Result<String> {
+    match flakehub_url.path().split('/').collect::<Vec<_>>()[..] {
+        ["", "f", org, project, version] => {
+            let version = version.strip_suffix(".tar.gz").unwrap_or(version);
+            Ok(format!("{org}/{project}/{version}"))
+        }
+        _ => Err(color_eyre::eyre::eyre!(
+            "FlakeHub URL {flakehub_url} did not match the expected format of `/f/org/project/version`"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FlakeOutput;
+
+    #[test]
+    fn glob_matching() {
+        let outputs: Vec<FlakeOutput> = [
+            "packages.x86_64-linux.default",
+            "packages.x86_64-linux.fh",
+            "packages.aarch64-darwin.default",
+            "packages.aarch64-darwin.fh",
+            "devShells.x86_64-linux.default",
+            "nixosConfigurations.web-1",
+            "nixosConfigurations.web-2",
+            "nixosConfigurations.db-1",
+        ]
+        .into_iter()
+        .map(|attribute_path| FlakeOutput {
+            attribute_path: attribute_path.to_string(),
+        })
+        .collect();
+
+        for (glob, expected) in [
+            (
+                "packages.x86_64-linux.*",
+                vec!["packages.x86_64-linux.default", "packages.x86_64-linux.fh"],
+            ),
+            (
+                "packages.*.fh",
+                vec!["packages.x86_64-linux.fh", "packages.aarch64-darwin.fh"],
+            ),
+            (
+                "*.x86_64-linux.default",
+                vec![
+                    "packages.x86_64-linux.default",
+                    "devShells.x86_64-linux.default",
+                ],
+            ),
+            (
+                "nixosConfigurations.web-*",
+                vec!["nixosConfigurations.web-1", "nixosConfigurations.web-2"],
+            ),
+            (
+                "nixosConfigurations.*-1",
+                vec!["nixosConfigurations.web-1", "nixosConfigurations.db-1"],
+            ),
+            // `*` never crosses a `.`
+            ("packages.*", vec![]),
+            ("packages.x86_64-linux.fh", vec!["packages.x86_64-linux.fh"]),
+        ] {
+            assert_eq!(
+                super::matching_outputs(glob, &outputs),
+                expected,
+                "glob: {glob}"
+            );
+        }
+    }
+
+    #[test]
+    fn segment_matching() {
+        assert!(super::segment_matches("*", ""));
+        assert!(super::segment_matches("*", "anything"));
+        assert!(super::segment_matches("a*b*c", "aXXbYYc"));
+        assert!(super::segment_matches("a*b*c", "abc"));
+        assert!(!super::segment_matches("a*b*c", "acb"));
+        assert!(!super::segment_matches("ab", "abc"));
+        assert!(!super::segment_matches("a*a", "a"));
+    }
+
+    #[test]
+    fn flakehub_url_to_release_ref() {
+        for (url, expected) in [
+            (
+                "https://flakehub.com/f/NixOS/nixpkgs/0.2305.0.tar.gz",
+                "NixOS/nixpkgs/0.2305.0",
+            ),
+            (
+                "https://flakehub.com/f/DeterminateSystems/fh/*",
+                "DeterminateSystems/fh/*",
+            ),
+        ] {
+            let url = url::Url::parse(url).unwrap();
+            assert_eq!(super::flakehub_url_to_release_ref(&url).unwrap(), expected);
+        }
+
+        let url = url::Url::parse("https://flakehub.com/flake/NixOS/nixpkgs").unwrap();
+        assert!(super::flakehub_url_to_release_ref(&url).is_err());
+    }
+
+    #[test]
+    fn token_is_null_when_absent() {
+        let mut resolved_path = super::ResolvedPath {
+            attribute_path: String::from("packages.x86_64-linux.default"),
+            store_path: String::from("/nix/store/xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx-hello-2.12.1"),
+            token: None,
+        };
+        let json = serde_json::to_value(&resolved_path).unwrap();
+        assert_eq!(json.get("token"), Some(&serde_json::Value::Null));
+
+        resolved_path.token = Some(String::from("secret-jwt"));
+        let json = serde_json::to_value(&resolved_path).unwrap();
+        assert_eq!(json["token"], "secret-jwt");
+
+        let debug = format!("{resolved_path:?}");
+        assert!(!debug.contains("secret-jwt"));
+        assert!(debug.contains("<redacted>"));
+    }
+
+    #[tokio::test]
+    async fn non_forge_refs_are_left_alone() {
+        let api_addr = url::Url::parse("https://api.flakehub.com").unwrap();
+
+        let flakehub_ref =
+            super::forge_ref_to_flakehub_ref(&api_addr, "NixOS/nixpkgs/0.2305.*#hello")
+                .await
+                .unwrap();
+        assert_eq!(flakehub_ref, None);
+    }
+
+    #[tokio::test]
+    async fn gitlab_refs_need_a_gitlab_project() {
+        let router = axum::Router::new().route(
+            "/f/:org/:project",
+            axum::routing::get(
+                |axum::extract::Path((org, project)): axum::extract::Path<(String, String)>| async move {
+                    axum::Json(serde_json::json!({
+                        "project": project,
+                        "pretty_download_url": format!("https://flakehub.com/f/{org}/{project}/0.1.5.tar.gz"),
+                        "source_host": if org == "gitlaborg" { "gitlab" } else { "github" },
+                    }))
+                },
+            ),
+        );
+        let test_server = crate::cli::cmd::test_server(router);
+        let api_addr = test_server.server_address().unwrap();
+
+        for (forge_ref, expected) in [
+            (
+                "gitlab:gitlaborg/tools#hello",
+                "gitlaborg/tools/0.1.5#hello",
+            ),
+            ("github:someorg/tools#hello", "someorg/tools/0.1.5#hello"),
+        ] {
+            let flakehub_ref = super::forge_ref_to_flakehub_ref(&api_addr, forge_ref)
+                .await
+                .unwrap();
+            assert_eq!(flakehub_ref.as_deref(), Some(expected));
+        }
+
+        // someorg/tools on FlakeHub comes from GitHub, not GitLab
+        assert!(
+            super::forge_ref_to_flakehub_ref(&api_addr, "gitlab:someorg/tools#hello")
+                .await
+                .is_err()
+        );
+    }
+}
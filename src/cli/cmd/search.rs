@@ -1,28 +1,53 @@
 use clap::Parser;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
-use std::{io::IsTerminal, process::ExitCode};
-use tabled::{Table, Tabled};
+use std::process::ExitCode;
+use tabled::Tabled;
 use url::Url;
 
 use crate::flakehub_url;
 
-use super::{list::FLAKEHUB_WEB_ROOT, print_json, CommandExecute, FlakeHubClient};
+use super::{
+    list::{apply_limit, FLAKEHUB_WEB_ROOT},
+    CommandExecute, FlakeHubClient, OutputFormat,
+};
 
 /// Searches FlakeHub for flakes that match your query.
+///
+/// When you're logged in, the results include the private flakes that your account can access,
+/// so they can differ from what someone who isn't logged in sees.
 #[derive(Debug, Parser)]
 pub(crate) struct SearchSubcommand {
     /// The search query.
     query: String,
 
-    /// The maximum number of search results to return.
-    #[clap(short, long, default_value = "10")]
-    max_results: usize,
+    /// The maximum number of search results to return. FlakeHub is asked for this many results,
+    /// and fh never shows more than this, even if the server sends more. With `--fuzzy`, every
+    /// result is fetched and re-ranked first, and then the best ones up to the limit are shown.
+    // `--max-results` and `-m` are the old names, kept working for existing scripts
+    #[clap(
+        short,
+        long,
+        alias = "max-results",
+        short_alias = 'm',
+        default_value = "10"
+    )]
+    limit: usize,
+
+    /// Re-rank the results locally so that flakes whose names most closely match the query come first.
+    #[clap(long)]
+    fuzzy: bool,
 
     /// Output results as JSON.
     #[clap(long, env = "FH_OUTPUT_JSON")]
     json: bool,
 
+    /// How to print the results. Defaults to a table in a terminal and CSV otherwise, or JSON
+    /// with `--json`.
+    #[clap(long, value_enum)]
+    output: Option<OutputFormat>,
+
     #[clap(from_global)]
     api_addr: url::Url,
 }
@@ -49,6 +74,24 @@ pub struct SearchResultRow {
     url: Url,
 }
 
+#[derive(Tabled, serde::Serialize)]
+pub struct FuzzySearchResultRow {
+    name: String,
+    url: Url,
+    #[tabled(rename = "match")]
+    #[serde(rename = "match")]
+    score: i64,
+}
+
+// A `--fuzzy` result with its score, so that JSON and YAML include the score as well.
+#[derive(Serialize)]
+struct RankedSearchResult {
+    #[serde(flatten)]
+    result: SearchResult,
+    #[serde(rename = "match")]
+    score: i64,
+}
+
 impl From<SearchResult> for SearchResultRow {
     fn from(value: SearchResult) -> Self {
         Self {
@@ -64,25 +107,37 @@ impl CommandExecute for SearchSubcommand {
         let pb = ProgressBar::new_spinner();
         pb.set_style(ProgressStyle::default_spinner());
 
-        match FlakeHubClient::search(self.api_addr.as_ref(), self.query).await {
+        // Fuzzy ranking can promote results that the server ranked lower, so it needs to see all
+        // of them before applying the limit
+        let server_limit = (!self.fuzzy).then_some(self.limit);
+        let format = OutputFormat::choose(self.output, self.json);
+
+        match FlakeHubClient::search(self.api_addr.as_ref(), self.query.clone(), server_limit).await
+        {
             Ok(results) => {
                 if results.is_empty() {
                     eprintln!("No results");
-                } else if self.json {
-                    print_json(&results)?;
-                } else {
-                    let rows: Vec<SearchResultRow> = results
+                }
+
+                if self.fuzzy {
+                    let ranked = rank_fuzzy(&self.query, results)
                         .into_iter()
-                        .take(self.max_results)
-                        .map(Into::into)
+                        .take(self.limit)
+                        .map(|(result, score)| RankedSearchResult { result, score })
                         .collect();
 
-                    if std::io::stdout().is_terminal() {
-                        let table = Table::new(rows);
-                        println!("{table}");
-                    } else {
-                        csv::Writer::from_writer(std::io::stdout()).serialize(rows)?;
-                    }
+                    format.print(ranked, |RankedSearchResult { result, score }| {
+                        FuzzySearchResultRow {
+                            name: result.name(),
+                            url: result.url(),
+                            score,
+                        }
+                    })?;
+                } else {
+                    // The API may not honor the limit, so enforce it here too
+                    let results = apply_limit(results, Some(self.limit));
+
+                    format.print(results, SearchResultRow::from)?;
                 }
             }
             Err(e) => {
@@ -93,3 +148,92 @@ impl CommandExecute for SearchSubcommand {
         Ok(ExitCode::SUCCESS)
     }
 }
+
+// Scores each result's `org/project` name against the query and sorts the best matches first.
+// Results that don't match at all get a score of 0; ties keep the server's order.
+fn rank_fuzzy(query: &str, results: Vec<SearchResult>) -> Vec<(SearchResult, i64)> {
+    let matcher = SkimMatcherV2::default();
+
+    let mut ranked: Vec<(SearchResult, i64)> = results
+        .into_iter()
+        .map(|result| {
+            let score = matcher.fuzzy_match(&result.name(), query).unwrap_or(0);
+            (result, score)
+        })
+        .collect();
+    ranked.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+
+    ranked
+}
+
+#[cfg(test)]
+mod test {
+    use axum::{extract::Query, response::IntoResponse};
+
+    use super::{rank_fuzzy, SearchResult};
+    use crate::cli::cmd::{list::apply_limit, FlakeHubClient};
+
+    #[derive(serde::Deserialize)]
+    struct SearchParams {
+        limit: Option<usize>,
+    }
+
+    // Returns as many results as it was asked for, or 20 without a limit
+    async fn search(Query(params): Query<SearchParams>) -> axum::response::Response {
+        let results: Vec<_> = (0..params.limit.unwrap_or(20))
+            .map(|i| serde_json::json!({ "org": "someorg", "project": format!("project{i}") }))
+            .collect();
+
+        axum::Json(results).into_response()
+    }
+
+    #[tokio::test]
+    async fn limit_is_forwarded_and_enforced() {
+        let router = axum::Router::new().route("/search", axum::routing::get(search));
+        let test_server = crate::cli::cmd::test_server(router);
+        let server_url = test_server.server_address().unwrap();
+
+        let results = FlakeHubClient::search(server_url.as_str(), String::from("nix"), Some(3))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+
+        // A server that ignores the limit still gets truncated
+        let results = FlakeHubClient::search(server_url.as_str(), String::from("nix"), None)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 20);
+        assert_eq!(apply_limit(results, Some(3)).len(), 3);
+    }
+
+    #[test]
+    fn fuzzy_ranking_prefers_close_names() {
+        let results = [
+            "DeterminateSystems/nix-installer",
+            "NixOS/nixpkgs",
+            "nix-community/home-manager",
+        ]
+        .into_iter()
+        .map(|name| {
+            let (org, project) = name.split_once('/').unwrap();
+            SearchResult {
+                org: org.to_string(),
+                project: project.to_string(),
+            }
+        })
+        .collect();
+
+        let ranked = rank_fuzzy("nixpkgs", results);
+        let names: Vec<String> = ranked.iter().map(|(result, _)| result.name()).collect();
+
+        assert_eq!(names[0], "NixOS/nixpkgs");
+        assert!(ranked[0].1 > ranked[1].1);
+
+        // JSON and YAML get the score next to the result
+        let (result, score) = ranked.into_iter().next().unwrap();
+        assert_eq!(
+            serde_json::to_value(super::RankedSearchResult { result, score }).unwrap(),
+            serde_json::json!({ "org": "NixOS", "project": "nixpkgs", "match": score })
+        );
+    }
+}
@@ -1,14 +1,33 @@
+use std::io::IsTerminal;
 use std::process::ExitCode;
 
 use clap::Parser;
-use color_eyre::eyre::WrapErr;
+use owo_colors::OwoColorize;
+use tabled::{Table, Tabled};
 
-use super::{CommandExecute, FlakeHubClient};
+use super::list::{bold, dimmed};
+use super::{print_json, print_yaml, CommandExecute, FlakeHubClient, OutputFormat, DEFAULT_STYLE};
+
+/// How many days before the token expires that fh starts warning about it, unless
+/// `--warn-within` says otherwise.
+pub(crate) const DEFAULT_WARN_WITHIN_DAYS: u32 = 7;
 
 // TODO: make status and login subcommands of a `auth` subcommand?
 /// Check your FlakeHub token status.
 #[derive(Debug, Parser)]
 pub(crate) struct StatusSubcommand {
+    /// Output the token status as JSON.
+    #[arg(long, env = "FH_OUTPUT_JSON")]
+    json: bool,
+
+    /// How to print the token status. Defaults to plain text, or JSON with `--json`.
+    #[arg(long, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Warn if the token expires within this many days.
+    #[arg(long, value_name = "DAYS", default_value_t = DEFAULT_WARN_WITHIN_DAYS)]
+    warn_within: u32,
+
     #[clap(from_global)]
     api_addr: url::Url,
 
@@ -16,23 +35,153 @@ pub(crate) struct StatusSubcommand {
     frontend_addr: url::Url,
 }
 
+/// Print the GitHub user name that you're logged in to FlakeHub as, or fail if you aren't logged in.
+#[derive(Debug, Parser)]
+pub(crate) struct WhoamiSubcommand {
+    /// Output the user name as JSON.
+    #[arg(long, env = "FH_OUTPUT_JSON")]
+    json: bool,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub(crate) struct TokenStatus {
-    gh_name: String,
+    pub(crate) gh_name: String,
     #[serde(deserialize_with = "i64_to_local_datetime")]
     expires_at: chrono::DateTime<chrono::Local>,
+    #[serde(skip)]
+    pub(crate) token_source: Option<crate::cli::token::TokenSource>,
 }
 
 impl std::fmt::Display for TokenStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Logged in: true")?;
-        writeln!(f, "GitHub user name: {}", self.gh_name)?;
-        writeln!(f, "Token expires at: {}", self.expires_at)?;
+        for row in self.rows() {
+            writeln!(f, "{}: {}", row.field, row.value)?;
+        }
 
         Ok(())
     }
 }
 
+#[derive(Tabled)]
+struct TokenStatusRow {
+    #[tabled(rename = "Field", display_with = "bold")]
+    field: &'static str,
+    #[tabled(rename = "Value", display_with = "dimmed")]
+    value: String,
+}
+
+impl TokenStatus {
+    fn rows(&self) -> Vec<TokenStatusRow> {
+        let mut rows = vec![
+            TokenStatusRow {
+                field: "Logged in",
+                value: String::from("true"),
+            },
+            TokenStatusRow {
+                field: "GitHub user name",
+                value: self.gh_name.clone(),
+            },
+            TokenStatusRow {
+                field: "Token expires at",
+                value: self.expires_at.to_string(),
+            },
+        ];
+        if let Some(token_source) = self.token_source {
+            rows.push(TokenStatusRow {
+                field: "Token source",
+                value: token_source.to_string(),
+            });
+        }
+
+        rows
+    }
+
+    // A warning to show if the token has expired, or will expire within `warn_within` of `now`
+    pub(crate) fn expiry_warning(
+        &self,
+        warn_within: chrono::Duration,
+        now: chrono::DateTime<chrono::Local>,
+    ) -> Option<String> {
+        let remaining = self.expires_at - now;
+
+        if remaining <= chrono::Duration::zero() {
+            Some(format!(
+                "Your FlakeHub token expired at {}. Run `fh login` to get a new one.",
+                self.expires_at
+            ))
+        } else if remaining <= warn_within {
+            let days = match remaining.num_days() {
+                0 => String::from("less than a day"),
+                1 => String::from("1 day"),
+                days => format!("{days} days"),
+            };
+            Some(format!(
+                "Your FlakeHub token expires in {days} (at {}). Run `fh login` to renew it before \
+                it starts causing authentication failures.",
+                self.expires_at
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Print a warning to stderr if the token has expired, or will within `warn_within_days` days.
+    pub(crate) fn warn_if_expiring(&self, warn_within_days: u32) {
+        let warn_within = chrono::Duration::days(warn_within_days.into());
+        let Some(warning) = self.expiry_warning(warn_within, chrono::Local::now()) else {
+            return;
+        };
+
+        if !crate::cli::color::stderr() {
+            eprintln!("{warning}");
+        } else if self.expires_at <= chrono::Local::now() {
+            eprintln!("{}", warning.red());
+        } else {
+            eprintln!("{}", warning.yellow());
+        }
+    }
+}
+
+// The JSON form of the token status, which is just `{"logged_in": false}` when logged out
+#[derive(Debug, serde::Serialize)]
+struct TokenStatusJson {
+    logged_in: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gh_name: Option<String>,
+    // RFC 3339
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<String>,
+    // Seconds since the Unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at_timestamp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_source: Option<String>,
+}
+
+impl From<Option<TokenStatus>> for TokenStatusJson {
+    fn from(status: Option<TokenStatus>) -> Self {
+        match status {
+            Some(status) => Self {
+                logged_in: true,
+                expires_at: Some(status.expires_at.to_rfc3339()),
+                expires_at_timestamp: Some(status.expires_at.timestamp()),
+                token_source: status.token_source.map(|source| source.to_string()),
+                gh_name: Some(status.gh_name),
+            },
+            None => Self {
+                logged_in: false,
+                gh_name: None,
+                expires_at: None,
+                expires_at_timestamp: None,
+                token_source: None,
+            },
+        }
+    }
+}
+
 fn i64_to_local_datetime<'de, D>(
     deserializer: D,
 ) -> Result<chrono::DateTime<chrono::Local>, D::Error>
@@ -51,7 +200,44 @@ where
 #[async_trait::async_trait]
 impl CommandExecute for StatusSubcommand {
     async fn execute(self) -> color_eyre::Result<ExitCode> {
-        match get_status_from_auth_file(self.api_addr).await {
+        let status = get_status_from_auth_file(self.api_addr).await;
+
+        if let Ok(status) = &status {
+            status.warn_if_expiring(self.warn_within);
+        }
+
+        // Unlike the listing subcommands, the status is short enough to print as text even when
+        // the output isn't a terminal
+        let format = self.output.unwrap_or(if self.json {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Table
+        });
+        match format {
+            OutputFormat::Json => {
+                print_json(TokenStatusJson::from(status.ok()))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            OutputFormat::Yaml => {
+                print_yaml(TokenStatusJson::from(status.ok()))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            OutputFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(std::io::stdout());
+                writer.serialize(TokenStatusJson::from(status.ok()))?;
+                writer.flush()?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            OutputFormat::Table => {}
+        }
+
+        match status {
+            // Scripts that read the plain `Field: value` lines keep getting them
+            Ok(status) if std::io::stdout().is_terminal() => {
+                let mut table = Table::new(status.rows());
+                table.with(DEFAULT_STYLE.clone());
+                println!("{table}");
+            }
             Ok(status) => {
                 print!("{status}");
             }
@@ -69,14 +255,108 @@ impl CommandExecute for StatusSubcommand {
     }
 }
 
+#[async_trait::async_trait]
+impl CommandExecute for WhoamiSubcommand {
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let gh_name = match get_status_from_auth_file(self.api_addr).await {
+            Ok(status) => Some(status.gh_name),
+            Err(e) => {
+                tracing::debug!("Not logged in: {e}");
+                None
+            }
+        };
+
+        if self.json {
+            print_json(serde_json::json!({ "gh_name": gh_name }))?;
+        } else if let Some(gh_name) = &gh_name {
+            println!("{gh_name}");
+        } else {
+            eprintln!("Not logged in to FlakeHub. To log in, run `fh login`.");
+        }
+
+        Ok(if gh_name.is_some() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+}
+
 pub(crate) async fn get_status_from_auth_file(
     api_addr: url::Url,
 ) -> color_eyre::Result<TokenStatus> {
-    let auth_token_path = crate::cli::cmd::login::user_auth_token_read_path().await?;
-    let token = tokio::fs::read_to_string(&auth_token_path)
-        .await
-        .wrap_err_with(|| format!("Could not open {}", auth_token_path.display()))?;
-    let token = token.trim();
+    let token = crate::cli::token::read_token()
+        .await?
+        .ok_or_else(|| color_eyre::eyre::eyre!("no FlakeHub token was found"))?;
+
+    let mut status = FlakeHubClient::auth_status(api_addr.as_ref(), &token.token).await?;
+    status.token_source = Some(token.source);
 
-    FlakeHubClient::auth_status(api_addr.as_ref(), token).await
+    Ok(status)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TokenStatus, TokenStatusJson};
+
+    #[test]
+    fn expiry_warning() {
+        let status: TokenStatus =
+            serde_json::from_str(r#"{"gh_name": "someone", "expires_at": 1737585708}"#).unwrap();
+        let week = chrono::Duration::days(7);
+
+        let now = status.expires_at - chrono::Duration::days(30);
+        assert_eq!(status.expiry_warning(week, now), None);
+
+        let now = status.expires_at - chrono::Duration::days(3);
+        assert!(status
+            .expiry_warning(week, now)
+            .unwrap()
+            .contains("expires in 3 days"));
+
+        let now = status.expires_at - chrono::Duration::hours(2);
+        assert!(status
+            .expiry_warning(week, now)
+            .unwrap()
+            .contains("expires in less than a day"));
+
+        let now = status.expires_at + chrono::Duration::hours(2);
+        assert!(status
+            .expiry_warning(week, now)
+            .unwrap()
+            .contains("expired at"));
+    }
+
+    #[test]
+    fn plain_status() {
+        let mut status: TokenStatus =
+            serde_json::from_str(r#"{"gh_name": "someone", "expires_at": 1737585708}"#).unwrap();
+        status.token_source = Some(crate::cli::token::TokenSource::Env);
+
+        let lines: Vec<String> = status.to_string().lines().map(String::from).collect();
+        assert_eq!(lines[0], "Logged in: true");
+        assert_eq!(lines[1], "GitHub user name: someone");
+        assert!(lines[2].starts_with("Token expires at: "));
+        assert_eq!(
+            lines[3],
+            format!("Token source: {}", crate::cli::token::TokenSource::Env)
+        );
+    }
+
+    #[test]
+    fn token_status_json() {
+        let status: TokenStatus =
+            serde_json::from_str(r#"{"gh_name": "someone", "expires_at": 1737585708}"#).unwrap();
+        let json = serde_json::to_value(TokenStatusJson::from(Some(status))).unwrap();
+
+        assert_eq!(json["logged_in"], true);
+        assert_eq!(json["gh_name"], "someone");
+        assert_eq!(json["expires_at_timestamp"], 1737585708);
+        let expires_at =
+            chrono::DateTime::parse_from_rfc3339(json["expires_at"].as_str().unwrap()).unwrap();
+        assert_eq!(expires_at.timestamp(), 1737585708);
+
+        let json = serde_json::to_value(TokenStatusJson::from(None)).unwrap();
+        assert_eq!(json, serde_json::json!({ "logged_in": false }));
+    }
 }
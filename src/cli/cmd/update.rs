@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, WrapErr};
+
+use super::lock_status::{flakehub_inputs, without_build, FlakeHubInput};
+use super::{local_flake_dir, nix_command, CommandExecute, SudoChoice};
+use crate::info_println;
+use crate::shared::flake_lock::FlakeLock;
+
+/// Update FlakeHub inputs to the newest releases that match their version constraints, leaving
+/// every other input as it's locked.
+#[derive(Debug, Parser)]
+pub(crate) struct UpdateSubcommand {
+    /// The inputs to update. Every input locked to a FlakeHub release is updated if none are given.
+    input_names: Vec<String>,
+
+    /// The flake.nix whose lock to update. Its flake.lock is next to it.
+    #[clap(long, default_value = "./flake.nix")]
+    flake_path: PathBuf,
+
+    #[clap(from_global)]
+    api_addr: url::Url,
+
+    #[clap(from_global)]
+    print_commands: bool,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for UpdateSubcommand {
+    #[tracing::instrument(skip_all)]
+    async fn execute(self) -> color_eyre::Result<ExitCode> {
+        let lock_path = self.flake_path.with_file_name("flake.lock");
+        let lock = FlakeLock::read(&lock_path).await?;
+        let inputs = self.inputs_to_check(flakehub_inputs(&lock))?;
+
+        if inputs.is_empty() {
+            info_println!(
+                "{} has no inputs locked to FlakeHub releases",
+                lock_path.display()
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut outdated = Vec::new();
+        for input in inputs {
+            match input.latest_version(&self.api_addr).await {
+                Some(latest) if input.is_outdated_by(&latest) => outdated.push(input),
+                Some(_) => info_println!(
+                    "{} is already up to date ({})",
+                    input.name,
+                    without_build(input.locked_version)
+                ),
+                None => {}
+            }
+        }
+
+        if outdated.is_empty() {
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut args = vec!["flake".to_string(), "lock".to_string()];
+        for input in &outdated {
+            args.extend(["--update-input".to_string(), input.name.clone()]);
+        }
+        args.push(local_flake_dir(&self.flake_path));
+
+        nix_command(&args, SudoChoice::Never, self.print_commands)
+            .await
+            .wrap_err("failed to update the flake's lock")?;
+
+        let updated = flakehub_inputs(&FlakeLock::read(&lock_path).await?);
+        for input in outdated {
+            let new_version = updated
+                .iter()
+                .find(|updated| updated.name == input.name)
+                .map(|updated| without_build(updated.locked_version.clone()));
+
+            match new_version {
+                Some(new_version) => info_println!(
+                    "Updated {}: {} -> {new_version}",
+                    input.name,
+                    without_build(input.locked_version)
+                ),
+                None => info_println!("Updated {}", input.name),
+            }
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl UpdateSubcommand {
+    // The FlakeHub inputs that were asked for, or all of them if none were.
+    fn inputs_to_check(
+        &self,
+        inputs: Vec<FlakeHubInput>,
+    ) -> color_eyre::Result<Vec<FlakeHubInput>> {
+        if let Some(missing) = self
+            .input_names
+            .iter()
+            .find(|name| !inputs.iter().any(|input| &input.name == *name))
+        {
+            return Err(eyre!(
+                "`{missing}` isn't an input locked to a FlakeHub release, so fh can't update it"
+            ));
+        }
+
+        Ok(inputs
+            .into_iter()
+            .filter(|input| self.input_names.is_empty() || self.input_names.contains(&input.name))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FlakeHubInput;
+
+    fn input(name: &str) -> FlakeHubInput {
+        FlakeHubInput {
+            name: name.to_string(),
+            org: String::from("NixOS"),
+            project: name.to_string(),
+            constraint: String::from("*"),
+            locked_version: semver::Version::new(0, 1, 0),
+        }
+    }
+
+    #[test]
+    fn inputs_to_check() {
+        let update = |input_names: &[&str]| super::UpdateSubcommand {
+            input_names: input_names.iter().map(ToString::to_string).collect(),
+            flake_path: "./flake.nix".into(),
+            api_addr: url::Url::parse("https://api.flakehub.com").unwrap(),
+            print_commands: false,
+        };
+        let names = |inputs: Vec<FlakeHubInput>| {
+            inputs
+                .into_iter()
+                .map(|input| input.name)
+                .collect::<Vec<_>>()
+        };
+
+        let all = update(&[])
+            .inputs_to_check(vec![input("nixpkgs"), input("fh")])
+            .unwrap();
+        assert_eq!(names(all), ["nixpkgs", "fh"]);
+
+        let some = update(&["fh"])
+            .inputs_to_check(vec![input("nixpkgs"), input("fh")])
+            .unwrap();
+        assert_eq!(names(some), ["fh"]);
+
+        let err = update(&["flake-utils"])
+            .inputs_to_check(vec![input("nixpkgs")])
+            .unwrap_err();
+        assert!(err.to_string().contains("`flake-utils` isn't an input"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+static COLOR_CHOICE: OnceLock<ColorChoice> = OnceLock::new();
+
+/// When to use colors in fh's output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Always use colors, even when the output isn't a terminal
+    Always,
+    /// Use colors when the output is a terminal
+    #[default]
+    Auto,
+    /// Never use colors
+    Never,
+}
+
+impl std::fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ColorChoice::Always => "always",
+                ColorChoice::Auto => "auto",
+                ColorChoice::Never => "never",
+            }
+        )
+    }
+}
+
+impl ColorChoice {
+    /// Apply `NO_COLOR` (https://no-color.org), which forces colors off when it's set to anything
+    /// other than an empty string.
+    pub fn with_no_color(self, no_color: Option<&std::ffi::OsStr>) -> Self {
+        match no_color {
+            Some(v) if !v.is_empty() => ColorChoice::Never,
+            _ => self,
+        }
+    }
+
+    pub fn should_colorize(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Auto => is_terminal,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Record the user's color choice for the rest of the process. Only the first call has an effect.
+pub fn init(choice: ColorChoice) {
+    let choice = choice.with_no_color(std::env::var_os("NO_COLOR").as_deref());
+    let _ = COLOR_CHOICE.set(choice);
+}
+
+fn choice() -> ColorChoice {
+    COLOR_CHOICE.get().copied().unwrap_or_default()
+}
+
+/// Whether output written to stdout should be colorized.
+pub fn stdout() -> bool {
+    choice().should_colorize(std::io::stdout().is_terminal())
+}
+
+/// Whether output written to stderr, like logs and errors, should be colorized.
+pub fn stderr() -> bool {
+    choice().should_colorize(std::io::stderr().is_terminal())
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsStr;
+
+    use super::ColorChoice;
+
+    #[test]
+    fn no_color_forces_never() {
+        assert_eq!(
+            ColorChoice::Always.with_no_color(Some(OsStr::new("1"))),
+            ColorChoice::Never
+        );
+        assert_eq!(
+            ColorChoice::Auto.with_no_color(Some(OsStr::new("1"))),
+            ColorChoice::Never
+        );
+        // An empty NO_COLOR is the same as it being unset
+        assert_eq!(
+            ColorChoice::Always.with_no_color(Some(OsStr::new(""))),
+            ColorChoice::Always
+        );
+        assert_eq!(ColorChoice::Auto.with_no_color(None), ColorChoice::Auto);
+    }
+
+    #[test]
+    fn should_colorize() {
+        assert!(ColorChoice::Always.should_colorize(false));
+        assert!(ColorChoice::Auto.should_colorize(true));
+        assert!(!ColorChoice::Auto.should_colorize(false));
+        assert!(!ColorChoice::Never.should_colorize(true));
+    }
+}
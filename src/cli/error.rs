@@ -1,9 +1,17 @@
 use reqwest::StatusCode;
 
+// What to say about why a Nix command failed, given the end of its stderr if that was captured.
+fn nix_failure_details(stderr_tail: Option<&str>) -> String {
+    match stderr_tail {
+        Some(tail) if !tail.trim().is_empty() => format!("it ended with:\n{tail}"),
+        _ => String::from("check prior Nix output for details"),
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum FhError {
-    #[error("Nix command `{0}` failed; check prior Nix output for details")]
-    FailedNixCommand(String),
+    #[error("Nix command `{0}` failed; {details}", details = nix_failure_details(.1.as_deref()))]
+    FailedNixCommand(String, Option<String>),
 
     #[error("file error: {0}")]
     Filesystem(#[from] std::io::Error),
@@ -11,18 +19,43 @@ pub(crate) enum FhError {
     #[error("flake name parsing error: {0}")]
     FlakeParse(String),
 
+    #[error(
+        "no GC root pointing into the Nix store was created at {0} for {1}. Versions of Nix whose \
+        `nix copy` lacks `--out-link` fall back to `nix build --out-link`, which may not have \
+        created it; try upgrading Nix, or run `nix build --max-jobs 0 --out-link {0} {1}`"
+    )]
+    GcRootNotCreated(String, String),
+
     #[error("invalid header: {0}")]
     Header(#[from] reqwest::header::InvalidHeaderValue),
 
     #[error("http error: {0}")]
-    Http(#[from] reqwest::Error),
+    Http(reqwest::Error),
 
     #[error("interactive initializer error: {0}")]
     Interactive(#[from] inquire::InquireError),
 
+    #[error(
+        "the edited flake.nix would no longer be valid ({0}), so it was left untouched; pass \
+        `--force` to write it anyway"
+    )]
+    InvalidEdit(String),
+
+    #[error("invalid fh init config: {0}")]
+    InvalidInitConfig(String),
+
+    #[error("`{0}` can't be passed to Nix here, because fh already sets it")]
+    InvalidNixArg(String),
+
     #[error("Profile path is not valid UTF-8")]
     InvalidProfile,
 
+    #[error(
+        "{0} is not valid UTF-8 (invalid byte at offset {1}); it may be corrupted or not a text \
+        flake. Pass `--lossy` to edit it anyway, replacing invalid bytes"
+    )]
+    InvalidUtf8(String, usize),
+
     #[error("json parsing error: {0}")]
     Json(#[from] serde_json::Error),
 
@@ -38,16 +71,38 @@ pub(crate) enum FhError {
     #[error("http call returned error code {0}")]
     MiscHttp(StatusCode),
 
-    #[error("{0} is not installed or not on the PATH")]
-    MissingExecutable(String),
-
     #[error("missing from flake output reference: {0}")]
     MissingFromOutputRef(String),
 
+    #[error(
+        "`{0}` is not installed or not on the PATH, and fh needs Nix for this. Install Nix with the \
+        Determinate Nix Installer (https://determinate.systems/nix-installer), or make sure `{0}` is \
+        on the PATH"
+    )]
+    MissingNix(String),
+
+    #[error(
+        "no FlakeHub token was found in the `{0}` token source; run `fh login`, or choose another \
+        source with `--token-source`"
+    )]
+    MissingToken(String),
+
+    #[error("permission denied writing the Nix configuration {0}")]
+    NixConfigPermissionDenied(String),
+
+    #[error("writing the Nix configuration {0} failed: {1}")]
+    NixConfigWrite(String, #[source] std::io::Error),
+
     #[error("the flake has no inputs")]
     NoInputs,
 
-    #[error("access to this {0} is not authorized")]
+    #[error("{0} is not a template; templates are the outputs under a flake's `templates`")]
+    NotATemplate(String),
+
+    #[error(
+        "access to this {0} is not authorized ({hint})",
+        hint = crate::cli::token::precedence_hint()
+    )]
     NotAuthorized(String),
 
     #[error("{0} {1} not found")]
@@ -65,9 +120,30 @@ pub(crate) enum FhError {
     #[error("a presumably unreachable point was reached: {0}")]
     Unreachable(String),
 
+    #[error(
+        "the request to FlakeHub timed out after {0} seconds; if your connection is slow, raise \
+        the limit with `--timeout`"
+    )]
+    Timeout(u64),
+
     #[error("url parse error: {0}")]
     Url(#[from] url::ParseError),
 
+    #[error("yaml serialization error: {0}")]
+    Yaml(#[from] serde_yaml_ng::Error),
+
     #[error("xdg base directory error: {0}")]
     Xdg(#[from] xdg::BaseDirectoriesError),
 }
+
+// Timeouts get their own error wherever they happen, including while the response body is read,
+// so that the message can point at `--timeout`.
+impl From<reqwest::Error> for FhError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout(crate::cli::timeout::seconds())
+        } else {
+            Self::Http(e)
+        }
+    }
+}
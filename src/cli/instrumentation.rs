@@ -1,5 +1,5 @@
 use std::error::Error;
-use std::io::IsTerminal;
+use std::sync::OnceLock;
 
 use color_eyre::eyre::WrapErr;
 use tracing_error::ErrorLayer;
@@ -7,6 +7,44 @@ use tracing_subscriber::{
     filter::Directive, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter,
 };
 
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+/// Whether `--quiet` was passed, so informational messages should be left out. Errors, warnings,
+/// and the output that a command exists to produce are still printed.
+pub fn quiet() -> bool {
+    QUIET.get().copied().unwrap_or_default()
+}
+
+/// `println!` for an informational message, which `--quiet` leaves out.
+#[macro_export]
+macro_rules! info_println {
+    ($($arg:tt)*) => {
+        if !$crate::cli::instrumentation::quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `print!` for an informational message, which `--quiet` leaves out.
+#[macro_export]
+macro_rules! info_print {
+    ($($arg:tt)*) => {
+        if !$crate::cli::instrumentation::quiet() {
+            print!($($arg)*);
+        }
+    };
+}
+
+/// `eprintln!` for an informational message, which `--quiet` leaves out.
+#[macro_export]
+macro_rules! info_eprintln {
+    ($($arg:tt)*) => {
+        if !$crate::cli::instrumentation::quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
 #[derive(Clone, Default, Debug, clap::ValueEnum)]
 pub enum Logger {
     #[default]
@@ -33,6 +71,15 @@ pub struct Instrumentation {
     /// Enable debug logs, -vv for trace
     #[clap(short = 'v', env = "FH_VERBOSITY", long, action = clap::ArgAction::Count, global = true)]
     pub verbose: u8,
+    /// Only print errors, warnings, and results, leaving out informational messages
+    #[clap(
+        short = 'q',
+        env = "FH_QUIET",
+        long,
+        global = true,
+        conflicts_with = "verbose"
+    )]
+    pub quiet: bool,
     /// Which logger to use
     #[clap(long, env = "FH_LOGGER", default_value_t = Default::default(), global = true)]
     pub logger: Logger,
@@ -46,6 +93,7 @@ pub struct Instrumentation {
 impl Instrumentation {
     pub fn log_level(&self) -> String {
         match self.verbose {
+            0 if self.quiet => "warn",
             0 => "info",
             1 => "debug",
             _ => "trace",
@@ -54,6 +102,8 @@ impl Instrumentation {
     }
 
     pub async fn setup(&self) -> color_eyre::Result<()> {
+        let _ = QUIET.set(self.quiet);
+
         let filter_layer = self.filter_layer()?;
         let registry = tracing_subscriber::registry()
             .with(filter_layer)
@@ -86,7 +136,7 @@ impl Instrumentation {
         S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
     {
         tracing_subscriber::fmt::Layer::new()
-            .with_ansi(std::io::stderr().is_terminal())
+            .with_ansi(super::color::stderr())
             .with_writer(std::io::stderr)
     }
 
@@ -95,7 +145,7 @@ impl Instrumentation {
         S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
     {
         tracing_subscriber::fmt::Layer::new()
-            .with_ansi(std::io::stderr().is_terminal())
+            .with_ansi(super::color::stderr())
             .with_writer(std::io::stderr)
             .pretty()
     }
@@ -105,7 +155,7 @@ impl Instrumentation {
         S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
     {
         tracing_subscriber::fmt::Layer::new()
-            .with_ansi(std::io::stderr().is_terminal())
+            .with_ansi(super::color::stderr())
             .with_writer(std::io::stderr)
             .json()
     }
@@ -115,7 +165,7 @@ impl Instrumentation {
         S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
     {
         tracing_subscriber::fmt::Layer::new()
-            .with_ansi(std::io::stderr().is_terminal())
+            .with_ansi(super::color::stderr())
             .with_writer(std::io::stderr)
             .compact()
             .without_time()
@@ -1,6 +1,10 @@
 pub(crate) mod cmd;
+pub(crate) mod color;
 mod error;
 pub(crate) mod instrumentation;
+pub(crate) mod retry;
+pub(crate) mod timeout;
+pub(crate) mod token;
 
 /// fh: a CLI for interacting with FlakeHub
 #[derive(clap::Parser)]
@@ -42,9 +46,70 @@ pub(crate) struct Cli {
     )]
     pub frontend_addr: url::Url,
 
+    /// Print each Nix command to stderr before running it.
+    #[clap(global = true, long, env = "FH_PRINT_COMMANDS")]
+    pub print_commands: bool,
+
+    /// When to use colors in output. `NO_COLOR` forces this to `never`.
+    #[clap(global = true, long, value_enum, default_value_t = color::ColorChoice::Auto, env = "FH_COLOR")]
+    pub color: color::ColorChoice,
+
+    /// Where to read the FlakeHub token from. `auto` tries `FH_TOKEN`, then the user's token file,
+    /// then the token shared by every user on the machine.
+    #[clap(global = true, long, value_enum, default_value_t = token::TokenSource::Auto, env = "FH_TOKEN_SOURCE")]
+    pub token_source: token::TokenSource,
+
+    /// How many times to retry a FlakeHub request that fails with a connection error, a gateway
+    /// error (502, 503, or 504), or rate limiting (429), waiting longer before each attempt.
+    #[clap(global = true, long, default_value_t = retry::DEFAULT_RETRIES, env = "FH_RETRIES")]
+    pub retries: u32,
+
+    /// How many seconds to wait for each request to FlakeHub before giving up.
+    #[clap(global = true, long, value_name = "SECONDS", default_value_t = timeout::DEFAULT_TIMEOUT_SECS, value_parser = clap::value_parser!(u64).range(1..), env = "FH_TIMEOUT")]
+    pub timeout: u64,
+
+    /// Print the settings fh would run with, including the source the FlakeHub token would be
+    /// read from, as JSON, and exit without running the command.
+    #[clap(global = true, long)]
+    pub dump_config: bool,
+
     #[clap(subcommand)]
     pub subcommand: cmd::FhSubcommands,
 
     #[clap(flatten)]
     pub instrumentation: instrumentation::Instrumentation,
 }
+
+/// The settings that fh runs with, as printed by `--dump-config`.
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct EffectiveConfig {
+    api_addr: String,
+    cache_addr: String,
+    frontend_addr: String,
+    print_commands: bool,
+    color: String,
+    token_source: String,
+    /// The source the token was actually found in, which only differs from `token_source` for
+    /// `auto`
+    token_found_in: Option<String>,
+    retries: u32,
+    timeout: u64,
+}
+
+impl Cli {
+    pub(crate) async fn effective_config(&self) -> EffectiveConfig {
+        let token_found_in = token::read_token().await.ok().flatten();
+
+        EffectiveConfig {
+            api_addr: self.api_addr.to_string(),
+            cache_addr: self.cache_addr.to_string(),
+            frontend_addr: self.frontend_addr.to_string(),
+            print_commands: self.print_commands,
+            color: self.color.to_string(),
+            token_source: self.token_source.to_string(),
+            token_found_in: token_found_in.map(|token| token.source.to_string()),
+            retries: self.retries,
+            timeout: self.timeout,
+        }
+    }
+}
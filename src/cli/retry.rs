@@ -0,0 +1,197 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use reqwest::{header::RETRY_AFTER, RequestBuilder, Response, StatusCode};
+
+use crate::cli::error::FhError;
+
+/// How many times to retry a FlakeHub request when `--retries` isn't given.
+pub(crate) const DEFAULT_RETRIES: u32 = 3;
+
+// The wait before the first retry, which doubles for each one after it
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+// The longest fh will wait between attempts, even if FlakeHub asks for longer
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+static RETRIES: OnceLock<u32> = OnceLock::new();
+
+/// Record how many times to retry failed requests for the rest of the process. Only the first
+/// call has an effect.
+pub fn init(retries: u32) {
+    let _ = RETRIES.set(retries);
+}
+
+fn retries() -> u32 {
+    RETRIES.get().copied().unwrap_or(DEFAULT_RETRIES)
+}
+
+/// Send a request, retrying connection errors, gateway errors (502, 503, and 504), and rate
+/// limiting (429) with exponential backoff. Any other response, including other 4xx errors, is
+/// returned as is. Timeouts aren't retried, since every attempt would take the whole `--timeout`.
+pub(crate) async fn send(request: RequestBuilder) -> Result<Response, FhError> {
+    Ok(send_with_backoff(request, retries(), BASE_DELAY).await?)
+}
+
+async fn send_with_backoff(
+    request: RequestBuilder,
+    retries: u32,
+    base_delay: Duration,
+) -> Result<Response, reqwest::Error> {
+    let mut attempt = 0;
+
+    loop {
+        // Requests with streaming bodies can't be cloned, and so can't be retried either
+        let Some(this_request) = request.try_clone() else {
+            return request.send().await;
+        };
+
+        let backoff = base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_DELAY);
+
+        let delay = match this_request.send().await {
+            Ok(response) if attempt < retries && is_transient(response.status()) => {
+                tracing::debug!(
+                    "FlakeHub responded with {}; retrying ({} of {retries})",
+                    response.status(),
+                    attempt + 1
+                );
+                retry_after(&response).unwrap_or(backoff)
+            }
+            Err(e) if attempt < retries && e.is_connect() && !e.is_timeout() => {
+                tracing::debug!(
+                    "Request to FlakeHub failed: {e}; retrying ({} of {retries})",
+                    attempt + 1
+                );
+                backoff
+            }
+            result => return result,
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+// The wait that a 429 asked for, in seconds. (`Retry-After` may also be an HTTP date, which isn't
+// worth parsing here; the usual backoff is used then.)
+fn retry_after(response: &Response) -> Option<Duration> {
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let seconds = response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+
+    Some(Duration::from_secs(seconds).min(MAX_DELAY))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+
+    // Responds with `failure` to the first `failures` requests, and with 200 after that
+    fn test_server(
+        failures: usize,
+        failure: StatusCode,
+    ) -> (axum_test::TestServer, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let router = axum::Router::new().route(
+            "/",
+            axum::routing::get(move || {
+                let counter = counter.clone();
+                async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                        failure
+                    } else {
+                        StatusCode::OK
+                    }
+                }
+            }),
+        );
+
+        (crate::cli::cmd::test_server(router), requests)
+    }
+
+    async fn send(server: &axum_test::TestServer, retries: u32) -> StatusCode {
+        let url = server.server_address().unwrap();
+        let request = reqwest::Client::new().get(url);
+
+        let response = super::send_with_backoff(request, retries, Duration::from_millis(1))
+            .await
+            .unwrap();
+        StatusCode::from_u16(response.status().as_u16()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let (server, requests) = test_server(2, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(send(&server, 3).await, StatusCode::OK);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_retries() {
+        let (server, requests) = test_server(5, StatusCode::BAD_GATEWAY);
+        assert_eq!(send(&server, 2).await, StatusCode::BAD_GATEWAY);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_not_retried() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let router = axum::Router::new().route(
+            "/",
+            axum::routing::get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    StatusCode::OK
+                }
+            }),
+        );
+        let server = crate::cli::cmd::test_server(router);
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let request = client.get(server.server_address().unwrap());
+
+        let err = super::send_with_backoff(request, 3, Duration::from_millis(1))
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (server, requests) = test_server(1, StatusCode::NOT_FOUND);
+        assert_eq!(send(&server, 3).await, StatusCode::NOT_FOUND);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,21 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How many seconds to wait for FlakeHub when `--timeout` isn't given.
+pub(crate) const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+static TIMEOUT_SECS: OnceLock<u64> = OnceLock::new();
+
+/// Record the request timeout for the rest of the process. Only the first call has an effect.
+pub fn init(seconds: u64) {
+    let _ = TIMEOUT_SECS.set(seconds);
+}
+
+pub(crate) fn seconds() -> u64 {
+    TIMEOUT_SECS.get().copied().unwrap_or(DEFAULT_TIMEOUT_SECS)
+}
+
+/// How long a single request to FlakeHub may take, from connecting to reading the whole response.
+pub(crate) fn timeout() -> Duration {
+    Duration::from_secs(seconds())
+}
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::cli::{cmd::login::dnixd_uds_at, error::FhError};
+
+/// The environment variable that a FlakeHub token can be passed in.
+pub(crate) const TOKEN_ENV_VAR: &str = "FH_TOKEN";
+
+static TOKEN_SOURCE: OnceLock<TokenSource> = OnceLock::new();
+
+/// Where fh reads the FlakeHub token from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TokenSource {
+    /// The first of `env`, `file`, and `global` that has a token
+    #[default]
+    Auto,
+    /// The `FH_TOKEN` environment variable
+    Env,
+    /// The user's own token file, written by `fh login`
+    File,
+    /// The token shared by every user on the machine, written by `fh login --system`
+    Global,
+    /// The token managed by determinate-nixd, which is only used while determinate-nixd is running
+    /// and answering
+    Dnixd,
+}
+
+impl std::fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TokenSource::Auto => "auto",
+                TokenSource::Env => "env",
+                TokenSource::File => "file",
+                TokenSource::Global => "global",
+                TokenSource::Dnixd => "dnixd",
+            }
+        )
+    }
+}
+
+// The order that `auto` tries the other sources in. determinate-nixd keeps its token in the same
+// place as `global`, so it doesn't need a turn of its own.
+const AUTO_PRECEDENCE: &[TokenSource] = &[TokenSource::Env, TokenSource::File, TokenSource::Global];
+
+/// Record the user's token source for the rest of the process. Only the first call has an effect.
+pub fn init(source: TokenSource) {
+    let _ = TOKEN_SOURCE.set(source);
+}
+
+pub(crate) fn source() -> TokenSource {
+    TOKEN_SOURCE.get().copied().unwrap_or_default()
+}
+
+/// Where fh looked for the token, for explaining a token that FlakeHub rejected.
+pub(crate) fn precedence_hint() -> String {
+    match source() {
+        TokenSource::Auto => format!(
+            "fh uses the first token it finds in `{TOKEN_ENV_VAR}`, then the token file written by \
+            `fh login`, then the token shared by every user on the machine; make sure that one is \
+            still valid"
+        ),
+        source => format!(
+            "fh used the token from the `{source}` token source, as chosen with `--token-source`; \
+            make sure it is still valid"
+        ),
+    }
+}
+
+/// A FlakeHub token, and the source it was read from.
+pub(crate) struct Token {
+    pub(crate) token: String,
+    pub(crate) source: TokenSource,
+}
+
+// The user's own token file, relative to `$XDG_CONFIG_HOME`
+const USER_TOKEN_FILE: &str = "flakehub/auth";
+
+/// The user's own token file, `$XDG_CONFIG_HOME/flakehub/auth` (basically
+/// `~/.config/flakehub/auth`). Every subcommand finds the token through this, or
+/// [`user_token_write_path`] when it's about to write one.
+pub(crate) fn user_token_read_path() -> Result<PathBuf, FhError> {
+    Ok(xdg::BaseDirectories::new()?.get_config_file(USER_TOKEN_FILE))
+}
+
+/// The same file as [`user_token_read_path`], but creates the directory that it goes in.
+pub(crate) fn user_token_write_path() -> Result<PathBuf, FhError> {
+    Ok(xdg::BaseDirectories::new()?.place_config_file(USER_TOKEN_FILE)?)
+}
+
+/// The token shared by every user on the machine, written by `fh login --system` or
+/// determinate-nixd.
+pub(crate) fn system_token_path() -> PathBuf {
+    Path::new(crate::DETERMINATE_STATE_DIR).join(crate::DETERMINATE_NIXD_TOKEN_NAME)
+}
+
+/// Read the FlakeHub token from the chosen source. With `auto`, having no token at all isn't an
+/// error, but a source that was chosen explicitly has to have one.
+pub(crate) async fn read_token() -> Result<Option<Token>, FhError> {
+    let locations = TokenLocations {
+        env: std::env::var(TOKEN_ENV_VAR).ok(),
+        file: user_token_read_path().ok(),
+        global: system_token_path(),
+        dnixd_socket: Path::new(crate::DETERMINATE_STATE_DIR)
+            .join(crate::DETERMINATE_NIXD_SOCKET_NAME),
+    };
+
+    locations.read(source()).await
+}
+
+struct TokenLocations {
+    env: Option<String>,
+    file: Option<PathBuf>,
+    global: PathBuf,
+    dnixd_socket: PathBuf,
+}
+
+impl TokenLocations {
+    async fn read(&self, source: TokenSource) -> Result<Option<Token>, FhError> {
+        if source != TokenSource::Auto {
+            return match self.read_from(source).await {
+                Some(token) => Ok(Some(Token { token, source })),
+                None => Err(FhError::MissingToken(source.to_string())),
+            };
+        }
+
+        for &source in AUTO_PRECEDENCE {
+            if let Some(token) = self.read_from(source).await {
+                tracing::debug!("Using the FlakeHub token from the `{source}` token source");
+                return Ok(Some(Token { token, source }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn read_from(&self, source: TokenSource) -> Option<String> {
+        let token = match source {
+            TokenSource::Auto => return None,
+            TokenSource::Env => self.env.clone()?,
+            TokenSource::File => tokio::fs::read_to_string(self.file.as_ref()?).await.ok()?,
+            TokenSource::Global => tokio::fs::read_to_string(&self.global).await.ok()?,
+            TokenSource::Dnixd => {
+                // A socket that's left over from a determinate-nixd that isn't running anymore
+                // doesn't count
+                if let Err(err) = dnixd_uds_at(&self.dnixd_socket).await {
+                    tracing::debug!("Not using determinate-nixd's token: {err}");
+                    return None;
+                }
+
+                tokio::fs::read_to_string(&self.global).await.ok()?
+            }
+        };
+
+        let token = token.trim();
+        (!token.is_empty()).then(|| token.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TokenLocations, TokenSource};
+    use crate::cli::error::FhError;
+
+    async fn read(
+        locations: &TokenLocations,
+        source: TokenSource,
+    ) -> Result<Option<(String, TokenSource)>, FhError> {
+        let token = locations.read(source).await?;
+        Ok(token.map(|token| (token.token, token.source)))
+    }
+
+    // Answers determinate-nixd's info request on a socket at `path`, like a running determinate-nixd
+    fn serve_dnixd(path: &std::path::Path) -> tokio::task::JoinHandle<()> {
+        let listener = tokio::net::UnixListener::bind(path).unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let service = hyper::service::service_fn(|_| async {
+                    Ok::<_, std::convert::Infallible>(hyper::Response::new(
+                        axum::body::Body::empty(),
+                    ))
+                });
+                tokio::spawn(
+                    hyper::server::conn::http1::Builder::new()
+                        .serve_connection(hyper_util::rt::TokioIo::new(stream), service),
+                );
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn token_source_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("auth");
+        let global = dir.path().join("token");
+        std::fs::write(&file, "file-token\n").unwrap();
+        std::fs::write(&global, "global-token\n").unwrap();
+
+        let mut locations = TokenLocations {
+            env: Some(String::from("env-token")),
+            file: Some(file.clone()),
+            global,
+            dnixd_socket: dir.path().join("determinate-nixd.socket"),
+        };
+
+        assert_eq!(
+            read(&locations, TokenSource::Auto).await.unwrap(),
+            Some((String::from("env-token"), TokenSource::Env))
+        );
+        assert_eq!(
+            read(&locations, TokenSource::File).await.unwrap(),
+            Some((String::from("file-token"), TokenSource::File))
+        );
+
+        // An empty environment variable doesn't count as a token
+        locations.env = Some(String::new());
+        assert_eq!(
+            read(&locations, TokenSource::Auto).await.unwrap(),
+            Some((String::from("file-token"), TokenSource::File))
+        );
+        assert!(matches!(
+            read(&locations, TokenSource::Env).await,
+            Err(FhError::MissingToken(source)) if source == "env"
+        ));
+
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(
+            read(&locations, TokenSource::Auto).await.unwrap(),
+            Some((String::from("global-token"), TokenSource::Global))
+        );
+        assert!(matches!(
+            read(&locations, TokenSource::File).await,
+            Err(FhError::MissingToken(source)) if source == "file"
+        ));
+
+        // determinate-nixd's token is only used while it's running, not just when its socket is
+        // left behind
+        assert!(read(&locations, TokenSource::Dnixd).await.is_err());
+        std::fs::write(&locations.dnixd_socket, "").unwrap();
+        assert!(read(&locations, TokenSource::Dnixd).await.is_err());
+        std::fs::remove_file(&locations.dnixd_socket).unwrap();
+        let dnixd = serve_dnixd(&locations.dnixd_socket);
+        assert_eq!(
+            read(&locations, TokenSource::Dnixd).await.unwrap(),
+            Some((String::from("global-token"), TokenSource::Dnixd))
+        );
+        dnixd.abort();
+
+        std::fs::remove_file(&locations.global).unwrap();
+        assert_eq!(read(&locations, TokenSource::Auto).await.unwrap(), None);
+    }
+}
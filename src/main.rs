@@ -1,8 +1,6 @@
 pub(crate) mod cli;
 pub(crate) mod shared;
 
-use std::io::IsTerminal;
-
 use clap::Parser;
 
 use crate::cli::{
@@ -18,32 +16,68 @@ static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_P
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<std::process::ExitCode> {
+    let cli = Cli::parse();
+    cli::color::init(cli.color);
+    cli::token::init(cli.token_source);
+    match cli.subcommand {
+        FhSubcommands::CompleteFlakes(_) | FhSubcommands::CompleteVersions(_) => {
+            cli::retry::init(0);
+            cli::timeout::init(
+                cli.timeout
+                    .min(cli::cmd::completion::COMPLETION_TIMEOUT_SECS),
+            );
+        }
+        _ => {
+            cli::retry::init(cli.retries);
+            cli::timeout::init(cli.timeout);
+        }
+    }
+
     color_eyre::config::HookBuilder::default()
         .issue_url(concat!(env!("CARGO_PKG_REPOSITORY"), "/issues/new"))
         .add_issue_metadata("version", env!("CARGO_PKG_VERSION"))
         .add_issue_metadata("os", std::env::consts::OS)
         .add_issue_metadata("arch", std::env::consts::ARCH)
-        .theme(if !std::io::stderr().is_terminal() {
+        .theme(if !cli::color::stderr() {
             color_eyre::config::Theme::new()
         } else {
             color_eyre::config::Theme::dark()
         })
         .install()?;
 
-    let cli = Cli::parse();
     cli.instrumentation.setup().await?;
 
+    if cli.dump_config {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&cli.effective_config().await)?
+        );
+        return Ok(std::process::ExitCode::SUCCESS);
+    }
+
     match cli.subcommand {
         FhSubcommands::Add(add) => add.execute().await,
         FhSubcommands::Apply(apply) => apply.execute().await,
+        FhSubcommands::Cache(cache) => cache.execute().await,
         FhSubcommands::Completion(completion) => completion.execute().await,
+        FhSubcommands::CompleteFlakes(complete_flakes) => complete_flakes.execute().await,
+        FhSubcommands::CompleteVersions(complete_versions) => complete_versions.execute().await,
         FhSubcommands::Convert(convert) => convert.execute().await,
+        FhSubcommands::Doctor(doctor) => doctor.execute().await,
         FhSubcommands::Eject(eject) => eject.execute().await,
+        FhSubcommands::Fetch(fetch) => fetch.execute().await,
         FhSubcommands::Init(init) => init.execute().await,
         FhSubcommands::List(list) => list.execute().await,
+        FhSubcommands::LockStatus(lock_status) => lock_status.execute().await,
         FhSubcommands::Login(login) => login.execute().await,
+        FhSubcommands::Logout(logout) => logout.execute().await,
+        FhSubcommands::Open(open) => open.execute().await,
+        FhSubcommands::Ping(ping) => ping.execute().await,
+        FhSubcommands::Remove(remove) => remove.execute().await,
         FhSubcommands::Resolve(resolve) => resolve.execute().await,
         FhSubcommands::Search(search) => search.execute().await,
         FhSubcommands::Status(status) => status.execute().await,
+        FhSubcommands::Update(update) => update.execute().await,
+        FhSubcommands::Whoami(whoami) => whoami.execute().await,
     }
 }
@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, WrapErr};
+use serde_json::{Map, Value};
+
+/// A `flake.lock`, kept as plain JSON so that fields we don't know about survive a round trip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlakeLock {
+    contents: Value,
+}
+
+impl FlakeLock {
+    pub fn parse(contents: &str) -> color_eyre::Result<Self> {
+        let contents: Value =
+            serde_json::from_str(contents).wrap_err("Parsing flake.lock as JSON")?;
+
+        if !contents["nodes"].is_object() {
+            return Err(eyre!("flake.lock has no `nodes`"));
+        }
+
+        Ok(Self { contents })
+    }
+
+    pub async fn read(path: &Path) -> color_eyre::Result<Self> {
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+
+        Self::parse(&contents)
+    }
+
+    pub async fn write(&self, path: &Path) -> color_eyre::Result<()> {
+        tokio::fs::write(path, self.render()?)
+            .await
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Render the lock the way Nix does: two-space indented JSON with a trailing newline.
+    pub fn render(&self) -> color_eyre::Result<String> {
+        let mut rendered = serde_json::to_string_pretty(&self.contents)?;
+        rendered.push('\n');
+
+        Ok(rendered)
+    }
+
+    fn nodes(&self) -> Option<&Map<String, Value>> {
+        self.contents["nodes"].as_object()
+    }
+
+    /// The name of the node that the root flake's input `input_name` is locked to. Inputs that
+    /// `follows` another input don't have a node of their own, so they return `None`.
+    pub fn root_input_node(&self, input_name: &str) -> Option<&str> {
+        let root = self.contents["root"].as_str().unwrap_or("root");
+
+        self.nodes()?
+            .get(root)?
+            .get("inputs")?
+            .get(input_name)?
+            .as_str()
+    }
+
+    /// The names of the root flake's inputs, including those that `follows` another input.
+    pub fn root_input_names(&self) -> Vec<&str> {
+        let root = self.contents["root"].as_str().unwrap_or("root");
+
+        self.nodes()
+            .and_then(|nodes| nodes.get(root)?.get("inputs")?.as_object())
+            .map(|inputs| inputs.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// The `locked` reference that the root flake's input is pinned to.
+    pub fn root_input_locked(&self, input_name: &str) -> Option<&Value> {
+        let node = self.root_input_node(input_name)?;
+
+        self.nodes()?.get(node)?.get("locked")
+    }
+
+    /// The `original` (unlocked) reference that the root flake's input was locked from.
+    pub fn root_input_original(&self, input_name: &str) -> Option<&Value> {
+        let node = self.root_input_node(input_name)?;
+
+        self.nodes()?.get(node)?.get("original")
+    }
+
+    /// Point the root flake's input `input_name` at the tarball `url` without touching what it's
+    /// locked to, so Nix keeps using the locked `rev` and `narHash` instead of re-locking it.
+    ///
+    /// Returns whether the lock changed.
+    pub fn set_root_input_tarball_url(
+        &mut self,
+        input_name: &str,
+        url: &url::Url,
+    ) -> color_eyre::Result<bool> {
+        let Some(node) = self.root_input_node(input_name).map(ToString::to_string) else {
+            return Ok(false);
+        };
+
+        let original = tarball_original(url);
+        if self.root_input_original(input_name) == Some(&original) {
+            return Ok(false);
+        }
+
+        let node = self.contents["nodes"]
+            .get_mut(&node)
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| eyre!("flake.lock node `{node}` is missing or malformed"))?;
+        node.insert(String::from("original"), original);
+
+        Ok(true)
+    }
+}
+
+fn tarball_original(url: &url::Url) -> Value {
+    // Nix percent-encodes `*` when it writes tarball URLs into the lock, and only reuses a lock
+    // entry when its `original` matches the flake's input exactly
+    let url = url.as_str().replace('*', "%2A");
+
+    serde_json::json!({
+        "type": "tarball",
+        "url": url,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::FlakeLock;
+
+    const SAMPLE_LOCK: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/samples/lock1.test.json"
+    ));
+
+    #[test]
+    fn root_inputs() {
+        let lock = FlakeLock::parse(SAMPLE_LOCK).unwrap();
+
+        assert_eq!(lock.root_input_node("nixpkgs"), Some("nixpkgs"));
+        assert_eq!(lock.root_input_node("fh"), Some("fh"));
+        assert_eq!(lock.root_input_node("does-not-exist"), None);
+        assert_eq!(
+            lock.root_input_names(),
+            ["fh", "nixpkgs", "nixpkgs-follower"]
+        );
+        assert_eq!(lock.root_input_locked("fh").unwrap()["repo"], "fh");
+        assert_eq!(lock.root_input_locked("nixpkgs-follower"), None);
+        assert_eq!(
+            lock.root_input_original("nixpkgs").unwrap()["type"],
+            "github"
+        );
+    }
+
+    #[test]
+    fn rewrite_original_preserves_locked_rev() {
+        let mut lock = FlakeLock::parse(SAMPLE_LOCK).unwrap();
+        let locked_before = lock.contents["nodes"]["nixpkgs"]["locked"].clone();
+
+        let url = url::Url::parse("https://flakehub.com/f/NixOS/nixpkgs/0.2305.*.tar.gz").unwrap();
+        assert!(lock.set_root_input_tarball_url("nixpkgs", &url).unwrap());
+        // Nothing left to do the second time around
+        assert!(!lock.set_root_input_tarball_url("nixpkgs", &url).unwrap());
+
+        let nixpkgs = &lock.contents["nodes"]["nixpkgs"];
+        assert_eq!(nixpkgs["locked"], locked_before);
+        assert_eq!(nixpkgs["original"]["type"], "tarball");
+        assert_eq!(
+            nixpkgs["original"]["url"],
+            "https://flakehub.com/f/NixOS/nixpkgs/0.2305.%2A.tar.gz"
+        );
+
+        let rendered = lock.render().unwrap();
+        assert!(rendered.ends_with("}\n"));
+        assert_eq!(FlakeLock::parse(&rendered).unwrap(), lock);
+    }
+
+    #[test]
+    fn follows_inputs_are_skipped() {
+        let mut lock = FlakeLock::parse(SAMPLE_LOCK).unwrap();
+        let before = lock.clone();
+
+        let url = url::Url::parse("https://flakehub.com/f/NixOS/nixpkgs/*.tar.gz").unwrap();
+        assert!(!lock
+            .set_root_input_tarball_url("nixpkgs-follower", &url)
+            .unwrap());
+        assert_eq!(lock, before);
+    }
+}
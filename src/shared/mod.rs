@@ -1,13 +1,12 @@
+pub mod flake_lock;
+pub mod nix_info;
+pub mod progress;
+
 use std::path::Path;
 
 use color_eyre::eyre::Context as _;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
-pub struct DaemonInfoReponse {
-    pub supported_features: Vec<String>,
-}
-
 #[derive(Deserialize, Serialize)]
 pub struct NetrcTokenAddRequest {
     pub token: String,
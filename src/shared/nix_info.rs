@@ -0,0 +1,134 @@
+use once_cell::sync::Lazy;
+use tokio::sync::OnceCell;
+
+static NIX_VERSION_REGEX: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(
+        r"^nix \((?<flavor>Determinate Nix(?: (?<determinate_version>[[:digit:]][^) ]*))?|Nix)\) (?<version>[[:digit:]]+\.[[:digit:]]+(?:\.[[:digit:]]+)?)",
+    )
+    .unwrap()
+});
+
+static NIX_INFO: OnceCell<Option<NixInfo>> = OnceCell::const_new();
+
+/// What we know about the Nix installation on this machine, as reported by `nix --version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NixInfo {
+    /// The upstream Nix version, e.g. `2.26.3`.
+    pub version: semver::Version,
+    /// Whether this is Determinate Nix rather than upstream Nix.
+    pub determinate: bool,
+    /// The Determinate Nix version, e.g. `3.0.0`, if it was reported.
+    pub determinate_version: Option<String>,
+}
+
+impl NixInfo {
+    /// Parse the first line of `nix --version` output, e.g. `nix (Nix) 2.24.10` or
+    /// `nix (Determinate Nix 3.0.0) 2.26.3`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let captures = NIX_VERSION_REGEX.captures(output.lines().next()?.trim())?;
+
+        let version = captures.name("version")?.as_str();
+        // Some Nix releases only report `major.minor`
+        let version = if version.matches('.').count() == 1 {
+            semver::Version::parse(&format!("{version}.0")).ok()?
+        } else {
+            semver::Version::parse(version).ok()?
+        };
+
+        Some(Self {
+            version,
+            determinate: captures
+                .name("flavor")?
+                .as_str()
+                .starts_with("Determinate Nix"),
+            determinate_version: captures
+                .name("determinate_version")
+                .map(|v| v.as_str().to_string()),
+        })
+    }
+
+    /// Whether `nix copy` accepts `--out-link` (https://github.com/NixOS/nix/pull/11657), which
+    /// lets us GC root a store path the moment it's copied.
+    pub fn copy_supports_out_link(&self) -> bool {
+        self.version >= semver::Version::new(2, 26, 0)
+    }
+
+    /// Whether FlakeHub URLs can be used without the `.tar.gz` suffix. Determinate Nix recognizes
+    /// tarballs served by FlakeHub regardless of their extension.
+    pub fn supports_bare_tarball_urls(&self) -> bool {
+        self.determinate
+    }
+}
+
+/// Run `nix --version` (at most once per process) and return what it told us, or `None` if Nix
+/// isn't available or its output couldn't be understood.
+#[cfg(not(test))]
+pub async fn nix_info() -> Option<&'static NixInfo> {
+    NIX_INFO
+        .get_or_init(|| async {
+            let output = crate::cli::cmd::nix_process("nix")
+                .inspect_err(|e| tracing::debug!("{e}"))
+                .ok()?
+                .arg("--version")
+                .output()
+                .await
+                .ok()?;
+
+            if !output.status.success() {
+                tracing::debug!("`nix --version` exited with {}", output.status);
+                return None;
+            }
+
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let info = NixInfo::parse(&stdout);
+            tracing::debug!("Detected Nix: {:?}", info);
+
+            info
+        })
+        .await
+        .as_ref()
+}
+
+/// Tests shouldn't depend on which Nix (if any) is installed on the machine running them.
+#[cfg(test)]
+pub async fn nix_info() -> Option<&'static NixInfo> {
+    NIX_INFO.get_or_init(|| async { None }).await.as_ref()
+}
+
+#[cfg(test)]
+mod test {
+    use super::NixInfo;
+
+    #[test]
+    fn parse_upstream_nix_version() {
+        let info = NixInfo::parse("nix (Nix) 2.24.10\n").unwrap();
+        assert_eq!(info.version, semver::Version::new(2, 24, 10));
+        assert!(!info.determinate);
+        assert_eq!(info.determinate_version, None);
+        assert!(!info.copy_supports_out_link());
+        assert!(!info.supports_bare_tarball_urls());
+    }
+
+    #[test]
+    fn parse_determinate_nix_version() {
+        let info = NixInfo::parse("nix (Determinate Nix 3.0.0) 2.26.3\n").unwrap();
+        assert_eq!(info.version, semver::Version::new(2, 26, 3));
+        assert!(info.determinate);
+        assert_eq!(info.determinate_version.as_deref(), Some("3.0.0"));
+        assert!(info.copy_supports_out_link());
+        assert!(info.supports_bare_tarball_urls());
+    }
+
+    #[test]
+    fn parse_prerelease_and_short_versions() {
+        let info = NixInfo::parse("nix (Nix) 2.26.0pre20241108_4ed8a6d\n").unwrap();
+        assert_eq!(info.version, semver::Version::new(2, 26, 0));
+        assert!(!info.determinate);
+
+        let info = NixInfo::parse("nix (Nix) 2.3\n").unwrap();
+        assert_eq!(info.version, semver::Version::new(2, 3, 0));
+
+        assert_eq!(NixInfo::parse("nix: command not found"), None);
+        assert_eq!(NixInfo::parse(""), None);
+    }
+}
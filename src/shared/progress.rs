@@ -0,0 +1,153 @@
+use std::io::{IsTerminal as _, Write as _};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+
+/// How to report progress for long-running operations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressFormat {
+    /// Human-readable log messages
+    #[default]
+    Human,
+    /// Newline-delimited JSON events on stderr, for wrappers and GUI frontends
+    Json,
+}
+
+impl std::fmt::Display for ProgressFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ProgressFormat::Human => "human",
+                ProgressFormat::Json => "json",
+            }
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ProgressEvent<'a> {
+    pub phase: &'a str,
+    pub percent: u8,
+    pub message: &'a str,
+}
+
+impl ProgressEvent<'_> {
+    pub fn to_json_line(&self) -> Result<String, serde_json::Error> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+
+        Ok(line)
+    }
+}
+
+/// Reports the phases of a long-running operation, either as log messages or as JSON events.
+#[derive(Debug, Clone, Copy)]
+pub struct Reporter {
+    format: ProgressFormat,
+}
+
+impl Reporter {
+    pub fn new(format: ProgressFormat) -> Self {
+        Self { format }
+    }
+
+    pub fn phase(&self, phase: &str, percent: u8, message: &str) {
+        match self.format {
+            ProgressFormat::Human => tracing::info!("{message}"),
+            ProgressFormat::Json => {
+                let event = ProgressEvent {
+                    phase,
+                    percent: percent.min(100),
+                    message,
+                };
+
+                match event.to_json_line() {
+                    Ok(line) => {
+                        // Progress is best-effort; a closed stderr shouldn't fail the operation
+                        let _ = std::io::stderr().lock().write_all(line.as_bytes());
+                    }
+                    Err(e) => tracing::debug!("Failed to serialize progress event: {e}"),
+                }
+            }
+        }
+    }
+
+    pub fn done(&self, message: &str) {
+        self.phase("done", 100, message);
+    }
+}
+
+// The spinner that's drawn right now, if any, so that other output can be printed above it
+static ACTIVE_SPINNER: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// A spinner on stderr that shows what fh is waiting on, like a `nix copy`, until it's dropped.
+/// Nothing is drawn when stderr isn't a terminal, or with `--quiet`. Anything else written to
+/// stderr in the meantime needs to go through [`eprintln_above_spinner`].
+pub struct Spinner(Option<ProgressBar>);
+
+impl Spinner {
+    pub fn start(message: String) -> Self {
+        if !std::io::stderr().is_terminal() || crate::cli::instrumentation::quiet() {
+            return Self(None);
+        }
+
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(ProgressStyle::default_spinner());
+        pb.set_message(message);
+        pb.enable_steady_tick(Duration::from_millis(100));
+        *ACTIVE_SPINNER.lock().expect("spinner lock poisoned") = Some(pb.clone());
+
+        Self(Some(pb))
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        // Clear the line so that the spinner doesn't linger under Nix's output
+        if let Some(pb) = &self.0 {
+            ACTIVE_SPINNER.lock().expect("spinner lock poisoned").take();
+            pb.finish_and_clear();
+        }
+    }
+}
+
+/// Prints a line to stderr, above the spinner if one is drawn, so that a line like Nix's output
+/// doesn't end up mixed with the spinner's.
+pub fn eprintln_above_spinner(line: &str) {
+    match &*ACTIVE_SPINNER.lock().expect("spinner lock poisoned") {
+        Some(pb) => pb.println(line),
+        None => eprintln!("{line}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProgressEvent;
+
+    #[test]
+    fn progress_event_json_line() {
+        let event = ProgressEvent {
+            phase: "resolve",
+            percent: 0,
+            message: "Resolving NixOS/nixpkgs/*#hello",
+        };
+
+        let line = event.to_json_line().unwrap();
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.lines().count(), 1);
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "phase": "resolve",
+                "percent": 0,
+                "message": "Resolving NixOS/nixpkgs/*#hello",
+            })
+        );
+    }
+}